@@ -19,6 +19,7 @@ trait BenchBuf: Buf + BufMut {
     fn construct() -> Self;
     fn construct_with_profile(chunk_size: usize, cnt: usize) -> Self;
     fn put_bytes(&mut self, bytes: Bytes);
+    fn find_byte(&self, byte: u8) -> Option<usize>;
 
     fn produce(&mut self, mut cnt: usize) {
         while cnt != 0 {
@@ -94,6 +95,10 @@ impl BenchBuf for loosely::ChunkedBytes {
     fn put_bytes(&mut self, bytes: Bytes) {
         self.put_bytes(bytes)
     }
+
+    fn find_byte(&self, byte: u8) -> Option<usize> {
+        self.find_byte(byte)
+    }
 }
 
 impl BenchBuf for strictly::ChunkedBytes {
@@ -108,6 +113,10 @@ impl BenchBuf for strictly::ChunkedBytes {
     fn put_bytes(&mut self, bytes: Bytes) {
         self.put_bytes(bytes)
     }
+
+    fn find_byte(&self, byte: u8) -> Option<usize> {
+        self.find_byte(byte)
+    }
 }
 
 impl BenchBuf for BytesMut {
@@ -122,6 +131,10 @@ impl BenchBuf for BytesMut {
     fn put_bytes(&mut self, bytes: Bytes) {
         self.put(bytes)
     }
+
+    fn find_byte(&self, byte: u8) -> Option<usize> {
+        memchr::memchr(byte, self)
+    }
 }
 
 #[generic_tests::define]
@@ -232,7 +245,7 @@ mod benches {
         b.iter(|| {
             let mut salami = Bytes::from(vec![0; chunk_size * cnt]);
             for _ in 0..cnt {
-                buf.put_bytes(salami.split_to(chunk_size));
+                BenchBuf::put_bytes(&mut buf, salami.split_to(chunk_size));
             }
             while buf.has_remaining() {
                 buf.consume_vectored(BUF_SIZE);
@@ -274,7 +287,7 @@ mod benches {
         let v = vec![0; slice_len];
         b.iter(|| {
             buf.put_slice(&v);
-            buf.put_bytes(Bytes::from(vec![0; bytes_len]));
+            BenchBuf::put_bytes(&mut buf, Bytes::from(vec![0; bytes_len]));
             while buf.has_remaining() {
                 buf.consume_vectored(BUF_SIZE);
             }
@@ -291,6 +304,40 @@ mod benches {
         mix_slice_and_bytes::<B>(b, 32, 4096)
     }
 
+    fn split_lines<B: BenchBuf>(
+        b: &mut Bencher,
+        chunk_size: usize,
+        line_len: usize,
+    ) {
+        let mut buf =
+            B::construct_with_profile(chunk_size, BUF_SIZE / chunk_size);
+        let mut text = vec![b'x'; BUF_SIZE];
+        for i in (line_len - 1..BUF_SIZE).step_by(line_len) {
+            text[i] = b'\n';
+        }
+        let text = Bytes::from(text);
+        b.iter(|| {
+            let mut salami = text.clone();
+            while !salami.is_empty() {
+                BenchBuf::put_bytes(&mut buf, salami.split_to(chunk_size));
+            }
+            while let Some(pos) = buf.find_byte(b'\n') {
+                test::black_box(buf.copy_to_bytes(pos + 1));
+            }
+            buf.advance(buf.remaining());
+        });
+    }
+
+    #[bench]
+    fn split_lines_80_in_chunks_of_1024<B: BenchBuf>(b: &mut Bencher) {
+        split_lines::<B>(b, 1024, 80)
+    }
+
+    #[bench]
+    fn split_lines_4000_in_chunks_of_1024<B: BenchBuf>(b: &mut Bencher) {
+        split_lines::<B>(b, 1024, 4000)
+    }
+
     #[instantiate_tests(<loosely::ChunkedBytes>)]
     mod loosely_chunked_bytes {}
 
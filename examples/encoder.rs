@@ -48,10 +48,10 @@ pub struct EncodingWriter<T> {
 
 impl<T: AsyncWrite> EncodingWriter<T> {
     pub fn new(out: T) -> Self {
-        EncodingWriter {
-            buf: ChunkedBytes::new(),
-            out,
-        }
+        let mut buf = ChunkedBytes::new();
+        let chunk_size = buf.chunk_size_hint();
+        buf.set_watermarks(0, chunk_size);
+        EncodingWriter { buf, out }
     }
 
     fn poll_write_buf(
@@ -88,11 +88,12 @@ impl<T: AsyncWrite> Sink<Message> for EncodingWriter<T> {
         cx: &mut Context<'_>,
     ) -> Poll<Result<(), Error>> {
         // Here's a way to provide back-pressure on the sink:
-        // rather than allowing the buffer to grow, drain it until
-        // there is only the staging buffer with room to fill.
-        let chunk_size = self.buf.chunk_size_hint();
-        while self.as_mut().buf.remaining() >= chunk_size {
-            ready!(self.as_mut().poll_write_buf(cx))?;
+        // rather than allowing the buffer to grow past the high watermark,
+        // drain it down to the low watermark.
+        if self.buf.is_above_high_watermark() {
+            while !self.buf.is_below_low_watermark() {
+                ready!(self.as_mut().poll_write_buf(cx))?;
+            }
         }
         Poll::Ready(Ok(()))
     }
@@ -123,7 +124,7 @@ impl<T: AsyncWrite> Sink<Message> for EncodingWriter<T> {
 #[tokio::main]
 async fn main() -> io::Result<()> {
     // Pretend we received the data from input into a Bytes handle
-    let blob = BytesMut::from_iter(iter::repeat(b'\xa5').take(8000));
+    let blob = BytesMut::from_iter(iter::repeat_n(b'\xa5', 8000));
 
     let msg = Message {
         int_field: 42,
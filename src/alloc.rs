@@ -0,0 +1,26 @@
+use bytes::BytesMut;
+
+/// A source of the buffers used for staging in `ChunkedBytes`.
+///
+/// By default, a `ChunkedBytes` container allocates its staging buffers
+/// with the global allocator and grows them in place when possible.
+/// An implementation of this trait set with `set_staging_alloc` supplies
+/// every new staging buffer instead, and is given back the buffers that
+/// the container no longer uses, which allows reusing the blocks across
+/// containers, e.g. with a pool.
+///
+/// Note that the memory of a buffer remains shared with the chunks split
+/// off it until all of them have been dropped. A pool implementation can
+/// reclaim a recycled buffer with `BytesMut::reserve`, which reuses
+/// the allocation if the buffer is no longer shared.
+pub trait StagingAlloc: Send + Sync {
+    /// Returns an empty buffer with the capacity of at least `capacity`.
+    fn allocate(&self, capacity: usize) -> BytesMut;
+
+    /// Takes back an empty buffer that is no longer used for staging.
+    ///
+    /// The default implementation drops the buffer.
+    fn recycle(&self, buf: BytesMut) {
+        drop(buf)
+    }
+}
@@ -0,0 +1,170 @@
+//! Asynchronous I/O helpers for the `futures-io` traits.
+//!
+//! These are the counterparts of the `tokio`-enabled methods
+//! `poll_write_to`, `write_all_to`, and `poll_read_from`, for applications
+//! using `futures::io::AsyncRead` and `futures::io::AsyncWrite`, such as
+//! those running on async-std or smol. They are provided as functions to
+//! avoid conflicts with the inherent methods when both features are enabled,
+//! and work with both variants of `ChunkedBytes`.
+//!
+//! Both variants of `ChunkedBytes` also implement `futures_io::AsyncWrite`
+//! with this feature.
+//!
+//! This module is only available with the `futures-io` feature.
+
+use crate::sync_io::MAX_IO_SLICES;
+use crate::{loosely, strictly};
+
+use bytes::{Buf, BufMut};
+use futures_io::{AsyncRead, AsyncWrite};
+
+use std::cmp::min;
+use std::future::poll_fn;
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::slice;
+use std::task::{Context, Poll};
+
+// Limits the number of bytes zeroed before each read into
+// uninitialized memory.
+const MAX_READ_LEN: usize = 64 * 1024;
+
+/// Attempts to write the data from `buf` to `out` with a single call
+/// to `poll_write_vectored`, advancing the reading position of `buf`
+/// by the number of bytes written.
+///
+/// On success, returns `Poll::Ready(Ok(n))` where `n` is the number
+/// of bytes written, which may be less than the length of the buffered data.
+pub fn poll_write_to<B, W>(
+    buf: &mut B,
+    cx: &mut Context<'_>,
+    out: Pin<&mut W>,
+) -> Poll<io::Result<usize>>
+where
+    B: Buf + ?Sized,
+    W: AsyncWrite + ?Sized,
+{
+    let mut io_bufs = [IoSlice::new(&[]); MAX_IO_SLICES];
+    let io_vec_len = buf.chunks_vectored(&mut io_bufs);
+    match out.poll_write_vectored(cx, &io_bufs[..io_vec_len]) {
+        Poll::Ready(Ok(bytes_written)) => {
+            buf.advance(bytes_written);
+            Poll::Ready(Ok(bytes_written))
+        }
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+/// Writes all of the data from `buf` to `out`, advancing the reading
+/// position of `buf` as the data is written.
+///
+/// An error of kind `ErrorKind::WriteZero` is returned if a write
+/// returns 0 while data remains in `buf`. On any error, the data that
+/// has not been written remains in `buf`.
+pub async fn write_all_to<B, W>(buf: &mut B, out: &mut W) -> io::Result<()>
+where
+    B: Buf + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    while buf.has_remaining() {
+        let n = poll_fn(|cx| poll_write_to(&mut *buf, cx, Pin::new(&mut *out)))
+            .await?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "failed to write the buffered data",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Attempts to read data from `src` directly into the writable space
+/// of `buf`, with a single call to `poll_read`.
+///
+/// For `ChunkedBytes`, space in the staging buffer is reserved in
+/// the same way as when writing through `BufMut`, so complete chunks are
+/// split off as the staging buffer fills up.
+///
+/// On success, returns `Poll::Ready(Ok(n))` where `n` is the number
+/// of bytes read. A return value of 0 means that the reader has reached
+/// end of input, or that `buf` has no more room.
+pub fn poll_read_from<B, R>(
+    buf: &mut B,
+    cx: &mut Context<'_>,
+    src: Pin<&mut R>,
+) -> Poll<io::Result<usize>>
+where
+    B: BufMut + ?Sized,
+    R: AsyncRead + ?Sized,
+{
+    if !buf.has_remaining_mut() {
+        return Poll::Ready(Ok(0));
+    }
+    let dst = buf.chunk_mut();
+    let len = min(dst.len(), MAX_READ_LEN);
+    // Safety: the memory is zeroed before it is exposed
+    // as an initialized slice.
+    let dst = unsafe {
+        let ptr = dst.as_mut_ptr();
+        ptr.write_bytes(0, len);
+        slice::from_raw_parts_mut(ptr, len)
+    };
+    match src.poll_read(cx, dst) {
+        Poll::Ready(Ok(n)) => {
+            assert!(n <= len, "reader reported more bytes than read");
+            // Safety: the first `n` bytes have been initialized
+            // within the slice returned by `chunk_mut`.
+            unsafe { buf.advance_mut(n) };
+            Poll::Ready(Ok(n))
+        }
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+macro_rules! impl_futures_io {
+    ($t:ty) => {
+        /// Appends the written data to the buffer, in the same way as
+        /// the implementation of `std::io::Write`. The buffer is always
+        /// ready to be written to.
+        ///
+        /// This implementation is only available with the `futures-io`
+        /// feature.
+        impl AsyncWrite for $t {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(io::Write::write(self.get_mut(), buf))
+            }
+
+            fn poll_write_vectored(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(io::Write::write_vectored(self.get_mut(), bufs))
+            }
+
+            fn poll_flush(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_close(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
+
+impl_futures_io!(loosely::ChunkedBytes);
+impl_futures_io!(strictly::ChunkedBytes);
@@ -0,0 +1,262 @@
+use crate::sync_io::MAX_IO_SLICES;
+use crate::{loosely, strictly};
+
+use bytes::{Buf, BufMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+use std::cmp::min;
+use std::future::{poll_fn, Future};
+use std::io::{self, IoSlice};
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::slice;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+macro_rules! impl_tokio {
+    ($t:ty) => {
+        impl $t {
+            /// Flushes the staging buffer once the bytes in it have lingered
+            /// there for the duration of `linger`, using `timer` to wait
+            /// for the deadline.
+            ///
+            /// The first call enables recording of the staging time as
+            /// `record_staged_at` does. If the staging buffer is not empty,
+            /// the timer is reset as needed to the deadline computed from
+            /// the value of `staged_at`, and polled. When the deadline
+            /// is reached, the staging buffer is split into a complete
+            /// chunk and `Poll::Ready` is returned.
+            ///
+            /// If the staging buffer is empty, `Poll::Pending` is returned
+            /// without registering the task for wakeup: the task writing into
+            /// the container is expected to poll this method again after
+            /// writing. This fits the use in a task that both writes into
+            /// the buffer and drains complete chunks from it.
+            ///
+            /// This method is only available with the `tokio` feature.
+            pub fn poll_flush_after(
+                &mut self,
+                cx: &mut Context<'_>,
+                mut timer: Pin<&mut Sleep>,
+                linger: Duration,
+            ) -> Poll<()> {
+                self.record_staged_at();
+                let deadline = match self.staged_at() {
+                    Some(staged_at) => staged_at + linger,
+                    None => return Poll::Pending,
+                };
+                if timer.deadline() != deadline {
+                    timer.as_mut().reset(deadline);
+                }
+                match timer.poll(cx) {
+                    Poll::Ready(()) => {
+                        self.flush();
+                        Poll::Ready(())
+                    }
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+
+            /// Attempts to write the buffered data to `out` with a single call
+            /// to `poll_write_vectored`, advancing the reading position by
+            /// the number of bytes written.
+            ///
+            /// On success, returns `Poll::Ready(Ok(n))` where `n` is the number
+            /// of bytes written, which may be less than the length of
+            /// the buffered data.
+            ///
+            /// This method is only available with the `tokio` feature.
+            pub fn poll_write_to<W>(
+                &mut self,
+                cx: &mut Context<'_>,
+                out: Pin<&mut W>,
+            ) -> Poll<io::Result<usize>>
+            where
+                W: AsyncWrite + ?Sized,
+            {
+                poll_write_buf(self, cx, out)
+            }
+
+            /// Writes all of the buffered data to `out`, advancing the
+            /// reading position as the data is written.
+            ///
+            /// An error of kind `ErrorKind::WriteZero` is returned if
+            /// a write returns 0 while data remains in the buffer.
+            /// On any error, the data that has not been written remains
+            /// in the buffer.
+            ///
+            /// This method is only available with the `tokio` feature.
+            pub async fn write_all_to<W>(
+                &mut self,
+                out: &mut W,
+            ) -> io::Result<()>
+            where
+                W: AsyncWrite + Unpin + ?Sized,
+            {
+                while self.has_remaining() {
+                    let n = poll_fn(|cx| {
+                        self.poll_write_to(cx, Pin::new(&mut *out))
+                    })
+                    .await?;
+                    if n == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "failed to write the buffered data",
+                        ));
+                    }
+                }
+                Ok(())
+            }
+
+            /// Attempts to read data from `src` directly into the staging
+            /// buffer, with a single call to `poll_read`.
+            ///
+            /// Space in the staging buffer is reserved in the same way as
+            /// when writing through `BufMut`, so complete chunks are split
+            /// off as the staging buffer fills up.
+            ///
+            /// On success, returns `Poll::Ready(Ok(n))` where `n` is the number
+            /// of bytes read. A return value of 0 means that the reader has
+            /// reached end of input, or that the maximum length set with
+            /// `set_max_len` has been reached.
+            ///
+            /// This method is only available with the `tokio` feature.
+            pub fn poll_read_from<R>(
+                &mut self,
+                cx: &mut Context<'_>,
+                src: Pin<&mut R>,
+            ) -> Poll<io::Result<usize>>
+            where
+                R: AsyncRead + ?Sized,
+            {
+                poll_read_buf(self, cx, src)
+            }
+        }
+
+        /// Reads the buffered data, advancing the reading position.
+        /// The buffer is always ready to be read from; when it is empty,
+        /// reading produces no data, signaling end of input.
+        ///
+        /// This implementation is only available with the `tokio` feature.
+        impl AsyncRead for $t {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                let this = self.get_mut();
+                while buf.remaining() != 0 && this.has_remaining() {
+                    let chunk = this.chunk();
+                    let len = min(chunk.len(), buf.remaining());
+                    buf.put_slice(&chunk[..len]);
+                    this.advance(len);
+                }
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        /// Appends the written data to the buffer, in the same way as
+        /// the implementation of `std::io::Write`. The buffer is always
+        /// ready to be written to.
+        ///
+        /// This implementation is only available with the `tokio` feature.
+        impl AsyncWrite for $t {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(io::Write::write(self.get_mut(), buf))
+            }
+
+            fn poll_write_vectored(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(io::Write::write_vectored(self.get_mut(), bufs))
+            }
+
+            fn is_write_vectored(&self) -> bool {
+                true
+            }
+
+            fn poll_flush(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
+
+fn poll_write_buf<B, W>(
+    buf: &mut B,
+    cx: &mut Context<'_>,
+    out: Pin<&mut W>,
+) -> Poll<io::Result<usize>>
+where
+    B: Buf,
+    W: AsyncWrite + ?Sized,
+{
+    let mut io_bufs = [IoSlice::new(&[]); MAX_IO_SLICES];
+    let io_vec_len = buf.chunks_vectored(&mut io_bufs);
+    match out.poll_write_vectored(cx, &io_bufs[..io_vec_len]) {
+        Poll::Ready(Ok(bytes_written)) => {
+            buf.advance(bytes_written);
+            Poll::Ready(Ok(bytes_written))
+        }
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+impl_tokio!(loosely::ChunkedBytes);
+impl_tokio!(strictly::ChunkedBytes);
+
+fn poll_read_buf<B, R>(
+    buf: &mut B,
+    cx: &mut Context<'_>,
+    src: Pin<&mut R>,
+) -> Poll<io::Result<usize>>
+where
+    B: BufMut,
+    R: AsyncRead + ?Sized,
+{
+    if !buf.has_remaining_mut() {
+        return Poll::Ready(Ok(0));
+    }
+    let dst = buf.chunk_mut();
+    // Safety: `ReadBuf` does not allow uninitialized bytes to be
+    // de-initialized or read.
+    let dst = unsafe {
+        slice::from_raw_parts_mut(
+            dst.as_mut_ptr() as *mut MaybeUninit<u8>,
+            dst.len(),
+        )
+    };
+    let mut read_buf = ReadBuf::uninit(dst);
+    let ptr = read_buf.filled().as_ptr();
+    match src.poll_read(cx, &mut read_buf) {
+        Poll::Ready(Ok(())) => {
+            // Make sure the reader did not swap the buffer.
+            assert_eq!(ptr, read_buf.filled().as_ptr());
+            let n = read_buf.filled().len();
+            // Safety: the first `n` bytes have been initialized by
+            // the reader.
+            unsafe { buf.advance_mut(n) };
+            Poll::Ready(Ok(n))
+        }
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => Poll::Pending,
+    }
+}
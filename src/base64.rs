@@ -0,0 +1,252 @@
+//! Streaming Base64 encoding into and decoding from `ChunkedBytes`.
+//!
+//! `Base64Encoder` and `Base64Decoder` consume the input as it becomes
+//! available, from any `Buf`, and append the output to `ChunkedBytes`.
+//! The groups of bytes or symbols split between chunks or between calls
+//! are carried over, so the input can be fed in pieces of any length.
+//! The encoding is performed with an engine of the `base64` crate,
+//! the standard engine with padding by default.
+//!
+//! This module is only available with the `base64` feature.
+
+use crate::ChunkedBytes;
+
+use ::base64::engine::general_purpose::{GeneralPurpose, STANDARD};
+use ::base64::{DecodeError, Engine};
+use bytes::{Buf, BufMut};
+
+use std::cmp::min;
+
+// Bytes encoded at a time, in whole groups of 3
+const ENCODE_BLOCK: usize = 768;
+// Symbols decoded at a time, in whole quads
+const DECODE_BLOCK: usize = 1024;
+
+const PAD: u8 = b'=';
+
+/// An incremental Base64 encoder.
+#[derive(Clone, Debug)]
+pub struct Base64Encoder<E = GeneralPurpose> {
+    engine: E,
+    carry: [u8; 2],
+    carry_len: usize,
+}
+
+impl Default for Base64Encoder {
+    #[inline]
+    fn default() -> Self {
+        Base64Encoder::with_engine(STANDARD)
+    }
+}
+
+impl Base64Encoder {
+    /// Creates an encoder with the standard alphabet and padding.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<E: Engine> Base64Encoder<E> {
+    /// Creates an encoder using the given engine.
+    #[inline]
+    pub fn with_engine(engine: E) -> Self {
+        Base64Encoder {
+            engine,
+            carry: [0; 2],
+            carry_len: 0,
+        }
+    }
+
+    /// Consumes the data remaining in `src`, appending the encoded output
+    /// to `dst`.
+    ///
+    /// Up to two trailing bytes that do not form a complete group are
+    /// carried over to the next call, or to `finish`.
+    pub fn encode<B: Buf>(&mut self, mut src: B, dst: &mut ChunkedBytes) {
+        let mut out = [0; ENCODE_BLOCK / 3 * 4];
+        if self.carry_len != 0 {
+            let mut group = [0; 3];
+            group[..self.carry_len]
+                .copy_from_slice(&self.carry[..self.carry_len]);
+            let n = min(3 - self.carry_len, src.remaining());
+            src.copy_to_slice(&mut group[self.carry_len..self.carry_len + n]);
+            self.carry_len += n;
+            if self.carry_len < 3 {
+                self.carry[..self.carry_len]
+                    .copy_from_slice(&group[..self.carry_len]);
+                return;
+            }
+            self.carry_len = 0;
+            self.put_encoded(&group, &mut out, dst);
+        }
+        while src.remaining() >= 3 {
+            let chunk = src.chunk();
+            let n = min(chunk.len(), ENCODE_BLOCK) / 3 * 3;
+            if n == 0 {
+                // The group is split between chunks
+                let mut group = [0; 3];
+                src.copy_to_slice(&mut group);
+                self.put_encoded(&group, &mut out, dst);
+                continue;
+            }
+            self.put_encoded(&chunk[..n], &mut out, dst);
+            src.advance(n);
+        }
+        self.carry_len = src.remaining();
+        src.copy_to_slice(&mut self.carry[..self.carry_len]);
+    }
+
+    /// Encodes the carried over bytes, if any, appending the final output
+    /// to `dst` with the padding required by the engine.
+    pub fn finish(self, dst: &mut ChunkedBytes) {
+        let mut out = [0; 4];
+        self.put_encoded(&self.carry[..self.carry_len], &mut out, dst);
+    }
+
+    fn put_encoded(&self, src: &[u8], out: &mut [u8], dst: &mut ChunkedBytes) {
+        let len = self
+            .engine
+            .encode_slice(src, out)
+            .expect("output block should fit the encoded input");
+        dst.put_slice(&out[..len]);
+    }
+}
+
+/// An incremental Base64 decoder.
+#[derive(Clone, Debug)]
+pub struct Base64Decoder<E = GeneralPurpose> {
+    engine: E,
+    carry: [u8; 3],
+    carry_len: usize,
+    // Offset of the next symbol in the whole input, for error reporting
+    offset: usize,
+    padded: bool,
+}
+
+impl Default for Base64Decoder {
+    #[inline]
+    fn default() -> Self {
+        Base64Decoder::with_engine(STANDARD)
+    }
+}
+
+impl Base64Decoder {
+    /// Creates a decoder with the standard alphabet and padding.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<E: Engine> Base64Decoder<E> {
+    /// Creates a decoder using the given engine.
+    #[inline]
+    pub fn with_engine(engine: E) -> Self {
+        Base64Decoder {
+            engine,
+            carry: [0; 3],
+            carry_len: 0,
+            offset: 0,
+            padded: false,
+        }
+    }
+
+    /// Consumes the data remaining in `src`, appending the decoded output
+    /// to `dst`.
+    ///
+    /// Up to three trailing symbols that do not form a complete quad are
+    /// carried over to the next call, or to `finish`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error reported by the engine for invalid input, with
+    /// the offsets counted from the start of the whole input. Input following
+    /// a padded quad is reported as `InvalidPadding`. The state of
+    /// the decoder is unspecified after an error.
+    pub fn decode<B: Buf>(
+        &mut self,
+        mut src: B,
+        dst: &mut ChunkedBytes,
+    ) -> Result<(), DecodeError> {
+        let mut out = [0; DECODE_BLOCK / 4 * 3];
+        if self.carry_len != 0 {
+            let mut quad = [0; 4];
+            quad[..self.carry_len]
+                .copy_from_slice(&self.carry[..self.carry_len]);
+            let n = min(4 - self.carry_len, src.remaining());
+            src.copy_to_slice(&mut quad[self.carry_len..self.carry_len + n]);
+            self.carry_len += n;
+            if self.carry_len < 4 {
+                self.carry[..self.carry_len]
+                    .copy_from_slice(&quad[..self.carry_len]);
+                return Ok(());
+            }
+            self.carry_len = 0;
+            self.put_decoded(&quad, &mut out, dst)?;
+        }
+        while src.remaining() >= 4 {
+            if self.padded {
+                return Err(DecodeError::InvalidPadding);
+            }
+            let chunk = src.chunk();
+            let n = min(chunk.len(), DECODE_BLOCK) / 4 * 4;
+            if n == 0 {
+                // The quad is split between chunks
+                let mut quad = [0; 4];
+                src.copy_to_slice(&mut quad);
+                self.put_decoded(&quad, &mut out, dst)?;
+                continue;
+            }
+            self.put_decoded(&chunk[..n], &mut out, dst)?;
+            src.advance(n);
+        }
+        if self.padded && src.has_remaining() {
+            return Err(DecodeError::InvalidPadding);
+        }
+        self.carry_len = src.remaining();
+        src.copy_to_slice(&mut self.carry[..self.carry_len]);
+        Ok(())
+    }
+
+    /// Decodes the carried over symbols, if any, appending the final output
+    /// to `dst`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error reported by the engine if the carried over symbols
+    /// are not a valid end of the input, such as when the engine requires
+    /// padding.
+    pub fn finish(mut self, dst: &mut ChunkedBytes) -> Result<(), DecodeError> {
+        let mut out = [0; 3];
+        let carry = self.carry;
+        self.put_decoded(&carry[..self.carry_len], &mut out, dst)
+    }
+
+    fn put_decoded(
+        &mut self,
+        src: &[u8],
+        out: &mut [u8],
+        dst: &mut ChunkedBytes,
+    ) -> Result<(), DecodeError> {
+        let offset = self.offset;
+        let len = self.engine.decode_slice_unchecked(src, out).map_err(
+            |e| match e {
+                DecodeError::InvalidByte(pos, b) => {
+                    DecodeError::InvalidByte(offset + pos, b)
+                }
+                DecodeError::InvalidLength(len) => {
+                    DecodeError::InvalidLength(offset + len)
+                }
+                DecodeError::InvalidLastSymbol(pos, b) => {
+                    DecodeError::InvalidLastSymbol(offset + pos, b)
+                }
+                e => e,
+            },
+        )?;
+        self.offset += src.len();
+        self.padded = src.last() == Some(&PAD);
+        dst.put_slice(&out[..len]);
+        Ok(())
+    }
+}
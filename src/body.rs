@@ -0,0 +1,121 @@
+use crate::{loosely, strictly, IntoChunks};
+
+use bytes::{Buf, Bytes};
+
+use std::fmt;
+
+/// An HTTP body yielding the chunks of a consumed `ChunkedBytes` container
+/// as data frames.
+///
+/// `ChunkedBody` can be created from either variant of `ChunkedBytes`
+/// with `From`. It can be used as a message body without concatenating
+/// the chunks into a single `Bytes` slice:
+///
+/// - with the `http-body` feature, `ChunkedBody` implements
+///   `http_body::Body` for use with hyper and other libraries
+///   in its ecosystem;
+/// - with the `actix-http` feature, `ChunkedBody` implements
+///   `actix_http::body::MessageBody` for use with actix-web.
+///
+/// This type is only available with either of these features.
+pub struct ChunkedBody {
+    chunks: IntoChunks,
+    remaining: usize,
+}
+
+impl From<loosely::ChunkedBytes> for ChunkedBody {
+    fn from(buf: loosely::ChunkedBytes) -> Self {
+        ChunkedBody {
+            remaining: buf.remaining(),
+            chunks: buf.into_chunks(),
+        }
+    }
+}
+
+impl From<strictly::ChunkedBytes> for ChunkedBody {
+    fn from(buf: strictly::ChunkedBytes) -> Self {
+        ChunkedBody {
+            remaining: buf.remaining(),
+            chunks: buf.into_chunks(),
+        }
+    }
+}
+
+impl ChunkedBody {
+    fn next_chunk(&mut self) -> Option<Bytes> {
+        let chunk = self.chunks.next()?;
+        self.remaining -= chunk.len();
+        Some(chunk)
+    }
+}
+
+impl fmt::Debug for ChunkedBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkedBody")
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+#[cfg(feature = "http-body")]
+mod http_body_impl {
+    use super::ChunkedBody;
+    use bytes::Bytes;
+    use http_body::{Body, Frame, SizeHint};
+    use std::convert::Infallible;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    impl Body for ChunkedBody {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+            Poll::Ready(self.get_mut().next_chunk().map(|c| Ok(Frame::data(c))))
+        }
+
+        fn is_end_stream(&self) -> bool {
+            self.remaining == 0
+        }
+
+        fn size_hint(&self) -> SizeHint {
+            SizeHint::with_exact(self.remaining as u64)
+        }
+    }
+}
+
+#[cfg(feature = "actix-http")]
+mod actix_impl {
+    use super::ChunkedBody;
+    use actix_http::body::{BodySize, MessageBody};
+    use bytes::Bytes;
+    use std::convert::Infallible;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    impl MessageBody for ChunkedBody {
+        type Error = Infallible;
+
+        fn size(&self) -> BodySize {
+            BodySize::Sized(self.remaining as u64)
+        }
+
+        fn poll_next(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Bytes, Infallible>>> {
+            Poll::Ready(self.get_mut().next_chunk().map(Ok))
+        }
+
+        fn try_into_bytes(mut self) -> Result<Bytes, Self> {
+            match self.chunks.len() {
+                0 => Ok(Bytes::new()),
+                1 => Ok(self.next_chunk().unwrap()),
+                _ => Err(self),
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::VecDeque;
+
+// Positions of the chunk boundaries forced with `set_chunk_boundaries`,
+// tracked against the count of bytes written.
+#[derive(Default)]
+pub(crate) struct Boundaries {
+    written: usize,
+    offsets: VecDeque<usize>,
+}
+
+impl Boundaries {
+    pub fn set<I>(&mut self, offsets: I)
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let written = self.written;
+        let mut offsets = offsets
+            .into_iter()
+            .filter(|&offset| offset != 0)
+            .map(|offset| written.checked_add(offset).expect("offset overflow"))
+            .collect::<Vec<_>>();
+        offsets.sort_unstable();
+        offsets.dedup();
+        self.offsets = offsets.into();
+    }
+
+    // Returns the number of bytes that can be written before
+    // the next boundary.
+    #[inline]
+    pub fn room(&self) -> usize {
+        match self.offsets.front() {
+            Some(&offset) => offset - self.written,
+            None => usize::MAX,
+        }
+    }
+
+    // Accounts for `cnt` bytes written, which must not exceed `room()`.
+    // Returns true if a boundary has been reached.
+    #[inline]
+    pub fn advance(&mut self, cnt: usize) -> bool {
+        debug_assert!(cnt <= self.room());
+        self.written += cnt;
+        if self.offsets.front() == Some(&self.written) {
+            self.offsets.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A limit on the total amount of data buffered by multiple
+/// `ChunkedBytes` containers.
+///
+/// A `MemoryBudget` handle is cheaply cloned to be shared between
+/// containers with `set_memory_budget`. Each container charges the budget
+/// for the data it holds, and releases the charge as the data is consumed
+/// or the container is dropped. While the budget is exhausted, the writes
+/// into the containers sharing it behave as if the maximum length set with
+/// `set_max_len` has been reached: `remaining_mut` reports the amount left
+/// in the budget, and the fallible methods such as `try_put_slice` return
+/// an error.
+///
+/// The budget is checked before each write and charged after it,
+/// so concurrent writes from multiple threads may exceed it slightly.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    inner: Arc<Shared>,
+}
+
+struct Shared {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Creates a budget allowing up to `limit` bytes to be buffered.
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            inner: Arc::new(Shared {
+                limit,
+                used: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Returns the total number of bytes allowed by the budget.
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.inner.limit
+    }
+
+    /// Returns the number of bytes currently charged to the budget.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes left in the budget.
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.limit().saturating_sub(self.used())
+    }
+
+    #[inline]
+    pub(crate) fn charge(&self, len: usize) {
+        self.inner.used.fetch_add(len, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn release(&self, len: usize) {
+        self.inner.used.fetch_sub(len, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limit", &self.limit())
+            .field("used", &self.used())
+            .finish()
+    }
+}
@@ -0,0 +1,189 @@
+use crate::ChunkedBytes;
+
+use bytes::{Buf, Bytes};
+
+use std::cmp::min;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A buffered asynchronous reader that accumulates the input in
+/// `ChunkedBytes`.
+///
+/// `ChunkedBufReader` works like the `BufReader` types provided by
+/// the async I/O libraries, implementing the `AsyncBufRead` trait, but in
+/// addition to copying, the consumed data can be split off the buffer as
+/// `Bytes` without copying, using `consume_bytes`. This is useful for
+/// protocols that retain slices of the input.
+///
+/// With the `tokio` feature, `ChunkedBufReader` implements
+/// `tokio::io::AsyncRead` and `tokio::io::AsyncBufRead` when the inner reader
+/// implements `tokio::io::AsyncRead`. With the `futures-io` feature,
+/// the `futures-io` traits are implemented likewise.
+#[derive(Debug)]
+pub struct ChunkedBufReader<R> {
+    inner: R,
+    buf: ChunkedBytes,
+}
+
+impl<R> ChunkedBufReader<R> {
+    /// Creates a new buffered reader over `inner`, with the preferred chunk
+    /// size set to a default value.
+    pub fn new(inner: R) -> Self {
+        ChunkedBufReader {
+            inner,
+            buf: ChunkedBytes::new(),
+        }
+    }
+
+    /// Creates a new buffered reader over `inner`, with the given
+    /// chunk size to prefer.
+    pub fn with_chunk_size_hint(chunk_size: usize, inner: R) -> Self {
+        ChunkedBufReader {
+            inner,
+            buf: ChunkedBytes::with_chunk_size_hint(chunk_size),
+        }
+    }
+
+    /// Returns a shared reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    ///
+    /// Reading directly from the inner reader may cause data loss,
+    /// as the already buffered data is skipped.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns a pinned mutable reference to the inner reader.
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut R> {
+        self.project().0
+    }
+
+    /// Returns a reference to the buffered data.
+    pub fn buffer(&self) -> &ChunkedBytes {
+        &self.buf
+    }
+
+    /// Removes `len` bytes from the front of the buffered data and returns
+    /// them as `Bytes`. The data is not copied if it is contained in
+    /// a single chunk.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `len` is larger than the length of
+    /// the buffered data.
+    pub fn consume_bytes(&mut self, len: usize) -> Bytes {
+        self.buf.copy_to_bytes(len)
+    }
+
+    /// Consumes the reader, returning the inner reader and the buffered
+    /// data that has not been read.
+    pub fn into_parts(self) -> (R, ChunkedBytes) {
+        (self.inner, self.buf)
+    }
+
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut R>, &mut ChunkedBytes) {
+        // Safety: `inner` is pinned structurally, `buf` is not;
+        // `ChunkedBufReader` does not implement `Drop` and is `Unpin`
+        // only if `R` is.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &mut this.buf)
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::*;
+    use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+    impl<R: AsyncRead> AsyncRead for ChunkedBufReader<R> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            dst: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let chunk = match self.as_mut().poll_fill_buf(cx) {
+                Poll::Ready(Ok(chunk)) => chunk,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let len = min(chunk.len(), dst.remaining());
+            dst.put_slice(&chunk[..len]);
+            self.consume(len);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<R: AsyncRead> AsyncBufRead for ChunkedBufReader<R> {
+        fn poll_fill_buf(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<&[u8]>> {
+            let (inner, buf) = self.project();
+            if !buf.has_remaining() {
+                match buf.poll_read_from(cx, inner) {
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Poll::Ready(Ok((*buf).chunk()))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.project().1.advance(amt)
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_impl {
+    use super::*;
+    use crate::async_futures::poll_read_from;
+    use futures_io::{AsyncBufRead, AsyncRead};
+
+    impl<R: AsyncRead> AsyncRead for ChunkedBufReader<R> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            dst: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let chunk = match self.as_mut().poll_fill_buf(cx) {
+                Poll::Ready(Ok(chunk)) => chunk,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let len = min(chunk.len(), dst.len());
+            dst[..len].copy_from_slice(&chunk[..len]);
+            self.consume(len);
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    impl<R: AsyncRead> AsyncBufRead for ChunkedBufReader<R> {
+        fn poll_fill_buf(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<&[u8]>> {
+            let (inner, buf) = self.project();
+            if !buf.has_remaining() {
+                match poll_read_from(&mut *buf, cx, inner) {
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Poll::Ready(Ok((*buf).chunk()))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.project().1.advance(amt)
+        }
+    }
+}
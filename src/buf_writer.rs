@@ -0,0 +1,341 @@
+use crate::sync_io;
+use crate::ChunkedBytes;
+
+use bytes::{Buf, Bytes};
+
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// A buffered writer that accumulates the output in `ChunkedBytes` and
+/// writes it out with vectored writes.
+///
+/// Small writes are appended to the staging buffer of the internal
+/// `ChunkedBytes` container. Writes at least as large as the preferred
+/// chunk size, as well as writes of at least half that size that don't fit
+/// into the free capacity of the staging buffer, bypass the buffer after
+/// the buffered data has been written out. When the buffered data would
+/// exceed the capacity of the writer, it is written out with
+/// `poll_write_vectored` over the gathered chunks.
+///
+/// `ChunkedBufWriter` implements `std::io::Write` when the inner writer
+/// implements `std::io::Write`, serving as a replacement for
+/// `std::io::BufWriter`. Unlike `BufWriter`, it does not write out
+/// the buffered data when dropped: call `flush` to make sure all data
+/// is written. `Bytes` can be buffered without copying with `write_bytes`.
+///
+/// With the `tokio` feature, `ChunkedBufWriter` implements
+/// `tokio::io::AsyncWrite` when the inner writer implements
+/// `tokio::io::AsyncWrite`. With the `futures-io` feature,
+/// `futures_io::AsyncWrite` is implemented likewise.
+#[derive(Debug)]
+pub struct ChunkedBufWriter<W> {
+    inner: W,
+    buf: ChunkedBytes,
+    capacity: usize,
+}
+
+impl<W> ChunkedBufWriter<W> {
+    /// Creates a new buffered writer over `inner` with a default capacity.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Creates a new buffered writer over `inner` that buffers up to
+    /// `capacity` bytes before writing them out.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        ChunkedBufWriter {
+            inner,
+            buf: ChunkedBytes::new(),
+            capacity,
+        }
+    }
+
+    /// Returns a shared reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    ///
+    /// Writing directly to the inner writer may cause the output to be
+    /// reordered with the buffered data.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns a pinned mutable reference to the inner writer.
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut W> {
+        self.project().0
+    }
+
+    /// Returns a reference to the buffered data.
+    pub fn buffer(&self) -> &ChunkedBytes {
+        &self.buf
+    }
+
+    /// Consumes the writer, returning the inner writer and the buffered
+    /// data that has not been written out.
+    pub fn into_parts(self) -> (W, ChunkedBytes) {
+        (self.inner, self.buf)
+    }
+
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut W>, &mut ChunkedBytes) {
+        // Safety: `inner` is pinned structurally, `buf` is not;
+        // `ChunkedBufWriter` does not implement `Drop` and is `Unpin`
+        // only if `W` is.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &mut this.buf)
+        }
+    }
+
+    // Checks if a write of `len` bytes should bypass the buffer.
+    // Writes of at least half the chunk size that do not fit into
+    // the free capacity of the staging buffer bypass it as well, rather
+    // than taking up most of a newly allocated staging buffer.
+    fn is_pass_through(&self, len: usize) -> bool {
+        let chunk_size = self.buf.chunk_size_hint();
+        let room = self.buf.staging_capacity() - self.buf.staging_len();
+        len >= chunk_size || (len > room && len >= chunk_size / 2)
+    }
+
+    // Checks if the buffered data need to be written out before
+    // a write of `len` bytes.
+    fn needs_flush(&self, len: usize) -> bool {
+        self.buf.has_remaining()
+            && (self.is_pass_through(len)
+                || self.buf.remaining().saturating_add(len) > self.capacity)
+    }
+}
+
+fn write_zero_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::WriteZero,
+        "failed to write the buffered data",
+    )
+}
+
+fn total_len(bufs: &[IoSlice<'_>]) -> usize {
+    bufs.iter()
+        .fold(0usize, |sum, buf| sum.saturating_add(buf.len()))
+}
+
+impl<W: io::Write> ChunkedBufWriter<W> {
+    /// Appends `src` to the buffer without copying the data, writing out
+    /// the buffered data if the capacity of the writer is reached.
+    pub fn write_bytes(&mut self, src: Bytes) -> io::Result<()> {
+        self.buf.put_bytes(src);
+        if self.buf.remaining() >= self.capacity {
+            self.flush_buf()?;
+        }
+        Ok(())
+    }
+
+    fn flush_buf(&mut self) -> io::Result<()> {
+        while self.buf.has_remaining() {
+            match sync_io::write_to(&mut self.buf, &mut self.inner) {
+                Ok(0) => return Err(write_zero_error()),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write> io::Write for ChunkedBufWriter<W> {
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        if self.needs_flush(src.len()) {
+            self.flush_buf()?;
+        }
+        if self.is_pass_through(src.len()) {
+            self.inner.write(src)
+        } else {
+            self.buf.write(src)
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let len = total_len(bufs);
+        if self.needs_flush(len) {
+            self.flush_buf()?;
+        }
+        if self.is_pass_through(len) {
+            self.inner.write_vectored(bufs)
+        } else {
+            self.buf.write_vectored(bufs)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+// Evaluates to the ready value of a `Poll`, or returns `Poll::Pending`.
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+macro_rules! ready {
+    ($e:expr) => {
+        match $e {
+            Poll::Ready(v) => v,
+            Poll::Pending => return Poll::Pending,
+        }
+    };
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::*;
+    use std::task::{Context, Poll};
+    use tokio::io::AsyncWrite;
+
+    fn poll_flush_buf<W: AsyncWrite>(
+        this: Pin<&mut ChunkedBufWriter<W>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (mut inner, buf) = this.project();
+        while buf.has_remaining() {
+            match buf.poll_write_to(cx, inner.as_mut()) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(write_zero_error()))
+                }
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    impl<W: AsyncWrite> AsyncWrite for ChunkedBufWriter<W> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            src: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.needs_flush(src.len()) {
+                ready!(poll_flush_buf(self.as_mut(), cx))?;
+            }
+            if self.is_pass_through(src.len()) {
+                self.get_pin_mut().poll_write(cx, src)
+            } else {
+                Poll::Ready(io::Write::write(self.project().1, src))
+            }
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let len = total_len(bufs);
+            if self.needs_flush(len) {
+                ready!(poll_flush_buf(self.as_mut(), cx))?;
+            }
+            if self.is_pass_through(len) {
+                self.get_pin_mut().poll_write_vectored(cx, bufs)
+            } else {
+                Poll::Ready(io::Write::write_vectored(self.project().1, bufs))
+            }
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            ready!(poll_flush_buf(self.as_mut(), cx))?;
+            self.get_pin_mut().poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            ready!(poll_flush_buf(self.as_mut(), cx))?;
+            self.get_pin_mut().poll_shutdown(cx)
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_impl {
+    use super::*;
+    use crate::async_futures::poll_write_to;
+    use futures_io::AsyncWrite;
+    use std::task::{Context, Poll};
+
+    fn poll_flush_buf<W: AsyncWrite>(
+        this: Pin<&mut ChunkedBufWriter<W>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (mut inner, buf) = this.project();
+        while buf.has_remaining() {
+            match poll_write_to(&mut *buf, cx, inner.as_mut()) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(write_zero_error()))
+                }
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    impl<W: AsyncWrite> AsyncWrite for ChunkedBufWriter<W> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            src: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.needs_flush(src.len()) {
+                ready!(poll_flush_buf(self.as_mut(), cx))?;
+            }
+            if self.is_pass_through(src.len()) {
+                self.get_pin_mut().poll_write(cx, src)
+            } else {
+                Poll::Ready(io::Write::write(self.project().1, src))
+            }
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let len = total_len(bufs);
+            if self.needs_flush(len) {
+                ready!(poll_flush_buf(self.as_mut(), cx))?;
+            }
+            if self.is_pass_through(len) {
+                self.get_pin_mut().poll_write_vectored(cx, bufs)
+            } else {
+                Poll::Ready(io::Write::write_vectored(self.project().1, bufs))
+            }
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            ready!(poll_flush_buf(self.as_mut(), cx))?;
+            self.get_pin_mut().poll_flush(cx)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            ready!(poll_flush_buf(self.as_mut(), cx))?;
+            self.get_pin_mut().poll_close(cx)
+        }
+    }
+}
@@ -1,38 +1,122 @@
-use crate::{DrainChunks, IntoChunks};
+#[cfg(feature = "test-util")]
+use crate::boundaries::Boundaries;
+use crate::queue::{Chunk, ChunkQueue};
+use crate::utf8;
+#[cfg(feature = "rayon")]
+use crate::ParChunks;
+use crate::{ChunkStats, DrainChunks, IntoChunks, Layout};
+use crate::{ChunkStorage, MemoryBudget, StagingAlloc};
 
 use bytes::buf::{Buf, BufMut, UninitSlice};
 use bytes::{Bytes, BytesMut};
+use memchr::memmem;
 
-use std::cmp::min;
-use std::collections::VecDeque;
+use std::cmp::{max, min};
+use std::collections::TryReserveError;
+use std::fmt;
 use std::io::IoSlice;
+use std::mem;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+#[cfg(feature = "tokio")]
+use tokio::time::Instant;
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
 const DEFAULT_CHUNK_SIZE: usize = 4096;
 
-#[derive(Debug)]
-pub(crate) struct Inner {
+type ChunkObserver = dyn FnMut(usize) + Send + Sync;
+
+pub(crate) struct Inner<S> {
     staging: BytesMut,
-    chunks: VecDeque<Bytes>,
+    chunks: ChunkQueue<S>,
+    // Total length of the chunks
+    chunks_len: usize,
     chunk_size: usize,
+    // Alignment of staging buffer allocations, 1 if not aligned
+    alignment: usize,
+    // Allocate staging buffers with the exact requested capacity
+    reserve_exact: bool,
+    alloc: Option<Arc<dyn StagingAlloc>>,
+    max_len: usize,
+    budget: Option<MemoryBudget>,
+    // Length past which the oldest chunks are evicted
+    ring_limit: usize,
+    evicted_len: u64,
+    // Length below which `put_bytes` copies, if set explicitly
+    copy_threshold: Option<usize>,
+    // Length below which `put_bytes` avoids flushing the staging buffer
+    min_chunk_size: usize,
+    // Chunks shorter than this are merged by copying, 0 if disabled
+    coalesce_threshold: usize,
+    low_watermark: usize,
+    high_watermark: usize,
+    flush_count: u64,
+    reserve_count: u64,
+    stats: Option<Box<ChunkStats>>,
+    observer: Option<Box<ChunkObserver>>,
+    capacity_waker: Option<Waker>,
+    #[cfg(feature = "zeroize")]
+    zeroize: bool,
+    // Set when the time of staging is being recorded
+    #[cfg(feature = "tokio")]
+    staged_at: Option<Option<Instant>>,
+    #[cfg(feature = "test-util")]
+    boundaries: Boundaries,
 }
 
-impl Default for Inner {
+impl<S> Default for Inner<S> {
     #[inline]
     fn default() -> Self {
         Inner {
             staging: BytesMut::new(),
-            chunks: VecDeque::new(),
+            chunks: ChunkQueue::default(),
+            chunks_len: 0,
             chunk_size: DEFAULT_CHUNK_SIZE,
+            alignment: 1,
+            reserve_exact: false,
+            alloc: None,
+            max_len: usize::MAX,
+            budget: None,
+            ring_limit: usize::MAX,
+            evicted_len: 0,
+            copy_threshold: None,
+            min_chunk_size: 0,
+            coalesce_threshold: 0,
+            low_watermark: 0,
+            high_watermark: usize::MAX,
+            flush_count: 0,
+            reserve_count: 0,
+            stats: None,
+            observer: None,
+            capacity_waker: None,
+            #[cfg(feature = "zeroize")]
+            zeroize: false,
+            #[cfg(feature = "tokio")]
+            staged_at: None,
+            #[cfg(feature = "test-util")]
+            boundaries: Boundaries::default(),
         }
     }
 }
 
+// Debug-formats the chunk queue as a list of chunk lengths.
+struct ChunkLengths<'a, S>(&'a ChunkQueue<S>);
+
+impl<'a, S: ChunkStorage> fmt::Debug for ChunkLengths<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|chunk| chunk.len()))
+            .finish()
+    }
+}
+
 pub(crate) enum AdvanceStopped {
     InChunk,
     InStaging(usize),
 }
 
-impl Inner {
+impl<S: ChunkStorage> Inner<S> {
     #[inline]
     pub fn with_chunk_size(chunk_size: usize) -> Self {
         Inner {
@@ -44,9 +128,62 @@ impl Inner {
     #[inline]
     pub fn with_profile(chunk_size: usize, chunking_capacity: usize) -> Self {
         Inner {
-            staging: BytesMut::new(),
-            chunks: VecDeque::with_capacity(chunking_capacity),
+            chunks: ChunkQueue::with_capacity(chunking_capacity),
             chunk_size,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_alignment(chunk_size: usize, alignment: usize) -> Self {
+        debug_assert!(alignment.is_power_of_two());
+        debug_assert!(chunk_size & (alignment - 1) == 0);
+        Inner {
+            chunk_size,
+            alignment,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        let chunks = chunks
+            .into_iter()
+            .filter(|c| !c.is_empty())
+            .collect::<ChunkQueue<S>>();
+        Inner {
+            chunks_len: chunks.iter().map(|chunk| chunk.len()).sum(),
+            chunks,
+            ..Default::default()
+        }
+    }
+
+    // Adds the fields describing the layout of the buffer to the
+    // `Debug` output of a container type.
+    pub fn debug_fields(&self, d: &mut fmt::DebugStruct<'_, '_>) {
+        d.field("chunks", &ChunkLengths(&self.chunks))
+            .field("staging_len", &self.staging.len())
+            .field("staging_capacity", &self.staging.capacity());
+        if self.max_len != usize::MAX {
+            d.field("max_len", &self.max_len);
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout_capped(usize::MAX)
+    }
+
+    pub fn layout_capped(&self, limit: usize) -> Layout {
+        Layout {
+            chunks: self
+                .chunks
+                .iter()
+                .flat_map(|chunk| piece_lens(chunk.len(), limit))
+                .collect(),
+            staging_len: self.staging.len(),
+            staging_capacity: self.staging.capacity(),
         }
     }
 
@@ -55,11 +192,273 @@ impl Inner {
         self.chunk_size
     }
 
+    #[inline]
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    #[inline]
+    fn is_aligned(&self) -> bool {
+        self.alignment != 1
+    }
+
+    #[inline]
+    pub fn set_reserve_exact(&mut self, enable: bool) {
+        self.reserve_exact = enable;
+    }
+
+    #[inline]
+    pub fn set_staging_alloc(&mut self, alloc: Arc<dyn StagingAlloc>) {
+        self.alloc = Some(alloc);
+    }
+
+    // Allocates a new empty staging buffer.
+    fn allocate(&self, capacity: usize) -> BytesMut {
+        match &self.alloc {
+            None => BytesMut::with_capacity(capacity),
+            Some(alloc) => {
+                let buf = alloc.allocate(capacity);
+                debug_assert!(buf.is_empty() && buf.capacity() >= capacity);
+                buf
+            }
+        }
+    }
+
+    // Replaces the staging buffer, giving the old one back to
+    // the staging allocator if there is one.
+    fn replace_staging(&mut self, staging: BytesMut) {
+        let mut old = mem::replace(&mut self.staging, staging);
+        if let Some(alloc) = &self.alloc {
+            old.clear();
+            alloc.recycle(old);
+        }
+    }
+
+    // Releases the staging buffer when the container is dropped.
+    pub fn release_staging(&mut self) {
+        if let Some(budget) = self.budget.take() {
+            budget.release(self.remaining());
+        }
+        #[cfg(feature = "zeroize")]
+        self.erase_all();
+        if self.alloc.is_some() {
+            self.replace_staging(BytesMut::new());
+        }
+    }
+
+    #[inline]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    #[inline]
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+        self.wake_capacity();
+    }
+
+    #[inline]
+    pub fn copy_threshold(&self) -> usize {
+        self.copy_threshold.unwrap_or(self.chunk_size / 8)
+    }
+
+    #[inline]
+    pub fn set_copy_threshold(&mut self, threshold: usize) {
+        self.copy_threshold = Some(threshold);
+    }
+
+    #[inline]
+    pub fn min_chunk_size(&self) -> usize {
+        self.min_chunk_size
+    }
+
+    #[inline]
+    pub fn set_min_chunk_size(&mut self, size: usize) {
+        self.min_chunk_size = size;
+    }
+
+    #[inline]
+    pub fn coalesce_threshold(&self) -> usize {
+        self.coalesce_threshold
+    }
+
+    #[inline]
+    pub fn set_coalesce_threshold(&mut self, threshold: usize) {
+        self.coalesce_threshold = threshold;
+    }
+
+    #[inline]
+    pub fn watermarks(&self) -> (usize, usize) {
+        (self.low_watermark, self.high_watermark)
+    }
+
+    #[inline]
+    pub fn set_watermarks(&mut self, low: usize, high: usize) {
+        assert!(
+            low <= high,
+            "low watermark {} is above high watermark {}",
+            low,
+            high
+        );
+        self.low_watermark = low;
+        self.high_watermark = high;
+        self.wake_capacity();
+    }
+
+    #[inline]
+    pub fn is_above_high_watermark(&self) -> bool {
+        self.remaining() >= self.high_watermark
+    }
+
+    #[inline]
+    pub fn is_below_low_watermark(&self) -> bool {
+        self.remaining() <= self.low_watermark
+    }
+
+    // The number of bytes that can be appended without reaching
+    // the high watermark or exceeding the maximum length.
+    #[inline]
+    fn capacity(&self) -> usize {
+        min(
+            self.high_watermark.saturating_sub(self.remaining()),
+            self.remaining_mut(),
+        )
+    }
+
+    pub fn poll_capacity(
+        &mut self,
+        cx: &mut Context<'_>,
+        n: usize,
+    ) -> Poll<()> {
+        if self.capacity() >= n || self.remaining() == 0 {
+            self.capacity_waker = None;
+            Poll::Ready(())
+        } else {
+            match &self.capacity_waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                _ => self.capacity_waker = Some(cx.waker().clone()),
+            }
+            Poll::Pending
+        }
+    }
+
+    // Wakes the task waiting in `poll_capacity`, if any, to check again
+    // after the buffered data has been consumed or the limits changed.
+    #[inline]
+    fn wake_capacity(&mut self) {
+        if let Some(waker) = self.capacity_waker.take() {
+            waker.wake();
+        }
+    }
+
+    #[inline]
+    pub fn is_bounded(&self) -> bool {
+        self.max_len != usize::MAX || self.budget.is_some()
+    }
+
+    #[inline]
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        let len = self.remaining();
+        if let Some(old) = mem::replace(&mut self.budget, budget) {
+            old.release(len);
+        }
+        if let Some(budget) = &self.budget {
+            budget.charge(len);
+        }
+        self.wake_capacity();
+    }
+
+    #[inline]
+    pub fn set_ring_limit(&mut self, limit: usize) {
+        self.ring_limit = limit;
+        self.evict();
+    }
+
+    #[inline]
+    pub fn evicted_len(&self) -> u64 {
+        self.evicted_len
+    }
+
+    // Drops the oldest complete chunks while the buffered data exceed
+    // the ring limit.
+    #[inline]
+    fn evict(&mut self) {
+        while self.remaining() > self.ring_limit {
+            let len = match self.chunks.front() {
+                Some(chunk) => chunk.len(),
+                None => break,
+            };
+            #[cfg(feature = "zeroize")]
+            self.erase_chunked(len);
+            self.chunks.pop_front();
+            self.chunks_len -= len;
+            self.evicted_len += len as u64;
+            if let Some(budget) = &self.budget {
+                budget.release(len);
+            }
+        }
+    }
+
+    #[inline]
+    fn charge(&self, len: usize) {
+        if let Some(budget) = &self.budget {
+            budget.charge(len);
+        }
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.chunks.is_empty() && self.staging.is_empty()
     }
 
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Counts the chunks as exposed in pieces of at most `limit` bytes.
+    pub fn chunk_count_capped(&self, limit: usize) -> usize {
+        if limit == usize::MAX {
+            return self.chunks.len();
+        }
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.len().div_ceil(limit))
+            .sum()
+    }
+
+    #[inline]
+    pub fn flush_count(&self) -> u64 {
+        self.flush_count
+    }
+
+    #[inline]
+    pub fn reserve_count(&self) -> u64 {
+        self.reserve_count
+    }
+
+    // Returns the time when bytes were first written into the staging
+    // buffer after it's been empty, or `None` if the buffer is empty.
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn staged_at(&self) -> Option<Instant> {
+        if self.staging.is_empty() {
+            None
+        } else {
+            self.staged_at.flatten()
+        }
+    }
+
+    // Starts recording the time when bytes are first written into
+    // the staging buffer, counting any bytes already staged as written now.
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn record_staged_at(&mut self) {
+        if self.staged_at.is_none() {
+            self.staged_at = Some(Some(Instant::now()));
+        }
+    }
+
     #[inline]
     pub fn staging_len(&self) -> usize {
         self.staging.len()
@@ -72,32 +471,328 @@ impl Inner {
 
     #[inline]
     pub fn push_chunk(&mut self, chunk: Bytes) {
+        self.push_chunk_capped(chunk, usize::MAX)
+    }
+
+    // Appends a chunk that is to be exposed to the consumer in pieces
+    // of at most `limit` bytes. The chunk is stored whole, and the pieces
+    // are only split off by the methods taking the same limit.
+    #[inline]
+    pub fn push_chunk_capped(&mut self, chunk: Bytes, limit: usize) {
+        self.push_any_capped(chunk.into(), limit)
+    }
+
+    pub fn push_storage_capped(&mut self, storage: S, limit: usize) {
+        self.push_any_capped(Chunk::Storage(storage), limit)
+    }
+
+    #[inline]
+    fn push_any_capped(&mut self, chunk: Chunk<S>, limit: usize) {
         debug_assert!(!chunk.is_empty());
+        self.charge(chunk.len());
+        #[cfg(feature = "test-util")]
+        let chunk = self.split_at_boundaries(chunk, limit);
+        self.push_back(chunk, limit)
+    }
+
+    #[inline]
+    fn push_back(&mut self, chunk: Chunk<S>, limit: usize) {
+        let mut rest = chunk.len();
+        while rest != 0 {
+            let len = min(rest, limit);
+            if let Some(stats) = &mut self.stats {
+                stats.record_produced(len);
+            }
+            if let Some(observer) = &mut self.observer {
+                observer(len);
+            }
+            rest -= len;
+        }
+        self.chunks_len += chunk.len();
+        // Make room for the new chunk, which itself is never evicted
+        self.evict();
+        if chunk.len() < self.coalesce_threshold {
+            if let Some(last) = self.chunks.back_mut() {
+                if last.len() < self.coalesce_threshold {
+                    last.extend_from_slice(&chunk);
+                    return;
+                }
+            }
+        }
         self.chunks.push_back(chunk)
     }
 
+    #[inline]
+    pub fn set_chunk_observer<F>(&mut self, observer: F)
+    where
+        F: FnMut(usize) + Send + Sync + 'static,
+    {
+        self.observer = Some(Box::new(observer));
+    }
+
+    #[inline]
+    pub fn clear_chunk_observer(&mut self) {
+        self.observer = None;
+    }
+
+    #[inline]
+    pub fn record_stats(&mut self) {
+        if self.stats.is_none() {
+            self.stats = Some(Box::new(ChunkStats::new()));
+        }
+    }
+
+    #[inline]
+    pub fn stats(&self) -> Option<&ChunkStats> {
+        self.stats.as_deref()
+    }
+
+    #[inline]
+    fn record_consumed(&mut self, len: usize) {
+        if let Some(stats) = &mut self.stats {
+            stats.record_consumed(len);
+        }
+        if let Some(budget) = &self.budget {
+            budget.release(len);
+        }
+    }
+
     #[inline]
     pub fn flush(&mut self) {
         if !self.staging.is_empty() {
-            let bytes = self.staging.split().freeze();
-            self.push_chunk(bytes)
+            let chunk = Chunk::Owned(self.staging.split());
+            self.push_back(chunk, usize::MAX);
+            self.flush_count += 1;
+            // In the aligned mode, don't continue filling the remaining
+            // capacity if it starts at a misaligned address.
+            if self.is_aligned()
+                && self.staging.as_ptr() as usize & (self.alignment - 1) != 0
+            {
+                self.replace_staging(BytesMut::new());
+            }
+        }
+    }
+
+    // Flushes the staging buffer before reserving `additional` bytes.
+    // If the capacity remaining after the staged bytes is too small to be
+    // reused for the reservation, the whole buffer is moved into the queue
+    // instead of splitting the staged bytes off. This keeps the allocation
+    // of the chunk unshared, so splitting it off and dropping it after
+    // consumption do not cost reference count updates.
+    fn flush_to_reserve(&mut self, additional: usize) {
+        let spare = self.staging.capacity() - self.staging.len();
+        if self.staging.is_empty()
+            || self.alloc.is_some()
+            || spare >= additional
+        {
+            self.flush();
+            return;
+        }
+        let chunk = Chunk::Owned(mem::take(&mut self.staging));
+        self.push_back(chunk, usize::MAX);
+        self.flush_count += 1;
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn set_chunk_boundaries<I>(&mut self, offsets: I)
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.boundaries.set(offsets)
+    }
+
+    // Pushes chunks split off the head of `chunk` at the forced boundaries,
+    // returning the remainder.
+    #[cfg(feature = "test-util")]
+    fn split_at_boundaries(
+        &mut self,
+        mut chunk: Chunk<S>,
+        limit: usize,
+    ) -> Chunk<S> {
+        let mut room = self.boundaries.room();
+        while room < chunk.len() {
+            self.boundaries.advance(room);
+            self.push_back(chunk.split_chunk_to(room), limit);
+            room = self.boundaries.room();
         }
+        self.boundaries.advance(chunk.len());
+        chunk
     }
 
     #[inline]
-    pub fn drain_chunks(&mut self) -> DrainChunks<'_> {
-        DrainChunks::new(self.chunks.drain(..))
+    pub fn drain_chunks(&mut self) -> DrainChunks<'_, S> {
+        self.drain_chunks_capped(usize::MAX)
+    }
+
+    // Removes all buffered data, keeping the staging buffer allocation
+    // and the capacity of the chunk queue.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "zeroize")]
+        self.erase_all();
+        drop(self.chunks.drain());
+        if let Some(budget) = &self.budget {
+            budget.release(self.remaining());
+        }
+        self.chunks_len = 0;
+        self.staging.clear();
+        if !self.is_aligned() && !self.reserve_exact {
+            // Reclaim the capacity split off with the dropped chunks,
+            // which is done without reallocating if the staging buffer
+            // is no longer shared.
+            self.staging.reserve(self.chunk_size);
+        }
+        self.wake_capacity();
+    }
+
+    // Takes over the allocations of a cleared container `other` that are
+    // compatible with the settings of this one, leaving `other` with
+    // empty buffers. The staging buffer is only taken if this container
+    // has not allocated one and both allocate it in the same way.
+    pub fn reuse_allocations(&mut self, other: &mut Inner<S>) {
+        debug_assert!(other.is_empty());
+        if self.staging.capacity() == 0
+            && self.alloc.is_none()
+            && other.alloc.is_none()
+            && self.chunk_size == other.chunk_size
+            && self.alignment == other.alignment
+            && self.reserve_exact == other.reserve_exact
+        {
+            self.staging = mem::take(&mut other.staging);
+        }
+        if let ChunkQueue::Inline(_, 0) = self.chunks {
+            mem::swap(&mut self.chunks, &mut other.chunks);
+        }
+    }
+
+    // Merges runs of adjacent chunks shorter than `max_small` into chunks
+    // of up to `target` bytes. Chunks not merged with others are kept
+    // without copying.
+    pub fn compact(&mut self, max_small: usize, target: usize) {
+        let max_small = min(max_small, target.saturating_add(1));
+        let mut chunks = ChunkQueue::default();
+        // The first chunk of the current run, kept until it can be merged
+        let mut head = None;
+        let mut merged = BytesMut::new();
+        for chunk in self.chunks.drain_chunks() {
+            if chunk.len() >= max_small {
+                flush_run(&mut chunks, &mut head, &mut merged);
+                chunks.push_back(chunk);
+                continue;
+            }
+            let run_len =
+                head.as_ref().map_or(merged.len(), |chunk| chunk.len());
+            if run_len + chunk.len() > target {
+                flush_run(&mut chunks, &mut head, &mut merged);
+            }
+            match head.take() {
+                None if merged.is_empty() => head = Some(chunk),
+                None => merged.extend_from_slice(&chunk),
+                Some(first) => {
+                    merged.reserve(target);
+                    merged.extend_from_slice(&first);
+                    merged.extend_from_slice(&chunk);
+                }
+            }
+        }
+        flush_run(&mut chunks, &mut head, &mut merged);
+        self.chunks = chunks;
+    }
+
+    #[inline]
+    pub fn drain_chunks_capped(&mut self, limit: usize) -> DrainChunks<'_, S> {
+        let count = self.chunk_count_capped(limit);
+        let len = mem::replace(&mut self.chunks_len, 0);
+        self.record_consumed(len);
+        self.wake_capacity();
+        DrainChunks::new(self.chunks.drain(), limit, count)
+    }
+
+    // Checks that the buffered data are held in a single `Bytes`-compatible
+    // chunk or the staging buffer, so they can be converted into `Bytes`
+    // without copying.
+    pub fn is_contiguous(&self) -> bool {
+        match self.chunks.len() {
+            0 => true,
+            1 => {
+                self.staging.is_empty()
+                    && !matches!(self.chunks.front(), Some(Chunk::Storage(_)))
+            }
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub fn into_chunks(self) -> IntoChunks<S> {
+        self.into_chunks_capped(usize::MAX)
     }
 
     #[inline]
-    pub fn into_chunks(mut self) -> IntoChunks {
+    pub fn into_chunks_capped(mut self, limit: usize) -> IntoChunks<S> {
+        self.record_consumed(self.remaining());
+        self.budget = None;
         if !self.staging.is_empty() {
-            self.chunks.push_back(self.staging.freeze());
+            let staging = mem::take(&mut self.staging);
+            self.chunks.push_back(Chunk::Owned(staging));
+        }
+        let count = self.chunk_count_capped(limit);
+        IntoChunks::new(self.chunks.into_iter(), limit, count)
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    pub fn set_zeroize(&mut self, enable: bool) {
+        self.zeroize = enable;
+    }
+
+    // Zeroes the first `len` bytes of the staging buffer before they are
+    // removed, if enabled with `set_zeroize`.
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    fn erase_staged(&mut self, len: usize) {
+        if self.zeroize {
+            self.staging[..len].zeroize();
+        }
+    }
+
+    // Zeroes the first `len` bytes of the owned chunks before they are
+    // consumed, if enabled with `set_zeroize`.
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    fn erase_chunked(&mut self, mut len: usize) {
+        if !self.zeroize {
+            return;
+        }
+        for chunk in self.chunks.iter_mut() {
+            if len == 0 {
+                break;
+            }
+            let n = min(len, chunk.len());
+            chunk.erase(n);
+            len -= n;
         }
-        IntoChunks::new(self.chunks.into_iter())
+    }
+
+    // Zeroes all buffered bytes in the staging buffer and the owned
+    // chunks if enabled with `set_zeroize`, for use when the data are
+    // cleared or the container is dropped.
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    fn erase_all(&mut self) {
+        self.erase_chunked(self.chunks_len);
+        let len = self.staging.len();
+        self.erase_staged(len);
     }
 
     pub fn reserve_staging(&mut self) -> usize {
+        if self.is_aligned() || self.reserve_exact {
+            // Reallocating could move the data to a misaligned address,
+            // or grow the buffer beyond the chunk size, so always split off
+            // the staged bytes first.
+            self.flush();
+            self.reserve(self.chunk_size);
+            return self.staging.capacity();
+        }
+
         let cap = self.staging.capacity();
 
         // We are here when either:
@@ -124,7 +819,11 @@ impl Inner {
             // necessitate a new allocation. Split them off to a chunk
             // first, so that the new allocation does not have to copy
             // them and the total required capacity is `self.chunk_size`.
-            self.flush();
+            trace!(
+                staging_len = self.staging.len(),
+                "flushing staging buffer before reallocation"
+            );
+            self.flush_to_reserve(self.chunk_size);
             self.chunk_size
         } else {
             // This amount will get BytesMut to reuse the allocation and
@@ -133,29 +832,266 @@ impl Inner {
             // A virgin buffer will be allocated to `self.chunk_size`.
             self.chunk_size - cap
         };
-        self.staging.reserve(additional);
+        self.reserve(additional);
         self.staging.capacity()
     }
 
+    // Like `reserve_staging`, but if `additional` exceeds the chunk size,
+    // allocates the staging buffer to fit `additional` bytes at once
+    // instead, so that a large slice can be copied in one go.
+    pub fn reserve_staging_for(&mut self, additional: usize) -> usize {
+        if additional > self.chunk_size {
+            self.flush_to_reserve(additional);
+            self.reserve(additional);
+            self.staging.capacity()
+        } else {
+            self.reserve_staging()
+        }
+    }
+
+    // Reserves capacity in the staging buffer, counting the reservations
+    // that may result in an allocation.
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        if additional > self.staging.capacity() - self.staging.len() {
+            trace!(
+                staging_len = self.staging.len(),
+                staging_capacity = self.staging.capacity(),
+                additional,
+                "reserving staging buffer capacity"
+            );
+            self.reserve_count += 1;
+            if self.is_aligned() {
+                debug_assert!(self.staging.is_empty());
+                self.allocate_aligned(max(additional, self.chunk_size));
+                return;
+            }
+            if self.reserve_exact {
+                debug_assert!(self.staging.is_empty());
+                let capacity = max(additional, self.chunk_size);
+                let staging = self.allocate(capacity);
+                self.replace_staging(staging);
+                return;
+            }
+            if self.alloc.is_some() {
+                let len = self.staging.len();
+                let capacity = max(len + additional, self.chunk_size);
+                let mut staging = self.allocate(capacity);
+                staging.extend_from_slice(&self.staging);
+                self.replace_staging(staging);
+                return;
+            }
+        }
+        self.staging.reserve(additional);
+    }
+
+    // Replaces the empty staging buffer with a new allocation of at least
+    // `capacity` bytes, starting at an address aligned to `self.alignment`.
+    fn allocate_aligned(&mut self, capacity: usize) {
+        let align = self.alignment;
+        let mut staging = self.allocate(capacity + align - 1);
+        let offset = staging.as_ptr().align_offset(align);
+        staging.resize(offset, 0);
+        staging.advance(offset);
+        self.replace_staging(staging);
+    }
+
+    // Makes sure that the staging buffer has capacity to append at least
+    // `additional` bytes without exceeding the length of `staging_limit`,
+    // flushing the staging buffer if necessary. Returns the capacity of the
+    // staging buffer.
+    pub fn try_reserve(
+        &mut self,
+        additional: usize,
+        staging_limit: usize,
+    ) -> Result<usize, TryReserveError> {
+        let len = self.staging.len();
+        let cap = self.staging.capacity();
+        if additional <= cap - len && additional <= staging_limit - len {
+            return Ok(cap);
+        }
+        self.chunks.try_reserve(1)?;
+        let new_cap = max(additional, self.chunk_size);
+        // BytesMut does not provide fallible allocation, so the new
+        // staging buffer is allocated as a vector. A staging allocator
+        // is trusted to handle its failures itself.
+        let mut vec = Vec::new();
+        if new_cap > cap - len && self.alloc.is_none() {
+            vec.try_reserve_exact(new_cap.saturating_add(self.alignment - 1))?;
+        }
+        trace!(
+            staging_len = len,
+            "flushing staging buffer before reallocation"
+        );
+        self.flush_to_reserve(new_cap);
+        match buf_from_vec(vec) {
+            Some(staging) if staging.capacity() != 0 => {
+                self.install_staging(staging)
+            }
+            _ => self.reserve(new_cap),
+        }
+        Ok(self.staging.capacity())
+    }
+
+    // Replaces the empty staging buffer with `staging`, aligning
+    // the start of the buffer if needed.
+    fn install_staging(&mut self, mut staging: BytesMut) {
+        debug_assert!(self.staging.is_empty());
+        self.reserve_count += 1;
+        if self.is_aligned() {
+            let offset = staging.as_ptr().align_offset(self.alignment);
+            staging.resize(offset, 0);
+            staging.advance(offset);
+        }
+        self.replace_staging(staging);
+    }
+
     #[inline]
     pub fn remaining_mut(&self) -> usize {
-        self.staging.remaining_mut()
+        if self.is_bounded() {
+            let room = self.max_len.saturating_sub(self.remaining());
+            match &self.budget {
+                None => room,
+                Some(budget) => min(room, budget.available()),
+            }
+        } else {
+            self.staging.remaining_mut()
+        }
+    }
+
+    // Checks that `len` more bytes can be appended without exceeding
+    // the maximum length.
+    #[inline]
+    pub fn fits(&self, len: usize) -> bool {
+        !self.is_bounded() || self.remaining_mut() >= len
     }
 
     #[inline]
     pub unsafe fn advance_mut(&mut self, cnt: usize) {
+        #[cfg(feature = "tokio")]
+        if let Some(staged_at) = &mut self.staged_at {
+            if self.staging.is_empty() && cnt != 0 {
+                *staged_at = Some(Instant::now());
+            }
+        }
         self.staging.advance_mut(cnt);
+        self.charge(cnt);
+        self.evict();
+        #[cfg(feature = "test-util")]
+        if self.boundaries.advance(cnt) {
+            self.flush();
+        }
+    }
+
+    // Returns the number of bytes that can be written into
+    // the staging buffer before one of the limits is reached.
+    #[inline]
+    fn write_limit(&self) -> usize {
+        let limit = if self.is_bounded() {
+            self.remaining_mut()
+        } else {
+            usize::MAX
+        };
+        #[cfg(feature = "test-util")]
+        let limit = min(limit, self.boundaries.room());
+        limit
     }
 
     #[inline]
     pub fn chunk_mut(&mut self) -> &mut UninitSlice {
-        self.staging.chunk_mut()
+        let limit = self.write_limit();
+        let chunk = self.staging.chunk_mut();
+        if chunk.len() > limit {
+            &mut chunk[..limit]
+        } else {
+            chunk
+        }
     }
 
+    #[inline]
     pub fn remaining(&self) -> usize {
+        self.chunks_len + self.staging.len()
+    }
+
+    // Returns the offset of the first occurrence of `byte`
+    // in the buffered data.
+    pub fn find_byte(&self, byte: u8) -> Option<usize> {
+        let mut off = 0;
+        for slice in self.slices() {
+            if let Some(i) = memchr::memchr(byte, slice) {
+                return Some(off + i);
+            }
+            off += slice.len();
+        }
+        None
+    }
+
+    // Returns the offset of the first occurrence of `needle`
+    // in the buffered data, including the occurrences that span
+    // chunk boundaries.
+    pub fn find(&self, needle: &[u8]) -> Option<usize> {
+        match needle.len() {
+            0 => return Some(0),
+            1 => return self.find_byte(needle[0]),
+            _ => {}
+        }
+        let finder = memmem::Finder::new(needle);
+        // The tail of the preceding data that may start an occurrence
+        // spanning into the next slice, and its offset
+        let keep = needle.len() - 1;
+        let mut window = Vec::with_capacity(keep * 2);
+        let mut window_off = 0;
+        let mut off = 0;
+        for slice in self.slices() {
+            let had_tail = !window.is_empty();
+            window.extend_from_slice(&slice[..min(keep, slice.len())]);
+            if had_tail {
+                if let Some(i) = finder.find(&window) {
+                    return Some(window_off + i);
+                }
+            }
+            if let Some(i) = finder.find(slice) {
+                return Some(off + i);
+            }
+            off += slice.len();
+            if slice.len() >= keep {
+                window.clear();
+                window.extend_from_slice(&slice[slice.len() - keep..]);
+            } else {
+                let excess = window.len().saturating_sub(keep);
+                window.drain(..excess);
+            }
+            window_off = off - window.len();
+        }
+        None
+    }
+
+    #[inline]
+    pub fn is_utf8(&self) -> bool {
+        utf8::validate(self.slices())
+    }
+
+    // Iterates over slices of all the buffered bytes, in order.
+    pub fn slices(&self) -> impl Iterator<Item = &[u8]> {
         self.chunks
             .iter()
-            .fold(self.staging.len(), |sum, chunk| sum + chunk.len())
+            .map(|chunk| &chunk[..])
+            .chain(Some(&self.staging[..]).filter(|s| !s.is_empty()))
+    }
+
+    // Iterates over slices of all the buffered bytes, splitting
+    // the chunks into pieces of at most `limit` bytes.
+    #[cfg(feature = "rayon")]
+    fn slices_capped(&self, limit: usize) -> impl Iterator<Item = &[u8]> {
+        self.chunks
+            .iter()
+            .flat_map(move |chunk| chunk.chunks(limit))
+            .chain(Some(&self.staging[..]).filter(|s| !s.is_empty()))
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn par_chunks_capped(&self, limit: usize) -> ParChunks<'_> {
+        ParChunks::new(self.slices_capped(limit).collect())
     }
 
     #[inline]
@@ -168,63 +1104,210 @@ impl Inner {
     }
 
     pub fn advance(&mut self, mut cnt: usize) -> AdvanceStopped {
-        loop {
-            match self.chunks.front_mut() {
-                None => {
-                    self.staging.advance(cnt);
-                    return AdvanceStopped::InStaging(cnt);
-                }
-                Some(chunk) => {
-                    let len = chunk.len();
-                    if cnt < len {
-                        chunk.advance(cnt);
-                        return AdvanceStopped::InChunk;
-                    } else {
-                        cnt -= len;
-                        self.chunks.pop_front();
-                    }
-                }
+        assert!(
+            cnt <= self.remaining(),
+            "cannot advance past remaining: {} <= {}",
+            cnt,
+            self.remaining()
+        );
+        self.record_consumed(cnt);
+        self.wake_capacity();
+        #[cfg(feature = "zeroize")]
+        self.erase_chunked(cnt);
+        // Find the chunks consumed in their entirety and drop them at once
+        let mut exhausted = 0;
+        for chunk in self.chunks.iter() {
+            if cnt < chunk.len() {
+                break;
+            }
+            cnt -= chunk.len();
+            self.chunks_len -= chunk.len();
+            exhausted += 1;
+        }
+        self.chunks.discard_front(exhausted);
+        match self.chunks.front_mut() {
+            None => {
+                #[cfg(feature = "zeroize")]
+                self.erase_staged(cnt);
+                self.staging.advance(cnt);
+                AdvanceStopped::InStaging(cnt)
+            }
+            Some(chunk) => {
+                chunk.advance(cnt);
+                self.chunks_len -= cnt;
+                AdvanceStopped::InChunk
             }
         }
     }
 
+    #[inline]
+    pub fn chunk_capped(&self, limit: usize) -> &[u8] {
+        let chunk = self.chunk();
+        &chunk[..min(chunk.len(), limit)]
+    }
+
     pub fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
-        let n = {
-            let zipped = dst.iter_mut().zip(self.chunks.iter());
-            let len = zipped.len();
-            for (io_slice, chunk) in zipped {
-                *io_slice = IoSlice::new(chunk);
+        self.chunks_vectored_capped(dst, usize::MAX)
+    }
+
+    pub fn chunks_vectored_capped<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        limit: usize,
+    ) -> usize {
+        self.gather(dst, 0, limit, usize::MAX).0
+    }
+
+    pub fn chunks_vectored_limited<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        limit: usize,
+        max_bytes: usize,
+    ) -> (usize, usize) {
+        self.gather(dst, 0, limit, max_bytes)
+    }
+
+    pub fn chunks_vectored_at<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        offset: usize,
+        limit: usize,
+    ) -> usize {
+        self.gather(dst, offset, limit, usize::MAX).0
+    }
+
+    // Fills `dst` with slices of the buffered data starting at `offset`,
+    // in pieces of at most `limit` bytes, stopping when `max_bytes` have
+    // been gathered. Returns the number of slices filled and their total
+    // length.
+    fn gather<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        mut offset: usize,
+        limit: usize,
+        max_bytes: usize,
+    ) -> (usize, usize) {
+        let mut n = 0;
+        let mut total = 0;
+        for slice in self.slices() {
+            if offset >= slice.len() {
+                offset -= slice.len();
+                continue;
             }
-            len
-        };
+            let mut rest = &slice[offset..];
+            offset = 0;
+            while !rest.is_empty() {
+                if n == dst.len() || total == max_bytes {
+                    return (n, total);
+                }
+                let len = min(min(rest.len(), limit), max_bytes - total);
+                let (piece, tail) = rest.split_at(len);
+                dst[n] = IoSlice::new(piece);
+                rest = tail;
+                total += len;
+                n += 1;
+            }
+        }
+        (n, total)
+    }
 
-        if n < dst.len() && !self.staging.is_empty() {
-            dst[n] = IoSlice::new(&self.staging);
-            n + 1
-        } else {
-            n
+    // Copies the first `dst.len()` bytes into `dst` and consumes them,
+    // dropping the exhausted chunks at once. Returns the number of bytes
+    // consumed from the staging buffer.
+    pub fn copy_to_slice(&mut self, dst: &mut [u8]) -> usize {
+        assert!(
+            self.remaining() >= dst.len(),
+            "buffer too short to copy {} bytes, remaining = {}",
+            dst.len(),
+            self.remaining()
+        );
+        self.record_consumed(dst.len());
+        self.wake_capacity();
+        let mut off = 0;
+        let mut exhausted = 0;
+        let mut partial = 0;
+        for chunk in self.chunks.iter() {
+            if off == dst.len() {
+                break;
+            }
+            let n = min(chunk.len(), dst.len() - off);
+            dst[off..off + n].copy_from_slice(&chunk[..n]);
+            off += n;
+            if n == chunk.len() {
+                exhausted += 1;
+            } else {
+                partial = n;
+            }
         }
+        #[cfg(feature = "zeroize")]
+        self.erase_chunked(off);
+        self.chunks.discard_front(exhausted);
+        if let Some(chunk) = self.chunks.front_mut() {
+            chunk.advance(partial);
+        }
+        self.chunks_len -= off;
+        let staged = dst.len() - off;
+        if staged != 0 {
+            dst[off..].copy_from_slice(&self.staging[..staged]);
+            #[cfg(feature = "zeroize")]
+            self.erase_staged(staged);
+            self.staging.advance(staged);
+        }
+        staged
     }
 
     pub fn copy_to_bytes(&mut self, len: usize) -> Bytes {
-        if self.chunks.is_empty() {
-            return self.staging.copy_to_bytes(len);
+        assert!(
+            len <= self.remaining(),
+            "buffer too short to copy {} bytes, remaining = {}",
+            len,
+            self.remaining()
+        );
+        self.record_consumed(len);
+        self.wake_capacity();
+        match self.chunks.front_mut() {
+            None => return self.staging.copy_to_bytes(len),
+            Some(chunk) if chunk.len() == len => {
+                // Hand out the chunk itself rather than a new reference
+                self.chunks_len -= len;
+                let chunk = self.chunks.pop_front().unwrap();
+                return chunk.into_bytes();
+            }
+            Some(chunk) if chunk.len() > len => {
+                self.chunks_len -= len;
+                return chunk.split_to(len);
+            }
+            Some(_) => {}
         }
-        let mut to_copy = min(len, self.remaining());
+        let mut to_copy = len;
         let mut buf = BytesMut::with_capacity(to_copy);
         loop {
             match self.chunks.front_mut() {
                 None => {
-                    buf.put((&mut self.staging).take(to_copy));
+                    buf.extend_from_slice(&self.staging[..to_copy]);
+                    #[cfg(feature = "zeroize")]
+                    self.erase_staged(to_copy);
+                    self.staging.advance(to_copy);
                     break;
                 }
                 Some(chunk) => {
                     if chunk.len() > to_copy {
-                        buf.put(chunk.take(to_copy));
+                        buf.extend_from_slice(&chunk[..to_copy]);
+                        #[cfg(feature = "zeroize")]
+                        if self.zeroize {
+                            chunk.erase(to_copy);
+                        }
+                        chunk.advance(to_copy);
+                        self.chunks_len -= to_copy;
                         break;
                     } else {
                         buf.extend_from_slice(chunk);
+                        #[cfg(feature = "zeroize")]
+                        if self.zeroize {
+                            chunk.erase(chunk.len());
+                        }
                         to_copy -= chunk.len();
+                        self.chunks_len -= chunk.len();
                     }
                 }
             }
@@ -232,4 +1315,115 @@ impl Inner {
         }
         buf.freeze()
     }
+
+    // Calls `f` with the mutable slices of the last `len` bytes of
+    // the buffered data, in order. Shared and storage chunks in the range
+    // are replaced with owned copies.
+    pub fn for_each_tail_mut<F>(&mut self, len: usize, mut f: F)
+    where
+        F: FnMut(&mut [u8]),
+    {
+        assert!(
+            len <= self.remaining(),
+            "tail length out of bounds: {} <= {}",
+            len,
+            self.remaining()
+        );
+        let mut skip = self.remaining() - len;
+        for chunk in self.chunks.iter_mut() {
+            if skip >= chunk.len() {
+                skip -= chunk.len();
+                continue;
+            }
+            f(&mut chunk.make_mut()[skip..]);
+            skip = 0;
+        }
+        if self.staging.len() > skip {
+            f(&mut self.staging[skip..]);
+        }
+    }
+
+    // Splits off the first `at` bytes into a new container with the same
+    // preferred chunk size, moving or splitting the chunks without copying.
+    pub fn split_to(&mut self, at: usize) -> Inner<S> {
+        assert!(
+            at <= self.remaining(),
+            "split_to out of bounds: {} <= {}",
+            at,
+            self.remaining()
+        );
+        self.record_consumed(at);
+        self.wake_capacity();
+        let mut head = Inner::with_chunk_size(self.chunk_size);
+        #[cfg(feature = "zeroize")]
+        head.set_zeroize(self.zeroize);
+        let mut rest = at;
+        while rest != 0 {
+            let chunk = match self.chunks.front_mut() {
+                Some(chunk) if chunk.len() <= rest => {
+                    self.chunks.pop_front().unwrap()
+                }
+                Some(chunk) => chunk.split_chunk_to(rest),
+                None => {
+                    // The rest comes from the staging buffer
+                    head.chunks.push_back(self.staging.split_to(rest).into());
+                    head.chunks_len += rest;
+                    break;
+                }
+            };
+            self.chunks_len -= chunk.len();
+            rest -= chunk.len();
+            head.chunks_len += chunk.len();
+            head.chunks.push_back(chunk);
+        }
+        head
+    }
+}
+
+// Converts an empty vector into a buffer taking over its allocation,
+// or returns `None` if the allocation could not be reused.
+fn buf_from_vec(vec: Vec<u8>) -> Option<BytesMut> {
+    debug_assert!(vec.is_empty());
+    let capacity = vec.capacity();
+    // bytes 1.1 does not implement `From<Vec<u8>>` for `BytesMut`.
+    // Collecting a vector iterator that has not been advanced reuses
+    // the allocation in the current standard library, but this is not
+    // guaranteed, so the capacity is checked.
+    let buf = vec.into_iter().collect::<BytesMut>();
+    (buf.capacity() >= capacity).then_some(buf)
+}
+
+// Completes the run of small chunks being compacted.
+fn flush_run<S: ChunkStorage>(
+    chunks: &mut ChunkQueue<S>,
+    head: &mut Option<Chunk<S>>,
+    merged: &mut BytesMut,
+) {
+    if let Some(chunk) = head.take() {
+        chunks.push_back(chunk);
+    } else if !merged.is_empty() {
+        chunks.push_back(Chunk::Owned(merged.split()));
+    }
+}
+
+// Lengths of the pieces of at most `limit` bytes that a chunk of `len`
+// bytes is exposed as.
+fn piece_lens(len: usize, limit: usize) -> impl Iterator<Item = usize> {
+    (0..len.div_ceil(limit)).map(move |i| min(len - i * limit, limit))
+}
+
+// Copies `total` bytes from the beginning of `bufs` into `dst`.
+pub(crate) fn put_vectored<B: BufMut>(
+    dst: &mut B,
+    bufs: &[IoSlice<'_>],
+    mut total: usize,
+) {
+    for buf in bufs {
+        if total == 0 {
+            break;
+        }
+        let len = min(buf.len(), total);
+        dst.put_slice(&buf[..len]);
+        total -= len;
+    }
 }
@@ -0,0 +1,180 @@
+//! [Consistent Overhead Byte Stuffing][cobs] of frames buffered in
+//! `ChunkedBytes`.
+//!
+//! `Cobs` encodes frames from any `Buf` into `ChunkedBytes`, replacing the
+//! zero bytes of the data so that a zero byte can delimit the frames, and
+//! decodes the delimited frames from the buffered data as they are
+//! received. The runs of non-zero bytes in a decoded frame are split off
+//! the received chunks, so only small runs are copied.
+//!
+//! With the `codec` feature, `Cobs` also implements the `Encoder`
+//! and `Decoder` traits of the `codec` module.
+//!
+//! [cobs]: https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing
+
+use crate::ChunkedBytes;
+
+use bytes::{Buf, BufMut};
+use memchr::memchr;
+
+use std::cmp::min;
+use std::io;
+
+const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+// The longest run of non-zero bytes that a block can hold
+const MAX_RUN: usize = 254;
+
+/// Encodes and decodes frames delimited by zero bytes with COBS.
+///
+/// Encoded frames are terminated with a zero byte. Decoding skips empty
+/// frames, so a zero byte can also be sent ahead of a frame to
+/// resynchronize the receiver. The length of an encoded frame in
+/// the received data is limited by the maximum frame length, which is
+/// 8 MiB by default.
+#[derive(Clone, Debug)]
+pub struct Cobs {
+    max_frame_len: usize,
+}
+
+impl Default for Cobs {
+    #[inline]
+    fn default() -> Self {
+        Cobs {
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+}
+
+impl Cobs {
+    /// Creates a codec with the default maximum frame length.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the maximum length of an encoded frame to decode.
+    #[inline]
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Sets the maximum length of an encoded frame to decode.
+    #[inline]
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.max_frame_len = len;
+    }
+
+    /// Consumes the data remaining in `src`, writing it into `dst` as
+    /// an encoded frame followed by the zero delimiter.
+    pub fn encode_frame<B: Buf>(&self, mut src: B, dst: &mut ChunkedBytes) {
+        let mut run = [0; MAX_RUN];
+        let mut run_len = 0;
+        while src.has_remaining() {
+            let chunk = src.chunk();
+            let room = MAX_RUN - run_len;
+            let (n, zero) = match memchr(0, &chunk[..min(chunk.len(), room)]) {
+                Some(pos) => (pos, true),
+                None => (min(chunk.len(), room), false),
+            };
+            run[run_len..run_len + n].copy_from_slice(&chunk[..n]);
+            run_len += n;
+            if zero || run_len == MAX_RUN {
+                put_block(dst, &run[..run_len]);
+                run_len = 0;
+            }
+            src.advance(n + zero as usize);
+        }
+        put_block(dst, &run[..run_len]);
+        dst.put_u8(0);
+    }
+
+    /// Decodes a frame from the data buffered in `src`.
+    ///
+    /// Returns `Ok(None)`, consuming nothing but the leading zero bytes,
+    /// if the delimiter of the frame has not yet been received.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData`, consuming the frame,
+    /// if the frame is not correctly encoded, or if it exceeds the maximum
+    /// frame length. In the latter case, the data is consumed up to
+    /// the next delimiter, if one has been received.
+    pub fn decode_frame(
+        &self,
+        src: &mut ChunkedBytes,
+    ) -> io::Result<Option<ChunkedBytes>> {
+        loop {
+            let pos = match src.find_byte(0) {
+                Some(pos) => pos,
+                None if src.remaining() > self.max_frame_len => {
+                    src.clear();
+                    return Err(frame_too_long());
+                }
+                None => return Ok(None),
+            };
+            let frame = src.split_to(pos);
+            src.advance(1);
+            if pos > self.max_frame_len {
+                return Err(frame_too_long());
+            }
+            if pos != 0 {
+                return unstuff(frame).map(Some);
+            }
+        }
+    }
+}
+
+// Writes a block of the encoding, consisting of the code byte
+// and the run of non-zero bytes.
+fn put_block(dst: &mut ChunkedBytes, run: &[u8]) {
+    dst.put_u8(run.len() as u8 + 1);
+    dst.put_slice(run);
+}
+
+// Restores the zero bytes of an encoded frame.
+fn unstuff(mut frame: ChunkedBytes) -> io::Result<ChunkedBytes> {
+    let mut data = ChunkedBytes::with_chunk_size_hint(frame.chunk_size_hint());
+    while frame.has_remaining() {
+        let code = frame.get_u8() as usize;
+        let run_len = code - 1;
+        if run_len > frame.remaining() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated COBS block",
+            ));
+        }
+        data.put_bytes(frame.copy_to_bytes(run_len));
+        if run_len != MAX_RUN && frame.has_remaining() {
+            data.put_u8(0);
+        }
+    }
+    Ok(data)
+}
+
+fn frame_too_long() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "COBS frame is too long")
+}
+
+#[cfg(feature = "codec")]
+impl<B: Buf> crate::codec::Encoder<B> for Cobs {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: B, dst: &mut ChunkedBytes) -> io::Result<()> {
+        self.encode_frame(item, dst);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "codec")]
+impl crate::codec::Decoder for Cobs {
+    type Item = ChunkedBytes;
+    type Error = io::Error;
+
+    fn decode(
+        &mut self,
+        src: &mut ChunkedBytes,
+    ) -> io::Result<Option<ChunkedBytes>> {
+        self.decode_frame(src)
+    }
+}
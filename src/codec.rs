@@ -0,0 +1,381 @@
+//! Frame encoding into and decoding from `ChunkedBytes`, in the style of
+//! `tokio_util::codec`.
+//!
+//! The `Encoder` trait in this module is the counterpart of the `Encoder`
+//! trait of `tokio-util`, with `ChunkedBytes` as the destination buffer
+//! in place of `BytesMut`. This allows encoders to append large payloads
+//! as `Bytes` without copying, with `put_bytes`. `FramedWrite` uses
+//! an `Encoder` to provide a `Sink` of frames writing into
+//! a `tokio::io::AsyncWrite`, gathering the buffered chunks for vectored
+//! output.
+//!
+//! Likewise, the `Decoder` trait takes `ChunkedBytes` as the source buffer,
+//! and `FramedRead` uses a `Decoder` to provide a `Stream` of frames read
+//! from a `tokio::io::AsyncRead`. Decoders can extract payloads that are
+//! contained in a single received chunk as `Bytes` without copying,
+//! using `Buf::copy_to_bytes`.
+//!
+//! This module is only available with the `codec` feature.
+
+use crate::ChunkedBytes;
+
+use bytes::Buf;
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const DEFAULT_HIGH_WATERMARK: usize = 64 * 1024;
+
+/// Encodes frames into `ChunkedBytes`.
+pub trait Encoder<Item> {
+    /// The type of encoding errors.
+    ///
+    /// `FramedWrite` requires this to be convertible from `io::Error`
+    /// to report output errors.
+    type Error: From<io::Error>;
+
+    /// Encodes a frame into the buffer provided.
+    fn encode(
+        &mut self,
+        item: Item,
+        dst: &mut ChunkedBytes,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Decodes frames from `ChunkedBytes`.
+pub trait Decoder {
+    /// The type of decoded frames.
+    type Item;
+
+    /// The type of decoding errors.
+    ///
+    /// `FramedRead` requires this to be convertible from `io::Error`
+    /// to report input errors.
+    type Error: From<io::Error>;
+
+    /// Attempts to decode a frame from the buffered data.
+    ///
+    /// If a complete frame is available, the decoder should consume its
+    /// data from `src` and return it. If more data is needed, `Ok(None)`
+    /// should be returned.
+    fn decode(
+        &mut self,
+        src: &mut ChunkedBytes,
+    ) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// Attempts to decode a frame when no more data will be received.
+    ///
+    /// The default implementation calls `decode`, and returns an error
+    /// if no frame is decoded while data remains in `src`.
+    fn decode_eof(
+        &mut self,
+        src: &mut ChunkedBytes,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "bytes remaining on stream",
+            )
+            .into()),
+        }
+    }
+}
+
+/// A `Stream` of frames read from an `AsyncRead` and decoded with
+/// a `Decoder`.
+///
+/// Input is read directly into the staging buffer of `ChunkedBytes`,
+/// splitting off complete chunks of the preferred size. Reaching
+/// the maximum length of the buffer, if one is set with
+/// `ChunkedBytes::set_max_len` on the buffer accessed with
+/// `read_buffer_mut`, is treated as end of input.
+///
+/// After an error is produced by the decoder or the reader, the stream
+/// terminates and the decoder is not called again.
+#[derive(Debug)]
+pub struct FramedRead<R, D> {
+    inner: R,
+    state: ReadState<D>,
+}
+
+#[derive(Debug)]
+struct ReadState<D> {
+    decoder: D,
+    buf: ChunkedBytes,
+    eof: bool,
+    done: bool,
+}
+
+impl<R, D> FramedRead<R, D> {
+    /// Creates a new `FramedRead` reading from `inner` frames decoded
+    /// with `decoder`.
+    pub fn new(inner: R, decoder: D) -> Self {
+        FramedRead {
+            inner,
+            state: ReadState {
+                decoder,
+                buf: ChunkedBytes::new(),
+                eof: false,
+                done: false,
+            },
+        }
+    }
+
+    /// Returns a shared reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns a shared reference to the decoder.
+    pub fn decoder(&self) -> &D {
+        &self.state.decoder
+    }
+
+    /// Returns a mutable reference to the decoder.
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.state.decoder
+    }
+
+    /// Returns a reference to the buffer of received data.
+    pub fn read_buffer(&self) -> &ChunkedBytes {
+        &self.state.buf
+    }
+
+    /// Returns a mutable reference to the buffer of received data.
+    pub fn read_buffer_mut(&mut self) -> &mut ChunkedBytes {
+        &mut self.state.buf
+    }
+
+    /// Consumes the `FramedRead`, returning the inner reader.
+    /// Any buffered data that has not been decoded is discarded.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut R>, &mut ReadState<D>) {
+        // Safety: `inner` is pinned structurally, `state` is not;
+        // `FramedRead` does not implement `Drop` and is `Unpin`
+        // if `R` and `D` are.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &mut this.state)
+        }
+    }
+}
+
+impl<R, D> Stream for FramedRead<R, D>
+where
+    R: AsyncRead,
+    D: Decoder,
+{
+    type Item = Result<D::Item, D::Error>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let (mut inner, state) = self.project();
+        loop {
+            if state.done {
+                return Poll::Ready(None);
+            }
+            if state.eof {
+                let res = state.decoder.decode_eof(&mut state.buf);
+                match res {
+                    Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                    Ok(None) => state.done = true,
+                    Err(e) => {
+                        state.done = true;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                continue;
+            }
+            match state.decoder.decode(&mut state.buf) {
+                Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                Ok(None) => {}
+                Err(e) => {
+                    state.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+            match state.buf.poll_read_from(cx, inner.as_mut()) {
+                Poll::Ready(Ok(0)) => state.eof = true,
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => {
+                    state.done = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// A `Sink` of frames encoded with an `Encoder` and written to
+/// an `AsyncWrite`.
+///
+/// Encoded data is accumulated in `ChunkedBytes`. When the buffered length
+/// reaches the high watermark, `poll_ready` writes out the buffered data
+/// with vectored writes until the length is below the low watermark.
+/// By default, the low watermark is 0 and the high watermark is 64 KiB;
+/// these can be changed with `ChunkedBytes::set_watermarks` on the buffer
+/// accessed with `write_buffer_mut`.
+#[derive(Debug)]
+pub struct FramedWrite<W, E> {
+    inner: W,
+    encoder: E,
+    buf: ChunkedBytes,
+}
+
+impl<W, E> FramedWrite<W, E> {
+    /// Creates a new `FramedWrite` writing to `inner` frames encoded
+    /// with `encoder`.
+    pub fn new(inner: W, encoder: E) -> Self {
+        let mut buf = ChunkedBytes::new();
+        buf.set_watermarks(0, DEFAULT_HIGH_WATERMARK);
+        FramedWrite {
+            inner,
+            encoder,
+            buf,
+        }
+    }
+
+    /// Returns a shared reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns a shared reference to the encoder.
+    pub fn encoder(&self) -> &E {
+        &self.encoder
+    }
+
+    /// Returns a mutable reference to the encoder.
+    pub fn encoder_mut(&mut self) -> &mut E {
+        &mut self.encoder
+    }
+
+    /// Returns a reference to the buffer of encoded data.
+    pub fn write_buffer(&self) -> &ChunkedBytes {
+        &self.buf
+    }
+
+    /// Returns a mutable reference to the buffer of encoded data.
+    pub fn write_buffer_mut(&mut self) -> &mut ChunkedBytes {
+        &mut self.buf
+    }
+
+    /// Consumes the `FramedWrite`, returning the inner writer.
+    /// Any buffered data that has not been written out is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn project(
+        self: Pin<&mut Self>,
+    ) -> (Pin<&mut W>, &mut E, &mut ChunkedBytes) {
+        // Safety: `inner` is pinned structurally, the other fields are not;
+        // `FramedWrite` does not implement `Drop` and is `Unpin`
+        // if `W` and `E` are.
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (
+                Pin::new_unchecked(&mut this.inner),
+                &mut this.encoder,
+                &mut this.buf,
+            )
+        }
+    }
+}
+
+impl<W: AsyncWrite, E> FramedWrite<W, E> {
+    // Writes out the buffered data until its length is not greater than
+    // `low`.
+    fn poll_write_down(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        low: usize,
+    ) -> Poll<io::Result<()>> {
+        let (mut inner, _, buf) = self.project();
+        while buf.remaining() > low {
+            match buf.poll_write_to(cx, inner.as_mut()) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the buffered data",
+                    )))
+                }
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W, E, Item> Sink<Item> for FramedWrite<W, E>
+where
+    W: AsyncWrite,
+    E: Encoder<Item>,
+{
+    type Error = E::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), E::Error>> {
+        if self.buf.is_above_high_watermark() {
+            let (low, _) = self.buf.watermarks();
+            self.poll_write_down(cx, low).map_err(Into::into)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), E::Error> {
+        let (_, encoder, buf) = self.project();
+        encoder.encode(item, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), E::Error>> {
+        match self.as_mut().poll_write_down(cx, 0) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+            Poll::Pending => return Poll::Pending,
+        }
+        self.project().0.poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), E::Error>> {
+        match self.as_mut().poll_write_down(cx, 0) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+            Poll::Pending => return Poll::Pending,
+        }
+        self.project().0.poll_shutdown(cx).map_err(Into::into)
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{loosely, strictly};
+
+use bytes::Buf;
+use subtle::{Choice, ConstantTimeEq};
+
+use std::io::IoSlice;
+
+macro_rules! impl_ct_eq {
+    ($t:ty) => {
+        impl $t {
+            /// Compares the buffered data with `other` in constant time,
+            /// across chunk boundaries.
+            ///
+            /// The time taken depends on the lengths of the data and
+            /// the chunk layout, but not on the byte values. If the lengths
+            /// differ, the comparison returns false without inspecting
+            /// the data.
+            ///
+            /// This method is only available with the `subtle` feature.
+            pub fn ct_eq(&self, other: &[u8]) -> Choice {
+                if self.remaining() != other.len() {
+                    return Choice::from(0);
+                }
+                let mut slices = vec![IoSlice::new(&[]); self.vectored_len()];
+                let n = self.chunks_vectored(&mut slices);
+                let mut eq = Choice::from(1);
+                let mut offset = 0;
+                for slice in &slices[..n] {
+                    let end = offset + slice.len();
+                    eq &= slice[..].ct_eq(&other[offset..end]);
+                    offset = end;
+                }
+                eq
+            }
+        }
+    };
+}
+
+impl_ct_eq!(loosely::ChunkedBytes);
+impl_ct_eq!(strictly::ChunkedBytes);
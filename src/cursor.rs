@@ -0,0 +1,247 @@
+use crate::{loosely, strictly};
+
+use bytes::Bytes;
+
+use std::cmp::min;
+use std::fmt;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+/// A seekable cursor over the data of a consumed `ChunkedBytes` container.
+///
+/// `ChunkedCursor` can be created from either variant of `ChunkedBytes`
+/// with `From`. It implements `Read`, `BufRead`, and `Seek`, mapping
+/// the position onto the chunks, so the data can be read repeatedly
+/// or in ranges without flattening it into a contiguous buffer.
+///
+/// With the `tokio` feature, `ChunkedCursor` also implements
+/// `tokio::io::AsyncRead` and `tokio::io::AsyncSeek`. With the `futures-io`
+/// feature, the `futures-io` traits are implemented likewise.
+pub struct ChunkedCursor {
+    chunks: Vec<Bytes>,
+    // Offset of each chunk from the start of the data
+    offsets: Vec<usize>,
+    len: usize,
+    pos: u64,
+}
+
+impl ChunkedCursor {
+    fn new(chunks: impl Iterator<Item = Bytes>) -> Self {
+        let mut len = 0;
+        let chunks =
+            chunks.filter(|chunk| !chunk.is_empty()).collect::<Vec<_>>();
+        let offsets = chunks
+            .iter()
+            .map(|chunk| {
+                let offset = len;
+                len += chunk.len();
+                offset
+            })
+            .collect();
+        ChunkedCursor {
+            chunks,
+            offsets,
+            len,
+            pos: 0,
+        }
+    }
+
+    /// Returns the total length of the data.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there is no data.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the current position of the cursor.
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the position of the cursor. The position may be past the end
+    /// of the data, in which case reading produces no data.
+    #[inline]
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// Returns a slice of the chunks.
+    #[inline]
+    pub fn chunks(&self) -> &[Bytes] {
+        &self.chunks
+    }
+
+    // Returns the remaining part of the chunk at the current position,
+    // or an empty slice if the position is at or past the end.
+    fn current_chunk(&self) -> &[u8] {
+        if self.pos >= self.len as u64 {
+            return &[];
+        }
+        let pos = self.pos as usize;
+        let index = self.offsets.partition_point(|&offset| offset <= pos) - 1;
+        &self.chunks[index][pos - self.offsets[index]..]
+    }
+
+    fn seek_pos(&self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => return Ok(pos),
+            SeekFrom::End(offset) => (self.len as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        let new_pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })
+    }
+}
+
+impl From<loosely::ChunkedBytes> for ChunkedCursor {
+    fn from(buf: loosely::ChunkedBytes) -> Self {
+        ChunkedCursor::new(buf.into_chunks())
+    }
+}
+
+impl From<strictly::ChunkedBytes> for ChunkedCursor {
+    fn from(buf: strictly::ChunkedBytes) -> Self {
+        ChunkedCursor::new(buf.into_chunks())
+    }
+}
+
+impl fmt::Debug for ChunkedCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkedCursor")
+            .field("chunks", &self.chunks.len())
+            .field("len", &self.len)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+impl Read for ChunkedCursor {
+    fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
+        let mut total = 0;
+        while !buf.is_empty() {
+            let chunk = self.current_chunk();
+            if chunk.is_empty() {
+                break;
+            }
+            let len = min(chunk.len(), buf.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+            buf = &mut buf[len..];
+            self.pos += len as u64;
+            total += len;
+        }
+        Ok(total)
+    }
+}
+
+impl BufRead for ChunkedCursor {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.current_chunk())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl Seek for ChunkedCursor {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.seek_pos(pos)?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::*;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+    impl AsyncRead for ChunkedCursor {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            while buf.remaining() != 0 {
+                let chunk = this.current_chunk();
+                if chunk.is_empty() {
+                    break;
+                }
+                let len = min(chunk.len(), buf.remaining());
+                buf.put_slice(&chunk[..len]);
+                this.pos += len as u64;
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncSeek for ChunkedCursor {
+        fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> io::Result<()> {
+            self.get_mut().seek(pos).map(|_| ())
+        }
+
+        fn poll_complete(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<io::Result<u64>> {
+            Poll::Ready(Ok(self.pos))
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_impl {
+    use super::*;
+    use futures_io::{AsyncBufRead, AsyncRead, AsyncSeek};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    impl AsyncRead for ChunkedCursor {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            dst: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().read(dst))
+        }
+    }
+
+    impl AsyncBufRead for ChunkedCursor {
+        fn poll_fill_buf(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<io::Result<&[u8]>> {
+            Poll::Ready(Ok(self.get_mut().current_chunk()))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().pos += amt as u64;
+        }
+    }
+
+    impl AsyncSeek for ChunkedCursor {
+        fn poll_seek(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            pos: SeekFrom,
+        ) -> Poll<io::Result<u64>> {
+            Poll::Ready(self.get_mut().seek(pos))
+        }
+    }
+}
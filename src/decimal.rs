@@ -0,0 +1,144 @@
+use std::mem::MaybeUninit;
+
+// Enough to fit the decimal representation of `i128::MIN`.
+const MAX_LEN: usize = 40;
+
+const DIGIT_PAIRS: &[u8; 200] = b"\
+    0001020304050607080910111213141516171819\
+    2021222324252627282930313233343536373839\
+    4041424344454647484950515253545556575859\
+    6061626364656667686970717273747576777879\
+    8081828384858687888990919293949596979899";
+
+/// A buffer for formatting the decimal representation of an integer.
+pub struct DecimalBuf {
+    bytes: [MaybeUninit<u8>; MAX_LEN],
+    pos: usize,
+}
+
+impl DecimalBuf {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        DecimalBuf {
+            bytes: [MaybeUninit::uninit(); MAX_LEN],
+            pos: MAX_LEN,
+        }
+    }
+
+    #[inline]
+    fn push_front(&mut self, byte: u8) {
+        self.pos -= 1;
+        self.bytes[self.pos] = MaybeUninit::new(byte);
+    }
+
+    #[inline]
+    fn push_digit_pair(&mut self, n: usize) {
+        self.push_front(DIGIT_PAIRS[n * 2 + 1]);
+        self.push_front(DIGIT_PAIRS[n * 2]);
+    }
+
+    fn format_u64(&mut self, mut n: u64) {
+        while n >= 100 {
+            let rem = (n % 100) as usize;
+            n /= 100;
+            self.push_digit_pair(rem);
+        }
+        if n >= 10 {
+            self.push_digit_pair(n as usize);
+        } else {
+            self.push_front(b'0' + n as u8);
+        }
+    }
+
+    fn format_u128(&mut self, mut n: u128) {
+        const DIV: u128 = 10_000_000_000_000_000_000;
+        // Peel off 19-digit groups so the bulk of the work is done
+        // in 64-bit arithmetic.
+        while n > u64::MAX as u128 {
+            let mut rem = (n % DIV) as u64;
+            n /= DIV;
+            for _ in 0..19 {
+                self.push_front(b'0' + (rem % 10) as u8);
+                rem /= 10;
+            }
+        }
+        self.format_u64(n as u64);
+    }
+
+    #[inline]
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        let digits = &self.bytes[self.pos..];
+        // Safety: all bytes from `pos` onwards have been initialized.
+        unsafe { &*(digits as *const [MaybeUninit<u8>] as *const [u8]) }
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Primitive integer types that can be appended to `ChunkedBytes`
+/// in decimal representation with `put_itoa`.
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait Integer: private::Sealed + Copy {
+    #[doc(hidden)]
+    fn format_decimal(self, buf: &mut DecimalBuf);
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl Integer for $t {
+                #[inline]
+                fn format_decimal(self, buf: &mut DecimalBuf) {
+                    buf.format_u64(self as u64)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl Integer for $t {
+                #[inline]
+                fn format_decimal(self, buf: &mut DecimalBuf) {
+                    buf.format_u64(self.unsigned_abs() as u64);
+                    if self < 0 {
+                        buf.push_front(b'-');
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, usize);
+impl_signed!(i8, i16, i32, i64, isize);
+
+impl private::Sealed for u128 {}
+
+impl Integer for u128 {
+    #[inline]
+    fn format_decimal(self, buf: &mut DecimalBuf) {
+        buf.format_u128(self)
+    }
+}
+
+impl private::Sealed for i128 {}
+
+impl Integer for i128 {
+    #[inline]
+    fn format_decimal(self, buf: &mut DecimalBuf) {
+        buf.format_u128(self.unsigned_abs());
+        if self < 0 {
+            buf.push_front(b'-');
+        }
+    }
+}
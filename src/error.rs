@@ -0,0 +1,38 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+
+/// The error returned by the fallible writing methods of `ChunkedBytes`
+/// when the data would not fit into the container's maximum length.
+///
+/// The value that has been rejected can be recovered with `into_inner`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CapacityError<T = ()> {
+    value: T,
+}
+
+impl<T> CapacityError<T> {
+    #[inline]
+    pub(crate) fn new(value: T) -> Self {
+        CapacityError { value }
+    }
+
+    /// Consumes the error, returning the value that could not be written.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapacityError").finish()
+    }
+}
+
+impl<T> Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("maximum length of the buffer exceeded")
+    }
+}
+
+impl<T> Error for CapacityError<T> {}
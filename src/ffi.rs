@@ -0,0 +1,122 @@
+//! A minimal C API for `loosely::ChunkedBytes`.
+//!
+//! The functions in this module have C linkage and unmangled names,
+//! so that C or C++ code linking with a Rust library that embeds this crate
+//! can fill and drain a buffer. The buffer is an opaque object created with
+//! `chunked_bytes_new` and released with `chunked_bytes_free`.
+//! On Unix, the buffered data can be gathered into `iovec` entries with
+//! `chunked_bytes_iovecs` for vectored output.
+//!
+//! This module is only available with the `ffi` feature.
+
+use crate::loosely::ChunkedBytes;
+
+use bytes::{Buf, BufMut};
+
+use std::cmp::min;
+#[cfg(unix)]
+use std::io::IoSlice;
+use std::slice;
+
+/// Creates a new buffer with the given preferred chunk size,
+/// or the default size if `chunk_size` is 0.
+///
+/// The returned buffer must be released with `chunked_bytes_free`.
+#[no_mangle]
+pub extern "C" fn chunked_bytes_new(chunk_size: usize) -> *mut ChunkedBytes {
+    let buf = if chunk_size == 0 {
+        ChunkedBytes::new()
+    } else {
+        ChunkedBytes::with_chunk_size_hint(chunk_size)
+    };
+    Box::into_raw(Box::new(buf))
+}
+
+/// Releases a buffer created with `chunked_bytes_new`.
+/// If `buf` is null, this function does nothing.
+///
+/// # Safety
+///
+/// `buf` must be null or a pointer returned by `chunked_bytes_new` that has
+/// not been released.
+#[no_mangle]
+pub unsafe extern "C" fn chunked_bytes_free(buf: *mut ChunkedBytes) {
+    if !buf.is_null() {
+        drop(Box::from_raw(buf));
+    }
+}
+
+/// Appends `len` bytes from `data` to the buffer, copying them.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer pointer. `data` must point to `len` readable
+/// bytes, or may be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn chunked_bytes_put(
+    buf: *mut ChunkedBytes,
+    data: *const u8,
+    len: usize,
+) {
+    if len != 0 {
+        (*buf).put_slice(slice::from_raw_parts(data, len));
+    }
+}
+
+/// Returns the number of bytes in the buffer.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer pointer.
+#[no_mangle]
+pub unsafe extern "C" fn chunked_bytes_remaining(
+    buf: *const ChunkedBytes,
+) -> usize {
+    (*buf).remaining()
+}
+
+/// Removes up to `cnt` bytes from the front of the buffer, typically after
+/// they have been written out. Returns the number of bytes removed.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer pointer.
+#[no_mangle]
+pub unsafe extern "C" fn chunked_bytes_advance(
+    buf: *mut ChunkedBytes,
+    cnt: usize,
+) -> usize {
+    let buf = &mut *buf;
+    let cnt = min(cnt, buf.remaining());
+    buf.advance(cnt);
+    cnt
+}
+
+/// Fills up to `len` `iovec` entries at `dst` with the slices of
+/// the buffered data, in order. Returns the number of entries filled.
+///
+/// The entries are valid until the buffer is modified or released.
+///
+/// This function is only available on Unix platforms.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer pointer. `dst` must point to `len` writable
+/// `iovec` entries, or may be null if `len` is 0.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn chunked_bytes_iovecs(
+    buf: *const ChunkedBytes,
+    dst: *mut libc::iovec,
+    len: usize,
+) -> usize {
+    let mut slices = vec![IoSlice::new(&[]); len];
+    let n = (*buf).chunks_vectored(&mut slices);
+    for (i, slice) in slices[..n].iter().enumerate() {
+        dst.add(i).write(libc::iovec {
+            iov_base: slice.as_ptr() as *mut _,
+            iov_len: slice.len(),
+        });
+    }
+    n
+}
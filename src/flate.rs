@@ -0,0 +1,252 @@
+//! Streaming deflate, zlib, and gzip compression into and decompression
+//! from `ChunkedBytes`.
+//!
+//! `CompressWriter` and `DecompressWriter` are writers feeding the data
+//! through an encoder or a decoder of the `flate2` crate, appending
+//! the output to `ChunkedBytes`. The input can be written from any `Buf`
+//! chunk by chunk with `write_buf`, so neither the input nor the output
+//! needs to be contiguous. The functions `compress_into` and
+//! `decompress_into` process the whole of the input in one call.
+//!
+//! This module is only available with the `flate2` feature.
+
+use crate::ChunkedBytes;
+
+use bytes::buf::{BufMut, Writer};
+use bytes::Buf;
+use flate2::write::{
+    DeflateDecoder, DeflateEncoder, GzDecoder, GzEncoder, ZlibDecoder,
+    ZlibEncoder,
+};
+
+pub use flate2::Compression;
+
+use std::io::{self, Write};
+
+/// The format of the compressed data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// A raw deflate stream.
+    Deflate,
+    /// A deflate stream with the zlib header and checksum.
+    Zlib,
+    /// A deflate stream with the gzip header and trailer.
+    Gzip,
+}
+
+type Output<'a> = Writer<&'a mut ChunkedBytes>;
+
+#[derive(Debug)]
+enum Encoder<'a> {
+    Deflate(DeflateEncoder<Output<'a>>),
+    Zlib(ZlibEncoder<Output<'a>>),
+    Gzip(GzEncoder<Output<'a>>),
+}
+
+#[derive(Debug)]
+enum Decoder<'a> {
+    Deflate(DeflateDecoder<Output<'a>>),
+    Zlib(ZlibDecoder<Output<'a>>),
+    Gzip(GzDecoder<Output<'a>>),
+}
+
+macro_rules! dispatch {
+    ($inner:expr, $w:ident => $e:expr) => {
+        match $inner {
+            Self::Deflate($w) => $e,
+            Self::Zlib($w) => $e,
+            Self::Gzip($w) => $e,
+        }
+    };
+}
+
+impl<'a> Encoder<'a> {
+    fn writer(&mut self) -> &mut dyn Write {
+        dispatch!(self, w => w)
+    }
+
+    fn finish(self) -> io::Result<()> {
+        dispatch!(self, w => w.finish().map(drop))
+    }
+}
+
+impl<'a> Decoder<'a> {
+    fn writer(&mut self) -> &mut dyn Write {
+        dispatch!(self, w => w)
+    }
+
+    fn finish(self) -> io::Result<()> {
+        dispatch!(self, w => w.finish().map(drop))
+    }
+}
+
+// Writes the data remaining in `src` chunk by chunk.
+fn write_buf<B: Buf>(w: &mut dyn Write, mut src: B) -> io::Result<()> {
+    while src.has_remaining() {
+        let chunk = src.chunk();
+        let len = chunk.len();
+        w.write_all(chunk)?;
+        src.advance(len);
+    }
+    Ok(())
+}
+
+/// A writer compressing the data written through it into `ChunkedBytes`.
+///
+/// The compressed output is appended to the buffer as it is produced
+/// by the encoder. The end of the compressed stream is written by `finish`,
+/// which must be called to complete the output.
+#[derive(Debug)]
+pub struct CompressWriter<'a> {
+    inner: Encoder<'a>,
+}
+
+impl<'a> CompressWriter<'a> {
+    /// Creates a writer compressing into `dst` in the given format
+    /// with the given compression level.
+    pub fn new(
+        dst: &'a mut ChunkedBytes,
+        format: Format,
+        level: Compression,
+    ) -> Self {
+        let out = dst.writer();
+        let inner = match format {
+            Format::Deflate => {
+                Encoder::Deflate(DeflateEncoder::new(out, level))
+            }
+            Format::Zlib => Encoder::Zlib(ZlibEncoder::new(out, level)),
+            Format::Gzip => Encoder::Gzip(GzEncoder::new(out, level)),
+        };
+        CompressWriter { inner }
+    }
+
+    /// Consumes the data remaining in `src`, feeding it to the encoder
+    /// chunk by chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed output does not fit into
+    /// the maximum length of the buffer.
+    pub fn write_buf<B: Buf>(&mut self, src: B) -> io::Result<()> {
+        write_buf(self.inner.writer(), src)
+    }
+
+    /// Completes the compressed stream, appending the remaining output
+    /// to the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed output does not fit into
+    /// the maximum length of the buffer.
+    pub fn finish(self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+impl<'a> Write for CompressWriter<'a> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.writer().write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.writer().flush()
+    }
+}
+
+/// A writer decompressing the data written through it into `ChunkedBytes`.
+///
+/// The decompressed output is appended to the buffer as it is produced
+/// by the decoder. `finish` should be called after the whole of
+/// the compressed stream has been written to flush the remaining output;
+/// for the gzip format, it also verifies the checksum and the length
+/// in the trailer.
+#[derive(Debug)]
+pub struct DecompressWriter<'a> {
+    inner: Decoder<'a>,
+}
+
+impl<'a> DecompressWriter<'a> {
+    /// Creates a writer decompressing the data in the given format
+    /// into `dst`.
+    pub fn new(dst: &'a mut ChunkedBytes, format: Format) -> Self {
+        let out = dst.writer();
+        let inner = match format {
+            Format::Deflate => Decoder::Deflate(DeflateDecoder::new(out)),
+            Format::Zlib => Decoder::Zlib(ZlibDecoder::new(out)),
+            Format::Gzip => Decoder::Gzip(GzDecoder::new(out)),
+        };
+        DecompressWriter { inner }
+    }
+
+    /// Consumes the data remaining in `src`, feeding it to the decoder
+    /// chunk by chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput` or `InvalidData` if
+    /// the compressed data is corrupt, or an error if the decompressed
+    /// output does not fit into the maximum length of the buffer.
+    pub fn write_buf<B: Buf>(&mut self, src: B) -> io::Result<()> {
+        write_buf(self.inner.writer(), src)
+    }
+
+    /// Flushes the remaining decompressed output to the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed data is corrupt, including
+    /// a mismatched gzip trailer, or if the decompressed output does not fit
+    /// into the maximum length of the buffer.
+    pub fn finish(self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+impl<'a> Write for DecompressWriter<'a> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.writer().write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.writer().flush()
+    }
+}
+
+/// Consumes the data remaining in `src`, appending it to `dst` compressed
+/// as a complete stream in the given format.
+///
+/// # Errors
+///
+/// Returns an error if the compressed output does not fit into
+/// the maximum length of `dst`.
+pub fn compress_into<B: Buf>(
+    src: B,
+    dst: &mut ChunkedBytes,
+    format: Format,
+    level: Compression,
+) -> io::Result<()> {
+    let mut w = CompressWriter::new(dst, format, level);
+    w.write_buf(src)?;
+    w.finish()
+}
+
+/// Consumes the data remaining in `src` as a compressed stream in the given
+/// format, appending the decompressed data to `dst`.
+///
+/// # Errors
+///
+/// Returns an error if the compressed data is corrupt, or if
+/// the decompressed output does not fit into the maximum length of `dst`.
+pub fn decompress_into<B: Buf>(
+    src: B,
+    dst: &mut ChunkedBytes,
+    format: Format,
+) -> io::Result<()> {
+    let mut w = DecompressWriter::new(dst, format);
+    w.write_buf(src)?;
+    w.finish()
+}
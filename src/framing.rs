@@ -0,0 +1,253 @@
+//! Length-delimited framing of data buffered in `ChunkedBytes`.
+//!
+//! `LengthDelimited` writes frames consisting of a big-endian length prefix
+//! of a configurable width followed by the payload, and reads such frames
+//! back from the buffered data. Payloads appended as `Bytes` are not copied
+//! when written, and the frames read are split off the buffer without
+//! copying, as `ChunkedBytes` or, when contained in a single chunk,
+//! as `Bytes`.
+//!
+//! With the `codec` feature, `LengthDelimited` also implements the `Encoder`
+//! and `Decoder` traits of the `codec` module.
+
+use crate::ChunkedBytes;
+
+use bytes::{Buf, BufMut, Bytes};
+
+use std::io::{self, IoSlice};
+
+const DEFAULT_PREFIX_WIDTH: usize = 4;
+const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// Writes and reads frames prefixed with the length of the payload.
+///
+/// The length prefix is an unsigned big-endian integer of 1 to 8 bytes,
+/// 4 bytes by default. The length of the payload is limited by the width
+/// of the prefix and by the maximum frame length, which is 8 MiB
+/// by default; frames exceeding either limit are rejected with an error
+/// when written or read.
+#[derive(Clone, Debug)]
+pub struct LengthDelimited {
+    prefix_width: usize,
+    max_frame_len: usize,
+}
+
+impl Default for LengthDelimited {
+    #[inline]
+    fn default() -> Self {
+        LengthDelimited {
+            prefix_width: DEFAULT_PREFIX_WIDTH,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+}
+
+impl LengthDelimited {
+    /// Creates a framer with a 4-byte length prefix and the default maximum
+    /// frame length.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a framer with a length prefix of `width` bytes
+    /// and the default maximum frame length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0 or greater than 8.
+    pub fn with_prefix_width(width: usize) -> Self {
+        assert!(
+            (1..=8).contains(&width),
+            "unsupported length prefix width {}",
+            width
+        );
+        LengthDelimited {
+            prefix_width: width,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the width of the length prefix in bytes.
+    #[inline]
+    pub fn prefix_width(&self) -> usize {
+        self.prefix_width
+    }
+
+    /// Returns the maximum length of a frame payload.
+    #[inline]
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Sets the maximum length of a frame payload.
+    #[inline]
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.max_frame_len = len;
+    }
+
+    // The largest payload length that the prefix can represent,
+    // within the maximum frame length.
+    fn len_limit(&self) -> u64 {
+        let prefix_max = u64::MAX >> (64 - 8 * self.prefix_width);
+        prefix_max.min(self.max_frame_len as u64)
+    }
+
+    fn put_prefix(&self, dst: &mut ChunkedBytes, len: usize) -> io::Result<()> {
+        if len as u64 > self.len_limit() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame of {} bytes is too long", len),
+            ));
+        }
+        dst.put_uint(len as u64, self.prefix_width);
+        Ok(())
+    }
+
+    /// Writes a frame with the given payload into `dst`.
+    ///
+    /// The payload is appended with `put_bytes`, so it is not copied
+    /// unless it is small enough to be cheaper to copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput`, writing nothing,
+    /// if the payload is too long to be framed.
+    pub fn write_frame(
+        &self,
+        dst: &mut ChunkedBytes,
+        payload: Bytes,
+    ) -> io::Result<()> {
+        self.put_prefix(dst, payload.len())?;
+        dst.put_bytes(payload);
+        Ok(())
+    }
+
+    /// Writes a frame with the payload produced by the closure `f`
+    /// into `dst`.
+    ///
+    /// The closure writes the payload into a separate buffer with the same
+    /// preferred chunk size as `dst`, so that its length is known before
+    /// the length prefix is written. The chunks of the payload are then
+    /// appended to `dst` after the prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput`, writing nothing,
+    /// if the payload is too long to be framed.
+    pub fn write_frame_with<F>(
+        &self,
+        dst: &mut ChunkedBytes,
+        f: F,
+    ) -> io::Result<()>
+    where
+        F: FnOnce(&mut ChunkedBytes),
+    {
+        let mut payload =
+            ChunkedBytes::with_chunk_size_hint(dst.chunk_size_hint());
+        f(&mut payload);
+        self.put_prefix(dst, payload.remaining())?;
+        for chunk in payload.into_chunks() {
+            dst.put_bytes(chunk);
+        }
+        Ok(())
+    }
+
+    // Returns the length of the payload of the first buffered frame,
+    // if the prefix has been buffered in full.
+    fn peek_len(&self, src: &ChunkedBytes) -> io::Result<Option<usize>> {
+        let width = self.prefix_width;
+        if src.remaining() < width {
+            return Ok(None);
+        }
+        // The prefix spans at most as many chunks as it has bytes
+        let mut slices = [IoSlice::new(&[]); 8];
+        let (n, _) = src.chunks_vectored_limited(&mut slices, width);
+        let mut prefix = [0; 8];
+        let mut pos = 8 - width;
+        for slice in &slices[..n] {
+            prefix[pos..pos + slice.len()].copy_from_slice(slice);
+            pos += slice.len();
+        }
+        let len = u64::from_be_bytes(prefix);
+        if len > self.len_limit() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes is too long", len),
+            ));
+        }
+        Ok(Some(len as usize))
+    }
+
+    // Consumes the prefix of the first buffered frame and returns the length
+    // of its payload, if the frame has been buffered in full.
+    fn take_prefix(&self, src: &mut ChunkedBytes) -> io::Result<Option<usize>> {
+        match self.peek_len(src)? {
+            Some(len) if src.remaining() - self.prefix_width >= len => {
+                src.advance(self.prefix_width);
+                Ok(Some(len))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads a frame from the data buffered in `src`, returning its payload
+    /// split off the buffer without copying.
+    ///
+    /// Returns `Ok(None)`, consuming nothing, if the frame has not yet been
+    /// buffered in full.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the length prefix exceeds
+    /// the maximum frame length.
+    pub fn read_frame(
+        &self,
+        src: &mut ChunkedBytes,
+    ) -> io::Result<Option<ChunkedBytes>> {
+        let frame = self.take_prefix(src)?.map(|len| src.split_to(len));
+        Ok(frame)
+    }
+
+    /// Reads a frame from the data buffered in `src`, returning its payload
+    /// as `Bytes`.
+    ///
+    /// The payload is not copied if it is contained in a single chunk.
+    /// Returns `Ok(None)`, consuming nothing, if the frame has not yet been
+    /// buffered in full.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the length prefix exceeds
+    /// the maximum frame length.
+    pub fn read_frame_bytes(
+        &self,
+        src: &mut ChunkedBytes,
+    ) -> io::Result<Option<Bytes>> {
+        let frame = self.take_prefix(src)?.map(|len| src.copy_to_bytes(len));
+        Ok(frame)
+    }
+}
+
+#[cfg(feature = "codec")]
+impl crate::codec::Encoder<Bytes> for LengthDelimited {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        item: Bytes,
+        dst: &mut ChunkedBytes,
+    ) -> io::Result<()> {
+        self.write_frame(dst, item)
+    }
+}
+
+#[cfg(feature = "codec")]
+impl crate::codec::Decoder for LengthDelimited {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut ChunkedBytes) -> io::Result<Option<Bytes>> {
+        self.read_frame_bytes(src)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{loosely, strictly};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use bytes::{Buf, BufMut, Bytes};
+
+use std::cmp::min;
+
+const MAX_CHUNK_SIZE: usize = 64;
+
+// Builds an arbitrary container by interpreting the input as a sequence
+// of operations changing the layout as the buffer is filled.
+macro_rules! impl_arbitrary {
+    ($t:ty) => {
+        impl<'a> Arbitrary<'a> for $t {
+            fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                let chunk_size = u.int_in_range(1..=MAX_CHUNK_SIZE)?;
+                let mut buf = Self::with_profile(chunk_size, 0);
+                while !u.is_empty() {
+                    let op = u8::arbitrary(u)?;
+                    let len = u.int_in_range(1..=MAX_CHUNK_SIZE)?;
+                    match op % 4 {
+                        0 => {
+                            let len = min(len, u.len());
+                            buf.put_slice(u.bytes(len)?);
+                        }
+                        1 => {
+                            let len = min(len, u.len());
+                            buf.put_bytes(Bytes::copy_from_slice(
+                                u.bytes(len)?,
+                            ));
+                        }
+                        2 => buf.flush(),
+                        _ => {
+                            let len = min(len, buf.remaining());
+                            buf.advance(len);
+                        }
+                    }
+                }
+                Ok(buf)
+            }
+        }
+    };
+}
+
+impl_arbitrary!(loosely::ChunkedBytes);
+impl_arbitrary!(strictly::ChunkedBytes);
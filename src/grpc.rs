@@ -0,0 +1,192 @@
+//! gRPC message framing of data buffered in `ChunkedBytes`.
+//!
+//! Messages in gRPC over HTTP/2 are prefixed with a 1-byte compressed flag
+//! and the 4-byte big-endian length of the message. `MessageFraming` writes
+//! message bodies with the prefix into `ChunkedBytes`, and reads complete
+//! messages from the buffered data, splitting them off the buffer without
+//! copying. This is useful for custom transports interoperating with gRPC
+//! implementations such as `tonic`.
+//!
+//! With the `codec` feature, `MessageFraming` also implements the `Encoder`
+//! and `Decoder` traits of the `codec` module.
+
+use crate::ChunkedBytes;
+
+use bytes::{Buf, BufMut};
+
+use std::io::{self, IoSlice};
+
+const PREFIX_LEN: usize = 5;
+const DEFAULT_MAX_MESSAGE_LEN: usize = 4 * 1024 * 1024;
+
+/// A gRPC message read from the buffered data.
+#[derive(Debug)]
+pub struct Message {
+    /// True if the message is compressed with the message encoding
+    /// of the call.
+    pub compressed: bool,
+    /// The body of the message.
+    pub data: ChunkedBytes,
+}
+
+/// Writes and reads gRPC messages prefixed with the compressed flag
+/// and the message length.
+///
+/// The length of the messages read is limited by the maximum message
+/// length, which is 4 MiB by default, matching the default limit of
+/// received messages in gRPC implementations.
+#[derive(Clone, Debug)]
+pub struct MessageFraming {
+    max_message_len: usize,
+}
+
+impl Default for MessageFraming {
+    #[inline]
+    fn default() -> Self {
+        MessageFraming {
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+        }
+    }
+}
+
+impl MessageFraming {
+    /// Creates a framer with the default maximum message length.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the maximum length of a message to read.
+    #[inline]
+    pub fn max_message_len(&self) -> usize {
+        self.max_message_len
+    }
+
+    /// Sets the maximum length of a message to read.
+    #[inline]
+    pub fn set_max_message_len(&mut self, len: usize) {
+        self.max_message_len = len;
+    }
+
+    /// Consumes the data remaining in `body`, writing it into `dst` as
+    /// a message with the given compressed flag.
+    ///
+    /// The body is appended chunk by chunk with `copy_to_bytes` and
+    /// `put_bytes`, so the chunks of `Bytes` or `ChunkedBytes` bodies are
+    /// not copied unless they are small enough to be cheaper to copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidInput`, writing nothing,
+    /// if the body is longer than a 32-bit length can represent.
+    pub fn write_message<B: Buf>(
+        &self,
+        dst: &mut ChunkedBytes,
+        compressed: bool,
+        mut body: B,
+    ) -> io::Result<()> {
+        let len = body.remaining();
+        if len > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("message of {} bytes is too long", len),
+            ));
+        }
+        dst.put_u8(compressed as u8);
+        dst.put_u32(len as u32);
+        while body.has_remaining() {
+            let n = body.chunk().len();
+            dst.put_bytes(body.copy_to_bytes(n));
+        }
+        Ok(())
+    }
+
+    // Returns the compressed flag and the length of the first buffered
+    // message, if the prefix has been buffered in full.
+    fn peek_prefix(
+        &self,
+        src: &ChunkedBytes,
+    ) -> io::Result<Option<(bool, usize)>> {
+        if src.remaining() < PREFIX_LEN {
+            return Ok(None);
+        }
+        let mut slices = [IoSlice::new(&[]); PREFIX_LEN];
+        let (n, _) = src.chunks_vectored_limited(&mut slices, PREFIX_LEN);
+        let mut prefix = [0; PREFIX_LEN];
+        let mut pos = 0;
+        for slice in &slices[..n] {
+            prefix[pos..pos + slice.len()].copy_from_slice(slice);
+            pos += slice.len();
+        }
+        let compressed = match prefix[0] {
+            0 => false,
+            1 => true,
+            flag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid message flag {:#04x}", flag),
+                ))
+            }
+        };
+        let len =
+            u32::from_be_bytes([prefix[1], prefix[2], prefix[3], prefix[4]]);
+        if len as usize > self.max_message_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message of {} bytes is too long", len),
+            ));
+        }
+        Ok(Some((compressed, len as usize)))
+    }
+
+    /// Reads a message from the data buffered in `src`, splitting off
+    /// its body without copying.
+    ///
+    /// Returns `Ok(None)`, consuming nothing, if the message has not yet
+    /// been buffered in full.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `InvalidData` if the compressed flag
+    /// is neither 0 nor 1, or if the length exceeds the maximum message
+    /// length.
+    pub fn read_message(
+        &self,
+        src: &mut ChunkedBytes,
+    ) -> io::Result<Option<Message>> {
+        match self.peek_prefix(src)? {
+            Some((compressed, len)) if src.remaining() - PREFIX_LEN >= len => {
+                src.advance(PREFIX_LEN);
+                let data = src.split_to(len);
+                Ok(Some(Message { compressed, data }))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "codec")]
+impl crate::codec::Encoder<Message> for MessageFraming {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        item: Message,
+        dst: &mut ChunkedBytes,
+    ) -> io::Result<()> {
+        self.write_message(dst, item.compressed, item.data)
+    }
+}
+
+#[cfg(feature = "codec")]
+impl crate::codec::Decoder for MessageFraming {
+    type Item = Message;
+    type Error = io::Error;
+
+    fn decode(
+        &mut self,
+        src: &mut ChunkedBytes,
+    ) -> io::Result<Option<Message>> {
+        self.read_message(src)
+    }
+}
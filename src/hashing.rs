@@ -0,0 +1,80 @@
+use bytes::BufMut;
+use digest::Update;
+
+use std::cmp::min;
+use std::fmt;
+use std::io;
+
+/// A writer adapter updating a hash with the data written through it.
+///
+/// This value is produced by the `hashing_writer` method of `ChunkedBytes`.
+/// The data written with `fmt::Write` or `io::Write` are appended to
+/// the buffer and fed to the hasher as they are appended, so the hash of
+/// the content is computed while it is being serialized, without making
+/// another pass over the buffered chunks. The hasher can be any type
+/// implementing `digest::Update`; it is returned by `into_hasher`.
+///
+/// When the data do not fit into the maximum length of the buffer,
+/// `io::Write::write` appends as much of it as it can and returns
+/// the number of bytes consumed; only the consumed bytes are hashed.
+/// `fmt::Write::write_str` returns an error in this case, with a part of
+/// the string possibly appended and hashed.
+///
+/// This type is only available with the `digest` feature.
+#[derive(Debug)]
+pub struct HashingWriter<'a, B: BufMut, D> {
+    buf: &'a mut B,
+    hasher: D,
+}
+
+impl<'a, B: BufMut, D: Update> HashingWriter<'a, B, D> {
+    #[inline]
+    pub(crate) fn new(buf: &'a mut B, hasher: D) -> Self {
+        HashingWriter { buf, hasher }
+    }
+
+    /// Returns a reference to the hasher.
+    #[inline]
+    pub fn hasher(&self) -> &D {
+        &self.hasher
+    }
+
+    /// Consumes the adapter, returning the hasher updated with all
+    /// the data appended through it.
+    #[inline]
+    pub fn into_hasher(self) -> D {
+        self.hasher
+    }
+
+    // Appends and hashes as much of `src` as fits, returning the number
+    // of bytes consumed.
+    fn append(&mut self, src: &[u8]) -> usize {
+        let n = min(src.len(), self.buf.remaining_mut());
+        self.buf.put_slice(&src[..n]);
+        self.hasher.update(&src[..n]);
+        n
+    }
+}
+
+impl<'a, B: BufMut, D: Update> fmt::Write for HashingWriter<'a, B, D> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.append(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+impl<'a, B: BufMut, D: Update> io::Write for HashingWriter<'a, B, D> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.append(buf))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use crate::chunked::Inner;
+use crate::{ChunkStorage, SharedStorage};
+
+use std::fmt::{self, Display, Write as _};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// A display adapter rendering the contents of `ChunkedBytes` as
+/// a hexadecimal dump.
+///
+/// This value is produced by the `hex_dump` method of `ChunkedBytes`.
+/// The output is formatted in lines of 16 bytes, each line starting with
+/// the offset of its first byte, followed by the hexadecimal byte values
+/// and their ASCII representation, where non-printable bytes are
+/// substituted with dots.
+///
+/// The buffered data are not copied; the chunks are walked as the output
+/// is formatted.
+pub struct HexDump<'a, S = SharedStorage> {
+    inner: &'a Inner<S>,
+}
+
+impl<'a, S> HexDump<'a, S> {
+    #[inline]
+    pub(crate) fn new(inner: &'a Inner<S>) -> Self {
+        HexDump { inner }
+    }
+}
+
+impl<'a, S> Clone for HexDump<'a, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, S> Copy for HexDump<'a, S> {}
+
+impl<'a, S: ChunkStorage> fmt::Debug for HexDump<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<'a, S: ChunkStorage> Display for HexDump<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.inner.slices().flatten().copied();
+        let mut line = [0u8; BYTES_PER_LINE];
+        let mut offset = 0;
+        loop {
+            let mut len = 0;
+            for (dst, b) in line.iter_mut().zip(&mut bytes) {
+                *dst = b;
+                len += 1;
+            }
+            if len == 0 {
+                return Ok(());
+            }
+            write!(f, "{:08x} ", offset)?;
+            for (i, b) in line.iter().enumerate() {
+                if i == BYTES_PER_LINE / 2 {
+                    f.write_char(' ')?;
+                }
+                if i < len {
+                    write!(f, " {:02x}", b)?;
+                } else {
+                    f.write_str("   ")?;
+                }
+            }
+            f.write_str("  |")?;
+            for &b in &line[..len] {
+                let c = if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                };
+                f.write_char(c)?;
+            }
+            f.write_str("|\n")?;
+            offset += len;
+        }
+    }
+}
+
+pub(crate) fn fmt_lower<S: ChunkStorage>(
+    inner: &Inner<S>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    if f.alternate() {
+        f.write_str("0x")?;
+    }
+    for b in inner.slices().flatten() {
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn fmt_upper<S: ChunkStorage>(
+    inner: &Inner<S>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    if f.alternate() {
+        f.write_str("0x")?;
+    }
+    for b in inner.slices().flatten() {
+        write!(f, "{:02X}", b)?;
+    }
+    Ok(())
+}
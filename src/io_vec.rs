@@ -0,0 +1,125 @@
+use crate::{loosely, strictly};
+
+use bytes::Bytes;
+
+use std::fmt;
+use std::io::IoSlice;
+use std::slice;
+
+/// An owned gather list over the chunks of a consumed `ChunkedBytes`
+/// container.
+///
+/// `OwnedIoVec` keeps the chunks alive together with an array of `iovec`
+/// entries referring to their data. The addresses of the data and
+/// the array do not change when the value is moved, which makes it suitable
+/// for submission to completion-based I/O runtimes that take ownership of
+/// the buffers for the duration of an operation.
+///
+/// With the `monoio` feature, `OwnedIoVec` implements
+/// `monoio::buf::IoVecBuf`.
+///
+/// This type is only available on Unix platforms.
+pub struct OwnedIoVec {
+    chunks: Vec<Bytes>,
+    iovecs: Vec<libc::iovec>,
+    len: usize,
+}
+
+// Safety: the `iovec` entries only refer to the data of the chunks
+// owned by this value, which can be shared between threads.
+unsafe impl Send for OwnedIoVec {}
+unsafe impl Sync for OwnedIoVec {}
+
+impl OwnedIoVec {
+    fn new(chunks: impl Iterator<Item = Bytes>) -> Self {
+        let chunks =
+            chunks.filter(|chunk| !chunk.is_empty()).collect::<Vec<_>>();
+        let iovecs = chunks
+            .iter()
+            .map(|chunk| libc::iovec {
+                iov_base: chunk.as_ptr() as *mut _,
+                iov_len: chunk.len(),
+            })
+            .collect();
+        let len = chunks.iter().map(|chunk| chunk.len()).sum();
+        OwnedIoVec {
+            chunks,
+            iovecs,
+            len,
+        }
+    }
+
+    /// Returns the total length of the data.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there is no data.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the gather list as a slice of `IoSlice` entries.
+    #[inline]
+    pub fn as_io_slices(&self) -> &[IoSlice<'_>] {
+        // Safety: `IoSlice` is ABI compatible with `iovec` on Unix,
+        // and the entries refer to the chunks borrowed along with `self`.
+        unsafe {
+            slice::from_raw_parts(
+                self.iovecs.as_ptr() as *const IoSlice<'_>,
+                self.iovecs.len(),
+            )
+        }
+    }
+
+    /// Returns the gather list as a slice of `iovec` entries,
+    /// to be passed to system calls such as `writev`.
+    #[inline]
+    pub fn as_iovecs(&self) -> &[libc::iovec] {
+        &self.iovecs
+    }
+
+    /// Consumes the gather list, returning the chunks.
+    #[inline]
+    pub fn into_chunks(self) -> Vec<Bytes> {
+        self.chunks
+    }
+}
+
+impl From<loosely::ChunkedBytes> for OwnedIoVec {
+    fn from(buf: loosely::ChunkedBytes) -> Self {
+        OwnedIoVec::new(buf.into_chunks())
+    }
+}
+
+impl From<strictly::ChunkedBytes> for OwnedIoVec {
+    fn from(buf: strictly::ChunkedBytes) -> Self {
+        OwnedIoVec::new(buf.into_chunks())
+    }
+}
+
+impl fmt::Debug for OwnedIoVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedIoVec")
+            .field("chunks", &self.chunks.len())
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+// Safety: the `iovec` array and the data it refers to are owned
+// by the value and are not changed while it exists.
+#[cfg(feature = "monoio")]
+unsafe impl monoio::buf::IoVecBuf for OwnedIoVec {
+    #[inline]
+    fn read_iovec_ptr(&self) -> *const libc::iovec {
+        self.iovecs.as_ptr()
+    }
+
+    #[inline]
+    fn read_iovec_len(&self) -> usize {
+        self.iovecs.len()
+    }
+}
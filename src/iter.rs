@@ -1,21 +1,66 @@
-use bytes::Bytes;
+use crate::queue;
+use crate::{ChunkStorage, SharedStorage};
 
-use std::collections::vec_deque;
+use bytes::{Buf, Bytes};
+
+use std::cmp::min;
 use std::iter::FusedIterator;
+use std::mem;
+
+// Yields the chunks produced by `inner`, splitting them into pieces
+// of at most `limit` bytes.
+struct Capped<I> {
+    inner: I,
+    pending: Bytes,
+    limit: usize,
+    // The number of pieces left to yield
+    count: usize,
+}
+
+impl<I: Iterator<Item = Bytes>> Capped<I> {
+    #[inline]
+    fn new(inner: I, limit: usize, count: usize) -> Self {
+        Capped {
+            inner,
+            pending: Bytes::new(),
+            limit,
+            count,
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<Bytes> {
+        if self.pending.is_empty() {
+            self.pending = self.inner.next()?;
+        }
+        self.count -= 1;
+        if self.pending.len() > self.limit {
+            Some(self.pending.split_to(self.limit))
+        } else {
+            Some(mem::take(&mut self.pending))
+        }
+    }
+}
 
 /// The iterator produced by the `drain_chunks` method of `ChunkedBytes`.
-pub struct DrainChunks<'a> {
-    inner: vec_deque::Drain<'a, Bytes>,
+pub struct DrainChunks<'a, S = SharedStorage> {
+    inner: Capped<queue::Drain<'a, S>>,
 }
 
-impl<'a> DrainChunks<'a> {
+impl<'a, S: ChunkStorage> DrainChunks<'a, S> {
     #[inline]
-    pub(crate) fn new(inner: vec_deque::Drain<'a, Bytes>) -> Self {
-        DrainChunks { inner }
+    pub(crate) fn new(
+        inner: queue::Drain<'a, S>,
+        limit: usize,
+        count: usize,
+    ) -> Self {
+        DrainChunks {
+            inner: Capped::new(inner, limit, count),
+        }
     }
 }
 
-impl<'a> Iterator for DrainChunks<'a> {
+impl<'a, S: ChunkStorage> Iterator for DrainChunks<'a, S> {
     type Item = Bytes;
 
     #[inline]
@@ -25,26 +70,32 @@ impl<'a> Iterator for DrainChunks<'a> {
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.inner.size_hint()
+        (self.inner.count, Some(self.inner.count))
     }
 }
 
-impl<'a> ExactSizeIterator for DrainChunks<'a> {}
-impl<'a> FusedIterator for DrainChunks<'a> {}
+impl<'a, S: ChunkStorage> ExactSizeIterator for DrainChunks<'a, S> {}
+impl<'a, S: ChunkStorage> FusedIterator for DrainChunks<'a, S> {}
 
 /// The iterator produced by the `into_chunks` method of `ChunkedBytes`.
-pub struct IntoChunks {
-    inner: vec_deque::IntoIter<Bytes>,
+pub struct IntoChunks<S = SharedStorage> {
+    inner: Capped<queue::IntoIter<S>>,
 }
 
-impl IntoChunks {
+impl<S: ChunkStorage> IntoChunks<S> {
     #[inline]
-    pub(crate) fn new(inner: vec_deque::IntoIter<Bytes>) -> Self {
-        IntoChunks { inner }
+    pub(crate) fn new(
+        inner: queue::IntoIter<S>,
+        limit: usize,
+        count: usize,
+    ) -> Self {
+        IntoChunks {
+            inner: Capped::new(inner, limit, count),
+        }
     }
 }
 
-impl Iterator for IntoChunks {
+impl<S: ChunkStorage> Iterator for IntoChunks<S> {
     type Item = Bytes;
 
     #[inline]
@@ -54,9 +105,49 @@ impl Iterator for IntoChunks {
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.inner.size_hint()
+        (self.inner.count, Some(self.inner.count))
+    }
+}
+
+impl<S: ChunkStorage> ExactSizeIterator for IntoChunks<S> {}
+impl<S: ChunkStorage> FusedIterator for IntoChunks<S> {}
+
+/// The iterator produced by the `packets` method of `ChunkedBytes`.
+///
+/// Each item removes up to the maximum packet size from the front of
+/// the buffer. A piece of data contained in a single chunk is split off
+/// without copying; smaller chunks are coalesced into one packet.
+pub struct Packets<'a, B: ?Sized> {
+    buf: &'a mut B,
+    mtu: usize,
+}
+
+impl<'a, B: ?Sized> Packets<'a, B> {
+    #[inline]
+    pub(crate) fn new(buf: &'a mut B, mtu: usize) -> Self {
+        assert!(mtu != 0, "the packet size must not be 0");
+        Packets { buf, mtu }
+    }
+}
+
+impl<'a, B: Buf + ?Sized> Iterator for Packets<'a, B> {
+    type Item = Bytes;
+
+    #[inline]
+    fn next(&mut self) -> Option<Bytes> {
+        let remaining = self.buf.remaining();
+        if remaining == 0 {
+            return None;
+        }
+        Some(self.buf.copy_to_bytes(min(remaining, self.mtu)))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.buf.remaining().div_ceil(self.mtu);
+        (count, Some(count))
     }
 }
 
-impl ExactSizeIterator for IntoChunks {}
-impl FusedIterator for IntoChunks {}
+impl<'a, B: Buf + ?Sized> ExactSizeIterator for Packets<'a, B> {}
+impl<'a, B: Buf + ?Sized> FusedIterator for Packets<'a, B> {}
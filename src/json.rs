@@ -0,0 +1,137 @@
+use bytes::BufMut;
+
+use std::cmp::min;
+use std::fmt;
+use std::io;
+
+/// A writer adapter escaping the text written through it as the contents
+/// of a JSON string.
+///
+/// This value is produced by the `json_escape` and `json_string` methods
+/// of `ChunkedBytes`. The text written with `fmt::Write` or `io::Write` is
+/// appended to the buffer with the quotation marks, the backslashes, and
+/// the control characters escaped; the other characters are appended
+/// as they are, so the bytes written with `io::Write` should be UTF-8.
+/// The runs of characters that need no escaping are appended without
+/// intermediate copies.
+///
+/// If the adapter is produced by `json_string`, the opening quotation mark
+/// is appended when the adapter is created, and the closing quotation mark
+/// is appended when it is dropped, if it fits into the maximum length
+/// of the buffer.
+///
+/// When the escaped text does not fit into the maximum length of the buffer,
+/// `io::Write::write` appends as much of it as it can, not splitting
+/// the escape sequences, and returns the number of input bytes consumed.
+/// `fmt::Write::write_str` returns an error in this case, with a part of
+/// the string possibly appended.
+#[derive(Debug)]
+pub struct JsonEscape<'a, B: BufMut> {
+    buf: &'a mut B,
+    quoted: bool,
+}
+
+impl<'a, B: BufMut> JsonEscape<'a, B> {
+    #[inline]
+    pub(crate) fn new(buf: &'a mut B) -> Self {
+        JsonEscape { buf, quoted: false }
+    }
+
+    #[inline]
+    pub(crate) fn quoted(buf: &'a mut B) -> Self {
+        buf.put_u8(b'"');
+        JsonEscape { buf, quoted: true }
+    }
+
+    // Appends the escaped `src`, returning the number of bytes consumed.
+    fn escape(&mut self, src: &[u8]) -> usize {
+        let mut consumed = 0;
+        while consumed < src.len() {
+            let rest = &src[consumed..];
+            let run = rest
+                .iter()
+                .position(|&b| needs_escape(b))
+                .unwrap_or(rest.len());
+            if run != 0 {
+                let n = min(run, self.buf.remaining_mut());
+                self.buf.put_slice(&rest[..n]);
+                consumed += n;
+                if n < run {
+                    break;
+                }
+                continue;
+            }
+            let mut seq = [0; 6];
+            let seq = escape_sequence(rest[0], &mut seq);
+            if self.buf.remaining_mut() < seq.len() {
+                break;
+            }
+            self.buf.put_slice(seq);
+            consumed += 1;
+        }
+        consumed
+    }
+}
+
+impl<'a, B: BufMut> Drop for JsonEscape<'a, B> {
+    fn drop(&mut self) {
+        if self.quoted && self.buf.has_remaining_mut() {
+            self.buf.put_u8(b'"');
+        }
+    }
+}
+
+#[inline]
+fn needs_escape(b: u8) -> bool {
+    b < 0x20 || b == b'"' || b == b'\\'
+}
+
+fn escape_sequence(b: u8, seq: &mut [u8; 6]) -> &[u8] {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let c = match b {
+        b'"' => b'"',
+        b'\\' => b'\\',
+        b'\n' => b'n',
+        b'\r' => b'r',
+        b'\t' => b't',
+        0x08 => b'b',
+        0x0c => b'f',
+        _ => {
+            *seq = [
+                b'\\',
+                b'u',
+                b'0',
+                b'0',
+                HEX_DIGITS[(b >> 4) as usize],
+                HEX_DIGITS[(b & 0xf) as usize],
+            ];
+            return seq;
+        }
+    };
+    seq[0] = b'\\';
+    seq[1] = c;
+    &seq[..2]
+}
+
+impl<'a, B: BufMut> fmt::Write for JsonEscape<'a, B> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.escape(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+impl<'a, B: BufMut> io::Write for JsonEscape<'a, B> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.escape(buf))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+/// A snapshot of the internal layout of a `ChunkedBytes` container.
+///
+/// This value is produced by the `layout` method of `ChunkedBytes`.
+/// It can be used to monitor fragmentation of the buffered data or to check
+/// the chunk sizes in tests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Layout {
+    /// Lengths of the complete chunks, in order of consumption.
+    pub chunks: Vec<usize>,
+    /// Number of bytes in the staging buffer.
+    pub staging_len: usize,
+    /// Capacity of the staging buffer, including `staging_len`.
+    pub staging_capacity: usize,
+}
+
+impl Layout {
+    /// Returns the total number of bytes in the buffer.
+    pub fn total_len(&self) -> usize {
+        self.chunks.iter().sum::<usize>() + self.staging_len
+    }
+}
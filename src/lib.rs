@@ -14,28 +14,17 @@
 //! `ChunkedBytes` implements the traits `Buf` and `BufMut` for read and write
 //! access to the buffered data. It also provides the `put_bytes` method
 //! for appending a `Bytes` slice to its queue of non-contiguous chunks without
-//! copying the data.
+//! copying the data, unless the slice is small enough to be cheaper to copy.
 //!
 //! # Examples
 //!
 //! ```
-//! use bytes::{Buf, BufMut, Bytes};
+//! use bytes::{BufMut, Bytes};
 //! use chunked_bytes::ChunkedBytes;
-//! use std::io::{self, IoSlice, Read, Write};
+//! use std::io::{self, Read};
 //! use std::net::{SocketAddr, TcpListener, TcpStream, Shutdown};
 //! use std::thread;
 //!
-//! fn write_vectored<W: Write>(
-//!     buf: &mut ChunkedBytes,
-//!     mut out: W,
-//! ) -> io::Result<usize> {
-//!     let mut io_bufs = [IoSlice::new(&[]); 32];
-//!     let io_vec_len = buf.chunks_vectored(&mut io_bufs);
-//!     let bytes_written = out.write_vectored(&io_bufs[..io_vec_len])?;
-//!     buf.advance(bytes_written);
-//!     Ok(bytes_written)
-//! }
-//!
 //! fn main() -> io::Result<()> {
 //!     const MESSAGE: &[u8] = b"I \xf0\x9f\x96\xa4 \x00\xc0\xff\xee";
 //!
@@ -60,7 +49,8 @@
 //!     buf.put_bytes(Bytes::from("🖤 "));
 //!     buf.put_u32(0xc0ffee);
 //!
-//!     let bytes_written = write_vectored(&mut buf, &mut sender)?;
+//!     // Write the chunks with a single vectored write
+//!     let bytes_written = buf.write_to(&mut sender)?;
 //!     assert_eq!(bytes_written, MESSAGE.len());
 //!
 //!     sender.shutdown(Shutdown::Write)?;
@@ -68,6 +58,81 @@
 //!     server_handle.join().expect("server thread panicked")?;
 //!     Ok(())
 //! }
+//! ```
+//!
+//! # Optional features
+//!
+//! - `actix-http`: implements `actix_http::body::MessageBody` for
+//!   `ChunkedBody`.
+//! - `arbitrary`: implements `arbitrary::Arbitrary` for both variants
+//!   of `ChunkedBytes`, for fuzzing.
+//! - `base64`: provides streaming Base64 encoding and decoding
+//!   in the `base64` module.
+//! - `bincode`: enables `put_bincode` to serialize values with `bincode`
+//!   directly into the buffer. Enables `serde`.
+//! - `cbor`: enables `put_cbor_bytes` and `put_cbor_bytes_chunked`
+//!   to append CBOR byte strings without copying.
+//! - `codec`: provides frame encoding into and decoding from `ChunkedBytes`
+//!   in the `codec` module, and implements its traits for `cobs::Cobs`,
+//!   `framing::LengthDelimited`, and `grpc::MessageFraming`. Enables
+//!   `tokio`, `futures-core`, and `futures-sink`.
+//! - `digest`: enables `hash_into` and `hashing_writer` to compute hashes
+//!   of the buffered data with hashers implementing `digest::Update`.
+//! - `ffi`: provides functions with C linkage to fill and drain a buffer
+//!   in the `ffi` module.
+//! - `flate2`: provides streaming deflate, zlib, and gzip compression
+//!   and decompression with `flate2` in the `flate` module.
+//! - `futures-core`: enables `collect_stream` and `collect_io_stream`
+//!   to collect streams of `Bytes`.
+//! - `futures-io`: provides asynchronous I/O helpers for the `futures-io`
+//!   traits in the `async_futures` module, enables `ChunkedBufReader`,
+//!   and implements the asynchronous I/O traits for `ChunkedBufWriter`.
+//! - `futures-sink`: implements `futures_sink::Sink<Bytes>` for both variants
+//!   of `ChunkedBytes`.
+//! - `http-body`: implements `http_body::Body` for `ChunkedBody`.
+//! - `lz4`: provides LZ4 frame compression and decompression
+//!   with `lz4_flex` in the `lz4` module.
+//! - `monoio`: implements `monoio::buf::IoVecBuf` for `OwnedIoVec`.
+//! - `percent-encoding`: enables `percent_encode` to append data
+//!   percent-encoded against a `percent_encoding::AsciiSet`.
+//! - `postcard`: enables `put_postcard` to serialize values with `postcard`
+//!   directly into the buffer. Enables `serde`.
+//! - `proptest`: provides strategies to generate buffers with `proptest`
+//!   in the `prop` module.
+//! - `prost`: enables `put_message` and `put_message_length_delimited`
+//!   to encode Protobuf messages with `prost`.
+//! - `rayon`: enables `par_chunks` and `par_fold` for processing
+//!   the buffered data in parallel.
+//! - `rmp`: enables `put_msgpack_bin` to append MessagePack binary data
+//!   without copying.
+//! - `ryu`: enables `put_f64_str` and `put_f32_str`.
+//! - `serde`: implements `Serialize` and `Deserialize` for both variants
+//!   of `ChunkedBytes`, as byte arrays.
+//! - `simdutf8`: validates UTF-8 in `is_utf8` with the SIMD routines
+//!   of the `simdutf8` crate.
+//! - `spill`: provides `SpillBuffer`, which spills buffered data to
+//!   a temporary file past an in-memory threshold.
+//! - `subtle`: enables `ct_eq` for constant-time comparison of the buffered
+//!   data.
+//! - `tokio`: enables integration with the Tokio runtime, such as
+//!   `poll_flush_after`, writing to `tokio::io::AsyncWrite`
+//!   with `poll_write_to` and `write_all_to`, and reading from
+//!   `tokio::io::AsyncRead` with `poll_read_from`, enables `ChunkedBufReader`,
+//!   implements the asynchronous I/O traits for `ChunkedBufWriter`,
+//!   and enables `pipe`.
+//! - `tracing`: emits trace-level events with `tracing` when the staging
+//!   buffer is reallocated or flushed to avoid copying, and when
+//!   `strictly::ChunkedBytes` splits an oversized chunk.
+//! - `test-util`: enables `set_chunk_boundaries` and the `test_util` module.
+//! - `wasm`: enables `put_uint8_array` and `to_uint8_arrays` to exchange
+//!   data with JavaScript `Uint8Array` objects.
+//! - `xxhash`: provides streaming xxHash hashers in the `xxhash` module
+//!   and enables `xxh3_64` and `xxh64` to compute fast checksums
+//!   of the buffered data. Enables `digest`.
+//! - `zeroize`: enables `set_zeroize` to zero the memory of consumed
+//!   data owned by the container.
+//! - `zstd`: provides streaming Zstandard compression and decompression
+//!   with `zstd` in the `zstd` module.
 
 #![warn(clippy::all)]
 #![warn(future_incompatible)]
@@ -75,14 +140,130 @@
 #![warn(rust_2018_idioms)]
 #![doc(test(no_crate_inject, attr(deny(warnings, rust_2018_idioms))))]
 
+// Emits a trace event with the `tracing` feature, or does nothing.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+#[cfg(feature = "futures-io")]
+pub mod async_futures;
+#[cfg(feature = "base64")]
+pub mod base64;
+pub mod cobs;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "flate2")]
+pub mod flate;
+pub mod framing;
+pub mod grpc;
 pub mod loosely;
+#[cfg(feature = "lz4")]
+pub mod lz4;
+#[cfg(feature = "proptest")]
+pub mod prop;
 pub mod strictly;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(unix)]
+pub mod unix;
+pub mod websocket;
+#[cfg(windows)]
+pub mod windows;
+#[cfg(feature = "xxhash")]
+pub mod xxhash;
+#[cfg(feature = "zstd")]
+pub mod zstd;
 
+mod alloc;
+#[cfg(feature = "tokio")]
+mod async_tokio;
+#[cfg(any(feature = "http-body", feature = "actix-http"))]
+mod body;
+#[cfg(feature = "test-util")]
+mod boundaries;
+mod budget;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod buf_reader;
+mod buf_writer;
 mod chunked;
+#[cfg(feature = "subtle")]
+mod ct_eq;
+mod cursor;
+mod decimal;
+mod error;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "digest")]
+mod hashing;
+mod hex;
+#[cfg(unix)]
+mod io_vec;
 mod iter;
+mod json;
+mod layout;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "percent-encoding")]
+mod percent;
+#[cfg(feature = "tokio")]
+mod pipe;
+mod pool;
+#[cfg(feature = "postcard")]
+mod postcard_flavor;
+mod queue;
+mod rope;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "futures-sink")]
+mod sink;
+#[cfg(feature = "spill")]
+mod spill;
+mod stats;
+mod storage;
+#[cfg(feature = "futures-core")]
+mod stream;
+mod sync_io;
+mod utf8;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-pub use self::iter::{DrainChunks, IntoChunks};
+pub use self::alloc::StagingAlloc;
+#[cfg(any(feature = "http-body", feature = "actix-http"))]
+pub use self::body::ChunkedBody;
+pub use self::budget::MemoryBudget;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use self::buf_reader::ChunkedBufReader;
+pub use self::buf_writer::ChunkedBufWriter;
+pub use self::cursor::ChunkedCursor;
+pub use self::decimal::Integer;
+pub use self::error::CapacityError;
+#[cfg(feature = "digest")]
+pub use self::hashing::HashingWriter;
+pub use self::hex::HexDump;
+#[cfg(unix)]
+pub use self::io_vec::OwnedIoVec;
+pub use self::iter::{DrainChunks, IntoChunks, Packets};
+pub use self::json::JsonEscape;
+pub use self::layout::Layout;
 pub use self::loosely::ChunkedBytes;
+#[cfg(feature = "rayon")]
+pub use self::par::ParChunks;
+#[cfg(feature = "percent-encoding")]
+pub use self::percent::PercentEncoder;
+#[cfg(feature = "tokio")]
+pub use self::pipe::{pipe, PipeReader, PipeWriter};
+pub use self::pool::{ChunkedBytesPool, Pooled, Recycle};
+pub use self::rope::{ChunkedRope, FrozenSlice};
+#[cfg(feature = "spill")]
+pub use self::spill::SpillBuffer;
+pub use self::stats::{ChunkStats, Histogram};
+pub use self::storage::{ChunkStorage, SharedStorage};
+pub use self::sync_io::{ReadChunkedExt, WriteChunkedExt};
 
 #[cfg(test)]
 mod tests;
@@ -1,13 +1,44 @@
 //! Buffer with a loose adherence to the preferred chunk size.
 
 use super::chunked::Inner;
-use crate::{DrainChunks, IntoChunks};
+use crate::chunked::put_vectored;
+use crate::decimal::DecimalBuf;
+#[cfg(feature = "postcard")]
+use crate::postcard_flavor::PutFlavor;
+use crate::sync_io;
+#[cfg(unix)]
+use crate::unix;
+#[cfg(feature = "xxhash")]
+use crate::xxhash;
+#[cfg(feature = "digest")]
+use crate::HashingWriter;
+#[cfg(feature = "rayon")]
+use crate::ParChunks;
+#[cfg(feature = "percent-encoding")]
+use crate::PercentEncoder;
+use crate::{hex, HexDump, JsonEscape};
+use crate::{
+    CapacityError, ChunkStats, ChunkStorage, ChunkedRope, DrainChunks, Integer,
+    IntoChunks, Layout, MemoryBudget, Packets, SharedStorage, StagingAlloc,
+};
 
 use bytes::buf::{Buf, BufMut, UninitSlice};
 use bytes::Bytes;
+#[cfg(feature = "rayon")]
+use rayon::iter::ParallelIterator;
 
+use std::cmp::min;
+use std::collections::TryReserveError;
+use std::convert::TryFrom;
 use std::fmt;
-use std::io::IoSlice;
+use std::io::{self, IoSlice};
+use std::mem;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio::time::Instant;
 
 /// A non-contiguous buffer for efficient serialization of data structures.
 ///
@@ -26,12 +57,27 @@ use std::io::IoSlice;
 /// Refer to the documentation on the methods available for `ChunkedBytes`,
 /// including the methods of traits `Buf` and `BufMut`, for details on working
 /// with this container.
-#[derive(Debug, Default)]
-pub struct ChunkedBytes {
-    inner: Inner,
+///
+/// The type parameter `S` is the type of storage backing the chunks
+/// appended with `put_storage`. Most applications can use the alias
+/// `ChunkedBytes` with the default storage type, `SharedStorage`.
+pub struct ChunkedBytesWith<S: ChunkStorage> {
+    inner: Inner<S>,
+}
+
+/// A `ChunkedBytesWith` container with the default chunk storage.
+pub type ChunkedBytes = ChunkedBytesWith<SharedStorage>;
+
+impl<S: ChunkStorage> Default for ChunkedBytesWith<S> {
+    #[inline]
+    fn default() -> Self {
+        ChunkedBytesWith {
+            inner: Inner::default(),
+        }
+    }
 }
 
-impl ChunkedBytes {
+impl<S: ChunkStorage> ChunkedBytesWith<S> {
     /// Creates a new `ChunkedBytes` container with the preferred chunk size
     /// set to a default value.
     #[inline]
@@ -43,11 +89,41 @@ impl ChunkedBytes {
     /// to prefer.
     #[inline]
     pub fn with_chunk_size_hint(chunk_size: usize) -> Self {
-        ChunkedBytes {
+        ChunkedBytesWith {
             inner: Inner::with_chunk_size(chunk_size),
         }
     }
 
+    /// Creates a new `ChunkedBytes` container with the given chunk size
+    /// to prefer, allocating staging buffers at addresses aligned to
+    /// `alignment`.
+    ///
+    /// Every chunk split off the staging buffer starts at an aligned
+    /// address, which makes the chunks suitable for SIMD processing or
+    /// direct I/O. The staged bytes are never moved by a reallocation:
+    /// when the staging buffer is full, its content is split off and
+    /// a new aligned buffer is allocated. The alignment of chunks appended
+    /// with `put_bytes` is not affected, and consuming data from the staging
+    /// buffer may cause the next chunk produced from it to be misaligned.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `alignment` is not a power of two, or if
+    /// `chunk_size` is not a non-zero multiple of `alignment`.
+    pub fn with_aligned_chunks(chunk_size: usize, alignment: usize) -> Self {
+        assert!(
+            alignment.is_power_of_two(),
+            "alignment must be a power of two"
+        );
+        assert!(
+            chunk_size != 0 && chunk_size & (alignment - 1) == 0,
+            "chunk size must be a multiple of the alignment"
+        );
+        ChunkedBytesWith {
+            inner: Inner::with_alignment(chunk_size, alignment),
+        }
+    }
+
     /// The fully detailed constructor for `ChunkedBytes`.
     /// The preferred chunk size is given in `chunk_size`, and an upper
     /// estimate of the number of chunks this container could be expected to
@@ -56,11 +132,35 @@ impl ChunkedBytes {
     /// internal data structures.
     #[inline]
     pub fn with_profile(chunk_size: usize, chunking_capacity: usize) -> Self {
-        ChunkedBytes {
+        ChunkedBytesWith {
             inner: Inner::with_profile(chunk_size, chunking_capacity),
         }
     }
 
+    /// Creates a new `ChunkedBytes` container holding `chunk` as its first
+    /// complete chunk, with the preferred chunk size set to a default value.
+    ///
+    /// Unlike `put_bytes` called on a newly created container,
+    /// this never copies the data, even if `chunk` is small.
+    #[inline]
+    pub fn with_first_chunk(chunk: Bytes) -> Self {
+        Self::from_chunks(Some(chunk))
+    }
+
+    /// Creates a new `ChunkedBytes` container holding the `Bytes` slices
+    /// produced by `chunks` as its complete chunks, in the iteration order.
+    /// Empty slices are skipped. The preferred chunk size is set to
+    /// a default value.
+    #[inline]
+    pub fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        ChunkedBytesWith {
+            inner: Inner::with_chunks(chunks),
+        }
+    }
+
     /// Returns the size this `ChunkedBytes` container uses as the threshold
     /// for splitting off complete chunks.
     ///
@@ -72,6 +172,175 @@ impl ChunkedBytes {
         self.inner.chunk_size()
     }
 
+    /// Returns the threshold set with `set_coalesce_threshold`.
+    /// The default is 0, meaning that chunks are not coalesced.
+    #[inline]
+    pub fn coalesce_threshold(&self) -> usize {
+        self.inner.coalesce_threshold()
+    }
+
+    /// Enables automatic coalescing of small chunks.
+    ///
+    /// When a chunk shorter than `threshold` bytes is appended, either by
+    /// splitting off the staging buffer or with `put_bytes`, and the last
+    /// complete chunk in the queue is also shorter than `threshold`,
+    /// the two are merged by copying into a new chunk. This keeps the queue
+    /// short under producers interleaving many tiny writes, at the cost
+    /// of copying. Setting the threshold to 0 disables coalescing.
+    #[inline]
+    pub fn set_coalesce_threshold(&mut self, threshold: usize) {
+        self.inner.set_coalesce_threshold(threshold)
+    }
+
+    /// Returns the alignment of the staging buffer allocations, as set with
+    /// `with_aligned_chunks`. This is 1 if the container was not created
+    /// with aligned allocation.
+    #[inline]
+    pub fn alignment(&self) -> usize {
+        self.inner.alignment()
+    }
+
+    /// Returns the maximum total length of data that this `ChunkedBytes`
+    /// container can hold. Unless set with `set_max_len`, the length
+    /// is not bounded and the returned value is `usize::MAX`.
+    #[inline]
+    pub fn max_len(&self) -> usize {
+        self.inner.max_len()
+    }
+
+    /// Sets the maximum total length of data that this `ChunkedBytes`
+    /// container can hold.
+    ///
+    /// While the limit is in effect, `remaining_mut` reports the number of
+    /// bytes that can be written before the limit is reached, and so
+    /// the `BufMut` methods that check it panic when asked to write
+    /// past the limit. Use `try_put_slice` and `try_put_bytes` to handle
+    /// the overflow as an error instead.
+    ///
+    /// If the container already holds more than `max_len` bytes,
+    /// no data is discarded, but no more can be written until enough
+    /// has been consumed. Setting the limit to `usize::MAX` removes it.
+    #[inline]
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.inner.set_max_len(max_len)
+    }
+
+    /// Returns the length below which `put_bytes` copies the data into
+    /// the staging buffer rather than appending the slice as a chunk.
+    ///
+    /// Unless set with `set_copy_threshold`, this is an eighth of
+    /// the preferred chunk size.
+    #[inline]
+    pub fn copy_threshold(&self) -> usize {
+        self.inner.copy_threshold()
+    }
+
+    /// Sets the length below which `put_bytes` copies the data into
+    /// the staging buffer rather than appending the slice as a chunk.
+    ///
+    /// The break-even point between copying and the atomic reference
+    /// counting of a shared `Bytes` slice depends on the workload.
+    /// Setting the threshold to 0 disables copying.
+    #[inline]
+    pub fn set_copy_threshold(&mut self, threshold: usize) {
+        self.inner.set_copy_threshold(threshold)
+    }
+
+    /// Sets a memory budget shared with other containers, or removes it
+    /// if `budget` is `None`.
+    ///
+    /// The data currently held by the container are charged to the new
+    /// budget, and released from the previous one, if any. While the budget
+    /// is in effect, the length of data that can be written is bounded by
+    /// the amount left in the budget, as well as by the maximum length set
+    /// with `set_max_len`.
+    #[inline]
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.inner.set_memory_budget(budget)
+    }
+
+    /// Sets the limit for the ring buffer mode. Setting the limit to
+    /// `usize::MAX`, which is the default, disables the mode.
+    ///
+    /// In the ring buffer mode, once the buffered data exceed `limit` bytes,
+    /// the oldest complete chunks are dropped to make room for new data,
+    /// which is useful for keeping only the last written data, e.g. the tail
+    /// of a log. Only whole chunks are dropped, and the data in the staging
+    /// buffer are never dropped, so the length of the buffered data may
+    /// exceed the limit by up to the length of a chunk plus the staging
+    /// buffer. The total length of the dropped data is reported by
+    /// `evicted_len`.
+    #[inline]
+    pub fn set_ring_limit(&mut self, limit: usize) {
+        self.inner.set_ring_limit(limit)
+    }
+
+    /// Returns the total number of bytes dropped in the ring buffer mode.
+    #[inline]
+    pub fn evicted_len(&self) -> u64 {
+        self.inner.evicted_len()
+    }
+
+    /// Returns the low and high watermarks set with `set_watermarks`.
+    #[inline]
+    pub fn watermarks(&self) -> (usize, usize) {
+        self.inner.watermarks()
+    }
+
+    /// Sets the low and high watermarks for the amount of buffered data,
+    /// to be checked with `is_above_high_watermark` and
+    /// `is_below_low_watermark`.
+    ///
+    /// The watermarks are intended for applying backpressure on the
+    /// producer: when the buffered data reach the high watermark, the
+    /// producer should pause until the consumer has drained the buffer down
+    /// to the low watermark. Unless set, the low watermark is 0 and
+    /// the high watermark is `usize::MAX`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `low` is greater than `high`.
+    #[inline]
+    pub fn set_watermarks(&mut self, low: usize, high: usize) {
+        self.inner.set_watermarks(low, high)
+    }
+
+    /// Returns true if the number of buffered bytes is at or above the high
+    /// watermark set with `set_watermarks`.
+    #[inline]
+    pub fn is_above_high_watermark(&self) -> bool {
+        self.inner.is_above_high_watermark()
+    }
+
+    /// Polls for at least `n` bytes of capacity to become available for
+    /// appending data without reaching the high watermark set with
+    /// `set_watermarks` or exceeding the maximum length set with
+    /// `set_max_len`.
+    ///
+    /// If there is not enough capacity, the task in `cx` is registered to be
+    /// woken up when the buffered data is consumed, or the limits are
+    /// changed. This is intended for a producer sharing the container
+    /// with a consumer, e.g. behind a mutex. Only the task of the last call
+    /// is registered.
+    ///
+    /// If the buffer is empty, `Poll::Ready` is returned even if `n` exceeds
+    /// the limits, as no amount of consumption would free more capacity.
+    #[inline]
+    pub fn poll_capacity(
+        &mut self,
+        cx: &mut Context<'_>,
+        n: usize,
+    ) -> Poll<()> {
+        self.inner.poll_capacity(cx, n)
+    }
+
+    /// Returns true if the number of buffered bytes is at or below the low
+    /// watermark set with `set_watermarks`.
+    #[inline]
+    pub fn is_below_low_watermark(&self) -> bool {
+        self.inner.is_below_low_watermark()
+    }
+
     /// Returns true if the `ChunkedBytes` container has no complete chunks
     /// and the staging buffer is empty.
     #[inline]
@@ -79,11 +348,300 @@ impl ChunkedBytes {
         self.inner.is_empty()
     }
 
-    #[cfg(test)]
+    /// Removes all buffered data from the container.
+    ///
+    /// The allocation of the staging buffer and the capacity of the chunk
+    /// queue are retained for reuse, as are all settings.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    // Takes over the reusable allocations of a cleared container.
+    pub(crate) fn reuse_allocations(&mut self, other: &mut Self) {
+        self.inner.reuse_allocations(&mut other.inner);
+    }
+
+    /// Returns a snapshot of the current layout of the buffered data
+    /// in chunks and the staging buffer.
+    #[inline]
+    pub fn layout(&self) -> Layout {
+        self.inner.layout()
+    }
+
+    /// Returns the number of complete chunks in the container.
+    /// This does not include the staging buffer.
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.inner.chunk_count()
+    }
+
+    /// Returns the number of `IoSlice` entries that `chunks_vectored`
+    /// needs to gather all of the buffered data: one for each complete
+    /// chunk, and one for the staging buffer if it is not empty.
+    #[inline]
+    pub fn vectored_len(&self) -> usize {
+        self.chunk_count() + (self.inner.staging_len() != 0) as usize
+    }
+
+    /// Returns the offset of the first occurrence of `byte` in the buffered
+    /// data, counted from the current reading position.
+    ///
+    /// Each chunk is scanned with the vectorized routines of the `memchr`
+    /// crate.
+    #[inline]
+    pub fn find_byte(&self, byte: u8) -> Option<usize> {
+        self.inner.find_byte(byte)
+    }
+
+    /// Returns the offset of the first occurrence of `needle` in
+    /// the buffered data, counted from the current reading position.
+    ///
+    /// Occurrences spanning the boundaries between chunks are found as
+    /// well. An empty `needle` is found at offset 0.
+    #[inline]
+    pub fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.inner.find(needle)
+    }
+
+    /// Returns true if the buffered data are valid UTF-8.
+    ///
+    /// The chunks are validated one by one, with the code points split
+    /// between chunks checked as a whole. With the `simdutf8` feature,
+    /// the validation uses the SIMD routines of the `simdutf8` crate.
+    #[inline]
+    pub fn is_utf8(&self) -> bool {
+        self.inner.is_utf8()
+    }
+
+    /// Returns a parallel iterator over the slices of the buffered data,
+    /// in the same order as `chunks_vectored` would fill them.
+    ///
+    /// This method is only available with the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_chunks(&self) -> ParChunks<'_> {
+        self.inner.par_chunks_capped(usize::MAX)
+    }
+
+    /// Folds the slices of the buffered data in parallel, combining
+    /// the partial results with `reduce`.
+    ///
+    /// Each parallel task starts with a value produced by `identity` and
+    /// folds a run of consecutive slices into it with `fold`. The results
+    /// of adjacent runs are combined in order, so an associative `reduce`
+    /// gives the same result as folding sequentially.
+    ///
+    /// This method is only available with the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    pub fn par_fold<T, ID, F, R>(&self, identity: ID, fold: F, reduce: R) -> T
+    where
+        T: Send,
+        ID: Fn() -> T + Sync + Send,
+        F: Fn(T, &[u8]) -> T + Sync + Send,
+        R: Fn(T, T) -> T + Sync + Send,
+    {
+        self.par_chunks()
+            .fold(&identity, fold)
+            .reduce(&identity, reduce)
+    }
+
+    /// Fills `dst` with the slice views of the buffered data like
+    /// `chunks_vectored` does, but stops once the total length of
+    /// the slices reaches `max_bytes`, cutting the last slice short
+    /// if necessary.
+    ///
+    /// Returns the number of `IoSlice` entries filled and the total
+    /// number of bytes in them, which is less than `max_bytes` only if
+    /// `dst` has been filled up or all of the buffered data have been
+    /// gathered.
+    #[inline]
+    pub fn chunks_vectored_limited<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        max_bytes: usize,
+    ) -> (usize, usize) {
+        self.inner
+            .chunks_vectored_limited(dst, usize::MAX, max_bytes)
+    }
+
+    /// Fills `dst` with the slice views of the buffered data like
+    /// `chunks_vectored` does, but starting at `offset` bytes past
+    /// the current reading position. Returns the number of `IoSlice`
+    /// entries filled, which is 0 if `offset` is not less than
+    /// the length of the buffered data.
+    ///
+    /// This allows a writer to resume gathering after a partial write
+    /// without advancing the reading position until the data has been
+    /// written out completely.
+    #[inline]
+    pub fn chunks_vectored_at<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        offset: usize,
+    ) -> usize {
+        self.inner.chunks_vectored_at(dst, offset, usize::MAX)
+    }
+
+    /// Splits the buffered data into two at the given index, removing
+    /// the first `at` bytes from this container and returning them in
+    /// a new container with the same preferred chunk size.
+    ///
+    /// The chunks are moved or split between the containers without
+    /// copying the data. Bytes taken from the staging buffer become
+    /// a complete chunk of the returned container.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the buffered data.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        ChunkedBytesWith {
+            inner: self.inner.split_to(at),
+        }
+    }
+
+    /// Splits off the buffered data up to and including the first
+    /// occurrence of `byte`, returning them in a new container like
+    /// `split_to` does.
+    ///
+    /// Returns `None`, leaving the data in place, if `byte` is not found
+    /// in the buffered data.
+    #[inline]
+    pub fn read_until_byte(&mut self, byte: u8) -> Option<Self> {
+        let pos = self.find_byte(byte)?;
+        Some(self.split_to(pos + 1))
+    }
+
+    /// Splits off the buffered data up to and including the first
+    /// occurrence of `delimiter`, returning them in a new container like
+    /// `split_to` does.
+    ///
+    /// Occurrences spanning the boundaries between chunks are found as well.
+    /// Returns `None`, leaving the data in place, if `delimiter` is not found
+    /// in the buffered data. This makes it possible to extract lines or other
+    /// delimited frames as they are received, without copying.
+    #[inline]
+    pub fn read_until(&mut self, delimiter: &[u8]) -> Option<Self> {
+        let pos = self.find(delimiter)?;
+        Some(self.split_to(pos + delimiter.len()))
+    }
+
+    // Calls `f` with the mutable slices of the last `len` bytes of
+    // the buffered data, copying the shared chunks in the range.
+    #[inline]
+    pub(crate) fn for_each_tail_mut<F>(&mut self, len: usize, f: F)
+    where
+        F: FnMut(&mut [u8]),
+    {
+        self.inner.for_each_tail_mut(len, f)
+    }
+
+    /// Feeds the buffered data to `hasher`, chunk by chunk, without
+    /// consuming it.
+    ///
+    /// This method is only available with the `digest` feature.
+    #[cfg(feature = "digest")]
+    pub fn hash_into<D: digest::Update>(&self, hasher: &mut D) {
+        for slice in self.inner.slices() {
+            hasher.update(slice);
+        }
+    }
+
+    /// Returns the 64-bit XXH3 hash of the buffered data, computed
+    /// chunk by chunk.
+    ///
+    /// This method is only available with the `xxhash` feature.
+    #[cfg(feature = "xxhash")]
+    pub fn xxh3_64(&self) -> u64 {
+        let mut hasher = xxhash::Xxh3::new();
+        self.hash_into(&mut hasher);
+        hasher.digest()
+    }
+
+    /// Returns the XXH64 hash of the buffered data with the given seed,
+    /// computed chunk by chunk.
+    ///
+    /// This method is only available with the `xxhash` feature.
+    #[cfg(feature = "xxhash")]
+    pub fn xxh64(&self, seed: u64) -> u64 {
+        let mut hasher = xxhash::Xxh64::new(seed);
+        self.hash_into(&mut hasher);
+        hasher.digest()
+    }
+
+    /// Returns the number of bytes in the staging buffer.
+    #[inline]
+    pub fn staging_len(&self) -> usize {
+        self.inner.staging_len()
+    }
+
+    /// Returns the capacity of the staging buffer, including the bytes
+    /// that are currently in it.
+    #[inline]
     pub fn staging_capacity(&self) -> usize {
         self.inner.staging_capacity()
     }
 
+    /// Returns the number of times the staging buffer has been split
+    /// into a complete chunk over the lifetime of the container, whether
+    /// by an explicit call to `flush` or internally.
+    #[inline]
+    pub fn flush_count(&self) -> u64 {
+        self.inner.flush_count()
+    }
+
+    /// Returns the number of times more capacity had to be reserved
+    /// in the staging buffer over the lifetime of the container.
+    ///
+    /// Each reservation results in either an allocation of a new buffer,
+    /// or reuse of the previously allocated buffer after its contents
+    /// have been consumed. A count that grows much faster than the amount
+    /// of data written divided by the chunk size indicates that the chunk
+    /// size may be too small for the pattern of usage.
+    #[inline]
+    pub fn reserve_count(&self) -> u64 {
+        self.inner.reserve_count()
+    }
+
+    /// Registers a callback to be invoked whenever a complete chunk is
+    /// added to the container, receiving the length of the chunk.
+    ///
+    /// The callback is invoked when the staging buffer is split off into
+    /// a chunk, whether by an explicit call to `flush` or internally,
+    /// and for every chunk appended with `put_bytes`. This can be used to
+    /// wake up a task draining complete chunks as soon as one is available.
+    /// Any previously registered callback is replaced.
+    #[inline]
+    pub fn set_chunk_observer<F>(&mut self, observer: F)
+    where
+        F: FnMut(usize) + Send + Sync + 'static,
+    {
+        self.inner.set_chunk_observer(observer)
+    }
+
+    /// Removes the callback registered with `set_chunk_observer`, if any.
+    #[inline]
+    pub fn clear_chunk_observer(&mut self) {
+        self.inner.clear_chunk_observer()
+    }
+
+    /// Starts recording statistics on the sizes of chunks produced
+    /// and consumed in this container. If recording has already been
+    /// enabled, this method does nothing.
+    ///
+    /// The recorded statistics can be retrieved with `stats`.
+    #[inline]
+    pub fn record_stats(&mut self) {
+        self.inner.record_stats()
+    }
+
+    /// Returns the statistics recorded since `record_stats` has been
+    /// called, or `None` if recording has not been enabled.
+    #[inline]
+    pub fn stats(&self) -> Option<&ChunkStats> {
+        self.inner.stats()
+    }
+
     /// Splits any bytes that are currently in the staging buffer into a new
     /// complete chunk.
     /// If the staging buffer is empty, this method does nothing.
@@ -96,27 +654,579 @@ impl ChunkedBytes {
         self.inner.flush()
     }
 
-    /// Appends a `Bytes` slice to the container without copying the data.
+    /// Forces chunk boundaries at the given offsets, counted in bytes
+    /// from the current end of the buffered data.
+    ///
+    /// As bytes are subsequently written into the container, the staging
+    /// buffer is split off into a complete chunk whenever the writing
+    /// position reaches one of the offsets, and slices appended with
+    /// `put_bytes` are split at the offsets they span. This replaces any
+    /// boundaries set by a previous call to this method; the boundaries
+    /// produced by the regular chunking policy still apply.
+    ///
+    /// This method is intended for testing consumers of the buffered data
+    /// with deterministic chunk layouts, and is only available with
+    /// the `test-util` feature.
+    #[cfg(feature = "test-util")]
+    pub fn set_chunk_boundaries<I>(&mut self, offsets: I)
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.inner.set_chunk_boundaries(offsets)
+    }
+
+    /// Enables or disables the exact reservation mode.
+    ///
+    /// By default, the staging buffer is grown with `BytesMut::reserve`,
+    /// which may allocate well beyond the chunk size. In the exact mode,
+    /// the bytes in the staging buffer are split off into a complete chunk
+    /// whenever more capacity is needed, and a new buffer is allocated
+    /// with the capacity of the chunk size, or the requested amount if
+    /// that is larger. This keeps the memory use predictable at the cost
+    /// of not reusing the allocations of consumed staging buffers.
+    #[inline]
+    pub fn set_reserve_exact(&mut self, enable: bool) {
+        self.inner.set_reserve_exact(enable)
+    }
+
+    /// Sets the allocator supplying the staging buffers for this container.
+    ///
+    /// The staging buffers allocated after this call, and any further
+    /// growth of the staging buffer, are obtained from `alloc`. The buffers
+    /// that are no longer used, including the staging buffer of a dropped
+    /// container, are given back to `alloc` for recycling.
+    #[inline]
+    pub fn set_staging_alloc(&mut self, alloc: Arc<dyn StagingAlloc>) {
+        self.inner.set_staging_alloc(alloc)
+    }
+
+    /// Enables or disables zeroing of the memory that held the staged
+    /// data when the data is consumed or the container is dropped.
+    ///
+    /// When enabled, the bytes read from the staging buffer are zeroed as
+    /// the reading position is advanced past them, and any bytes remaining
+    /// in the staging buffer are zeroed when the container is cleared
+    /// or dropped. The same applies to the complete chunks split off
+    /// the staging buffer, which are owned by the container until they
+    /// are handed out as `Bytes`; they are also zeroed when evicted in
+    /// the ring buffer mode. Chunks appended with `put_bytes` or as custom
+    /// storage are not zeroed, as they may be shared with other owners.
+    /// Copies of the staged data made when the staging buffer is
+    /// reallocated, or when small chunks are merged, are not zeroed either.
+    ///
+    /// This method is only available with the `zeroize` feature.
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    pub fn set_zeroize(&mut self, enable: bool) {
+        self.inner.set_zeroize(enable)
+    }
+
+    /// Starts recording the time when bytes are first written into
+    /// the staging buffer after it's been empty. If bytes are already in
+    /// the staging buffer, they are considered to be written at the time
+    /// of this call. If recording has already been enabled, this method
+    /// does nothing.
+    ///
+    /// The recorded time can be retrieved with `staged_at`. Recording is
+    /// enabled automatically by `poll_flush_after`.
+    ///
+    /// This method is only available with the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn record_staged_at(&mut self) {
+        self.inner.record_staged_at()
+    }
+
+    /// Returns the time when bytes were first written into the staging
+    /// buffer since it was last empty, or `None` if the staging buffer
+    /// is empty or the time has not been recorded since
+    /// `record_staged_at` was called.
+    ///
+    /// This method is only available with the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub fn staged_at(&self) -> Option<Instant> {
+        self.inner.staged_at()
+    }
+
+    /// Appends a `Bytes` slice to the container, copying the data only
+    /// if the slice is small.
     ///
-    /// If `chunk` is empty, this method does nothing. Otherwise,
-    /// if there are any bytes currently in the staging buffer, they are split
-    /// to form a complete chunk. Next, the given slice is appended as the
-    /// next chunk.
+    /// If `chunk` is empty, this method does nothing. If it is shorter
+    /// than the threshold returned by `copy_threshold` (512 bytes with
+    /// the default settings), the bytes are copied into the staging buffer,
+    /// so that a stream of small slices does not fragment the container
+    /// into many tiny chunks. Otherwise, if there are any bytes currently
+    /// in the staging buffer, they are split to form a complete chunk.
+    /// Next, the given slice is appended as the next chunk.
     ///
-    /// # Performance Notes
+    /// # Panics
     ///
-    /// For a small slice originating from a buffer that is not split
-    /// or shared between other `Bytes` instances, copying the bytes with
-    /// `BufMut::put_slice` may be faster than the overhead of
-    /// atomic reference counting induced by use of this method.
+    /// This method panics if appending the slice would exceed the maximum
+    /// length set with `set_max_len`.
     #[inline]
     pub fn put_bytes(&mut self, chunk: Bytes) {
         if !chunk.is_empty() {
+            assert!(
+                self.inner.fits(chunk.len()),
+                "maximum length of the buffer exceeded"
+            );
+            if chunk.len() < self.inner.copy_threshold() {
+                self.put_slice(&chunk);
+            } else {
+                self.flush();
+                self.inner.push_chunk(chunk);
+            }
+        }
+    }
+
+    /// Appends a chunk backed by `storage` without copying its data.
+    ///
+    /// If `storage` is empty, this method does nothing. Otherwise, if there
+    /// are any bytes currently in the staging buffer, they are split to form
+    /// a complete chunk. Next, the stored bytes are appended as the next
+    /// chunk. See the documentation on `ChunkStorage` for how
+    /// the data of such chunks are handed out.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if appending the data would exceed the maximum
+    /// length set with `set_max_len`.
+    pub fn put_storage<T: Into<S>>(&mut self, storage: T) {
+        let storage = storage.into();
+        let len = storage.len();
+        if len != 0 {
+            assert!(
+                self.inner.fits(len),
+                "maximum length of the buffer exceeded"
+            );
             self.flush();
-            self.inner.push_chunk(chunk);
+            self.inner.push_storage_capped(storage, usize::MAX);
+        }
+    }
+
+    /// Tries to reserve capacity for at least `additional` more bytes to be
+    /// written into the staging buffer, so that the next slice returned by
+    /// `chunk_mut` is at least as long.
+    ///
+    /// If the staging buffer does not have enough spare capacity, any bytes
+    /// in it are split off into a complete chunk before a new buffer
+    /// is allocated with the size of the greater of `additional` and
+    /// the preferred chunk size.
+    ///
+    /// # Errors
+    ///
+    /// Unlike the allocation performed by the `BufMut` methods,
+    /// which aborts the process when memory is exhausted, this method returns
+    /// an error if the allocation cannot be made or the capacity overflows.
+    /// The container is not modified in this case. A staging allocator set
+    /// with `set_staging_alloc` is expected to handle allocation failures
+    /// of staging buffers itself.
+    #[inline]
+    pub fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional, usize::MAX)?;
+        Ok(())
+    }
+
+    /// Appends the bytes of `src` if they fit into the maximum length
+    /// of the container, like `BufMut::put_slice` would.
+    ///
+    /// # Errors
+    ///
+    /// If writing all of `src` would exceed the maximum length set with
+    /// `set_max_len`, nothing is written and a `CapacityError` is returned.
+    #[inline]
+    pub fn try_put_slice(&mut self, src: &[u8]) -> Result<(), CapacityError> {
+        if self.inner.fits(src.len()) {
+            self.put_slice(src);
+            Ok(())
+        } else {
+            Err(CapacityError::new(()))
         }
     }
 
+    /// Appends a `Bytes` slice without copying if it fits into the maximum
+    /// length of the container, like `put_bytes` would.
+    ///
+    /// # Errors
+    ///
+    /// If appending `src` would exceed the maximum length set with
+    /// `set_max_len`, the container is not modified and `src` is returned
+    /// in the `CapacityError`.
+    #[inline]
+    pub fn try_put_bytes(
+        &mut self,
+        src: Bytes,
+    ) -> Result<(), CapacityError<Bytes>> {
+        if self.inner.fits(src.len()) {
+            self.put_bytes(src);
+            Ok(())
+        } else {
+            Err(CapacityError::new(src))
+        }
+    }
+
+    /// Appends the decimal representation of an integer.
+    ///
+    /// This is more efficient than formatting the value with `write!`,
+    /// as it bypasses the `std::fmt` machinery.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the digits would exceed the maximum
+    /// length set with `set_max_len`.
+    #[inline]
+    pub fn put_itoa<T: Integer>(&mut self, value: T) {
+        let mut digits = DecimalBuf::new();
+        value.format_decimal(&mut digits);
+        self.put_slice(digits.as_bytes());
+    }
+
+    /// Appends the shortest decimal representation of a `f64` value
+    /// that round-trips to the same value when parsed.
+    ///
+    /// Non-finite values are represented as `NaN`, `inf`, and `-inf`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the text would exceed the maximum
+    /// length set with `set_max_len`.
+    #[cfg(feature = "ryu")]
+    #[inline]
+    pub fn put_f64_str(&mut self, value: f64) {
+        let mut text = ryu::Buffer::new();
+        self.put_slice(text.format(value).as_bytes());
+    }
+
+    /// Appends the shortest decimal representation of a `f32` value
+    /// that round-trips to the same value when parsed.
+    ///
+    /// Non-finite values are represented as `NaN`, `inf`, and `-inf`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the text would exceed the maximum
+    /// length set with `set_max_len`.
+    #[cfg(feature = "ryu")]
+    #[inline]
+    pub fn put_f32_str(&mut self, value: f32) {
+        let mut text = ryu::Buffer::new();
+        self.put_slice(text.format(value).as_bytes());
+    }
+
+    /// Appends the text produced by formatting `value` with its
+    /// implementation of `Display`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the formatting fails, including the cases
+    /// where memory cannot be allocated or the maximum length set with
+    /// `set_max_len` is exceeded.
+    #[inline]
+    pub fn put_display<T: fmt::Display + ?Sized>(&mut self, value: &T) {
+        fmt::Write::write_fmt(self, format_args!("{}", value))
+            .expect("failed to format the value into the buffer")
+    }
+
+    /// Encodes a Protobuf message into the buffer.
+    ///
+    /// Capacity for the encoded length of the message is reserved in
+    /// the staging buffer before encoding, flushing the staging buffer
+    /// if it does not have enough spare capacity.
+    ///
+    /// # Errors
+    ///
+    /// If the encoded message would exceed the maximum length set with
+    /// `set_max_len`, nothing is written and an error is returned.
+    ///
+    /// This method is only available with the `prost` feature.
+    #[cfg(feature = "prost")]
+    pub fn put_message<M>(&mut self, msg: &M) -> Result<(), prost::EncodeError>
+    where
+        M: prost::Message,
+    {
+        // If the reservation fails, the writes will allocate as needed
+        let _ = self.try_reserve(msg.encoded_len());
+        msg.encode(self)
+    }
+
+    /// Encodes a Protobuf message into the buffer, preceded by its length
+    /// encoded as a varint.
+    ///
+    /// Capacity for the length and the encoded message is reserved like
+    /// in `put_message`.
+    ///
+    /// # Errors
+    ///
+    /// If the encoded message would exceed the maximum length set with
+    /// `set_max_len`, nothing is written and an error is returned.
+    ///
+    /// This method is only available with the `prost` feature.
+    #[cfg(feature = "prost")]
+    pub fn put_message_length_delimited<M>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError>
+    where
+        M: prost::Message,
+    {
+        let len = msg.encoded_len();
+        let _ = self.try_reserve(prost::length_delimiter_len(len) + len);
+        msg.encode_length_delimited(self)
+    }
+
+    /// Appends a `Bytes` slice as MessagePack binary data, writing the `bin`
+    /// header and then appending the slice like `put_bytes` would,
+    /// without copying.
+    ///
+    /// Other MessagePack values can be written with `rmp` or `rmp-serde`
+    /// through the implementation of `io::Write`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the slice is longer than `u32::MAX` bytes,
+    /// or if appending the data would exceed the maximum length set with
+    /// `set_max_len`.
+    ///
+    /// This method is only available with the `rmp` feature.
+    #[cfg(feature = "rmp")]
+    pub fn put_msgpack_bin(&mut self, data: Bytes) {
+        let len = u32::try_from(data.len())
+            .expect("binary data is too long for MessagePack");
+        let mut header = [0; 5];
+        let mut out = &mut header[..];
+        rmp::encode::write_bin_len(&mut out, len)
+            .expect("the header should fit into the array");
+        let header_len = 5 - out.len();
+        assert!(
+            self.inner.fits(header_len + data.len()),
+            "maximum length of the buffer exceeded"
+        );
+        self.put_slice(&header[..header_len]);
+        self.put_bytes(data);
+    }
+
+    /// Appends a `Bytes` slice as a CBOR byte string, writing the header
+    /// and then appending the slice like `put_bytes` would, without copying.
+    ///
+    /// Other CBOR values can be written with `ciborium` or other
+    /// serializers through the implementation of `io::Write`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if appending the data would exceed the maximum
+    /// length set with `set_max_len`.
+    ///
+    /// This method is only available with the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    pub fn put_cbor_bytes(&mut self, data: Bytes) {
+        let mut header = [0; 9];
+        let mut out = &mut header[..];
+        ciborium_ll::Encoder::from(&mut out)
+            .push(ciborium_ll::Header::Bytes(Some(data.len())))
+            .expect("the header should fit into the array");
+        let header_len = 9 - out.len();
+        assert!(
+            self.inner.fits(header_len + data.len()),
+            "maximum length of the buffer exceeded"
+        );
+        self.put_slice(&header[..header_len]);
+        self.put_bytes(data);
+    }
+
+    /// Appends the chunks produced by an iterator as a CBOR byte string
+    /// of indefinite length, without copying.
+    ///
+    /// Each chunk is appended like with `put_cbor_bytes`, as a segment of
+    /// the string. This allows a byte string to be written as it is
+    /// produced, without knowing its length in advance.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if appending the data would exceed the maximum
+    /// length set with `set_max_len`. In this case, the segments appended
+    /// before the panic remain in the buffer.
+    ///
+    /// This method is only available with the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    pub fn put_cbor_bytes_chunked<I>(&mut self, chunks: I)
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        self.put_u8(0x5f);
+        for chunk in chunks {
+            self.put_cbor_bytes(chunk);
+        }
+        self.put_u8(0xff);
+    }
+
+    /// Serializes a value with `bincode` into the buffer.
+    ///
+    /// The serialized size of the value is computed first to reserve
+    /// capacity in the staging buffer, like in `put_message`.
+    ///
+    /// # Errors
+    ///
+    /// If the serialized value would exceed the maximum length set with
+    /// `set_max_len`, nothing is written and an error of kind `SizeLimit`
+    /// is returned. Errors of the serialization are passed through.
+    ///
+    /// This method is only available with the `bincode` feature.
+    #[cfg(feature = "bincode")]
+    pub fn put_bincode<T>(&mut self, value: &T) -> bincode::Result<()>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let len = bincode::serialized_size(value)? as usize;
+        if !self.inner.fits(len) {
+            return Err(Box::new(bincode::ErrorKind::SizeLimit));
+        }
+        let _ = self.try_reserve(len);
+        bincode::serialize_into(self, value)
+    }
+
+    /// Serializes a value with `postcard` into the buffer.
+    ///
+    /// # Errors
+    ///
+    /// If the serialized value would exceed the maximum length set with
+    /// `set_max_len`, an error of kind `SerializeBufferFull` is returned;
+    /// the data serialized before the error remain in the buffer.
+    /// Errors of the serialization are passed through.
+    ///
+    /// This method is only available with the `postcard` feature.
+    #[cfg(feature = "postcard")]
+    pub fn put_postcard<T>(&mut self, value: &T) -> postcard::Result<()>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        postcard::serialize_with_flavor(value, PutFlavor(self))
+    }
+
+    /// Returns a writer adapter appending the text written through it
+    /// to the buffer, escaped as the contents of a JSON string.
+    /// See the documentation of `JsonEscape` for details.
+    #[inline]
+    pub fn json_escape(&mut self) -> JsonEscape<'_, Self> {
+        JsonEscape::new(self)
+    }
+
+    /// Returns a writer adapter appending the text written through it
+    /// to the buffer as a quoted JSON string. The opening quotation mark
+    /// is appended immediately, the closing one when the adapter is dropped.
+    /// See the documentation of `JsonEscape` for details.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the opening quotation mark would exceed
+    /// the maximum length set with `set_max_len`.
+    #[inline]
+    pub fn json_string(&mut self) -> JsonEscape<'_, Self> {
+        JsonEscape::quoted(self)
+    }
+
+    /// Returns a writer adapter appending the data written through it
+    /// to the buffer, percent-encoding the bytes in `set` and all
+    /// non-ASCII bytes. See the documentation of `PercentEncoder`
+    /// for details.
+    ///
+    /// This method is only available with the `percent-encoding` feature.
+    #[cfg(feature = "percent-encoding")]
+    #[inline]
+    pub fn percent_encode(
+        &mut self,
+        set: &'static percent_encoding::AsciiSet,
+    ) -> PercentEncoder<'_, Self> {
+        PercentEncoder::new(self, set)
+    }
+
+    /// Returns a writer adapter appending the data written through it
+    /// to the buffer and updating `hasher` with the appended data.
+    /// See the documentation of `HashingWriter` for details.
+    ///
+    /// This method is only available with the `digest` feature.
+    #[cfg(feature = "digest")]
+    #[inline]
+    pub fn hashing_writer<D: digest::Update>(
+        &mut self,
+        hasher: D,
+    ) -> HashingWriter<'_, Self, D> {
+        HashingWriter::new(self, hasher)
+    }
+
+    /// Returns an adapter to display the buffered data as a hexadecimal dump.
+    /// See the documentation of `HexDump` for details on the output format.
+    #[inline]
+    pub fn hex_dump(&self) -> HexDump<'_, S> {
+        HexDump::new(&self.inner)
+    }
+
+    /// Writes the buffered data to `out` with a single call to
+    /// `write_vectored`, then advances the reading position by the number
+    /// of bytes written.
+    ///
+    /// Returns the number of bytes written, which may be less than the
+    /// length of the buffered data, as reported by `out`.
+    #[inline]
+    pub fn write_to<W>(&mut self, out: &mut W) -> io::Result<usize>
+    where
+        W: io::Write + ?Sized,
+    {
+        sync_io::write_to(self, out)
+    }
+
+    /// Writes the buffered data to the file `file` at the position `offset`
+    /// with a single call to `pwritev`, then advances the reading position
+    /// by the number of bytes written. The file offset is not changed.
+    ///
+    /// Returns the number of bytes written, which may be less than the
+    /// length of the buffered data.
+    ///
+    /// This method is only available on Unix platforms.
+    #[cfg(unix)]
+    #[inline]
+    pub fn write_to_at<F>(&mut self, file: &F, offset: u64) -> io::Result<usize>
+    where
+        F: AsRawFd + ?Sized,
+    {
+        unix::write_to_at(self, file, offset)
+    }
+
+    /// Reads up to `max` bytes from `src` directly into the staging buffer,
+    /// splitting off complete chunks as the staging buffer fills up
+    /// to the preferred chunk size.
+    ///
+    /// Reading stops when `max` bytes have been read, the reader
+    /// reaches end of input, or the maximum length set with `set_max_len`
+    /// is reached. Reads failing with `ErrorKind::Interrupted` are retried.
+    /// Returns the number of bytes read.
+    ///
+    /// If an error is returned, the bytes read before the failure remain
+    /// in the buffer.
+    #[inline]
+    pub fn read_from<R>(&mut self, src: &mut R, max: usize) -> io::Result<usize>
+    where
+        R: io::Read + ?Sized,
+    {
+        sync_io::read_from(self, src, max)
+    }
+
+    /// Merges runs of adjacent complete chunks shorter than `max_small`
+    /// bytes into new chunks of up to the preferred chunk size, copying
+    /// the data of the merged chunks. Larger chunks, and small chunks that
+    /// have no small neighbors, are kept without copying.
+    ///
+    /// This can be used to repair a buffer fragmented by many small
+    /// appended slices before a vectored write with a limited number
+    /// of I/O slices.
+    pub fn compact(&mut self, max_small: usize) {
+        let target = self.chunk_size_hint();
+        self.inner.compact(max_small, target)
+    }
+
     /// Returns an iterator that removes complete chunks from the
     /// `ChunkedBytes` container and yields the removed chunks as `Bytes`
     /// slice handles. This does not include bytes in the staging buffer.
@@ -126,10 +1236,26 @@ impl ChunkedBytes {
     /// if the `DrainChunks` value is not dropped, but the borrow it holds
     /// expires (e.g. due to `std::mem::forget`).
     #[inline]
-    pub fn drain_chunks(&mut self) -> DrainChunks<'_> {
+    pub fn drain_chunks(&mut self) -> DrainChunks<'_, S> {
         self.inner.drain_chunks()
     }
 
+    /// Returns an iterator that removes the buffered data in pieces of
+    /// at most `mtu` bytes, yielding each piece as `Bytes`. This is useful
+    /// for constructing datagrams with each piece as a packet's payload.
+    ///
+    /// A piece contained in a single chunk, or in the staging buffer,
+    /// is split off without copying. Smaller chunks are coalesced by copying
+    /// into pieces of `mtu` bytes, except for the last piece.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `mtu` is 0.
+    #[inline]
+    pub fn packets(&mut self, mtu: usize) -> Packets<'_, Self> {
+        Packets::new(self, mtu)
+    }
+
     /// Consumes the `ChunkedBytes` container to produce an iterator over
     /// its chunks. If there are bytes in the staging buffer, they are yielded
     /// as the last chunk.
@@ -138,12 +1264,26 @@ impl ChunkedBytes {
     /// `ChunkedBytes` container it consumes. This is an infrequent side effect
     /// of making the internal state efficient in general for iteration.
     #[inline]
-    pub fn into_chunks(self) -> IntoChunks {
-        self.inner.into_chunks()
+    pub fn into_chunks(mut self) -> IntoChunks<S> {
+        mem::take(&mut self.inner).into_chunks()
+    }
+
+    /// Consumes the `ChunkedBytes` container to produce an immutable
+    /// `ChunkedRope` holding its chunks. If there are bytes in the staging
+    /// buffer, they become the last chunk of the rope.
+    #[inline]
+    pub fn freeze(self) -> ChunkedRope {
+        self.into()
+    }
+}
+
+impl<S: ChunkStorage> Drop for ChunkedBytesWith<S> {
+    fn drop(&mut self) {
+        self.inner.release_staging();
     }
 }
 
-unsafe impl BufMut for ChunkedBytes {
+unsafe impl<S: ChunkStorage> BufMut for ChunkedBytesWith<S> {
     #[inline]
     fn remaining_mut(&self) -> usize {
         self.inner.remaining_mut()
@@ -167,9 +1307,62 @@ unsafe impl BufMut for ChunkedBytes {
         }
         self.inner.chunk_mut()
     }
+
+    /// Copies the bytes from `src` into the staging buffer.
+    ///
+    /// Unlike the default implementation, this method reserves the space
+    /// for the rest of `src` at once when the staging buffer is filled up,
+    /// so the slice is copied in at most two pieces, the first one
+    /// completing the chunk that gets split off.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `src` exceeds the remaining capacity
+    /// of the container as limited by `set_max_len`.
+    fn put_slice(&mut self, src: &[u8]) {
+        assert!(
+            self.inner.fits(src.len()),
+            "buffer overflow; remaining = {}; src = {}",
+            self.remaining_mut(),
+            src.len()
+        );
+        let mut src = src;
+        while !src.is_empty() {
+            if self.inner.staging_len() == self.inner.staging_capacity() {
+                self.inner.reserve_staging_for(src.len());
+            }
+            let dst = self.inner.chunk_mut();
+            let n = min(dst.len(), src.len());
+            dst[..n].copy_from_slice(&src[..n]);
+            unsafe {
+                self.inner.advance_mut(n);
+            }
+            src = &src[n..];
+        }
+    }
 }
 
-impl Buf for ChunkedBytes {
+/// Converts the container into `Bytes` if this can be done without copying.
+///
+/// The conversion succeeds if the buffered data are held entirely in either
+/// one complete chunk or the staging buffer. Otherwise, the container is
+/// returned unchanged in the error value.
+impl<S: ChunkStorage> TryFrom<ChunkedBytesWith<S>> for Bytes {
+    type Error = ChunkedBytesWith<S>;
+
+    fn try_from(
+        mut buf: ChunkedBytesWith<S>,
+    ) -> Result<Bytes, ChunkedBytesWith<S>> {
+        if buf.inner.is_contiguous() {
+            let bytes = mem::take(&mut buf.inner).into_chunks().next();
+            Ok(bytes.unwrap_or_default())
+        } else {
+            Err(buf)
+        }
+    }
+}
+
+impl<S: ChunkStorage> Buf for ChunkedBytesWith<S> {
     #[inline]
     fn remaining(&self) -> usize {
         self.inner.remaining()
@@ -214,21 +1407,85 @@ impl Buf for ChunkedBytes {
         self.inner.chunks_vectored(dst)
     }
 
+    /// Copies bytes into `dst` directly from the chunks and the staging
+    /// buffer, advancing the reading position by `dst.len()` and dropping
+    /// the chunks consumed in their entirety at once.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `self.remaining() < dst.len()`.
+    fn copy_to_slice(&mut self, dst: &mut [u8]) {
+        let _ = self.inner.copy_to_slice(dst);
+    }
+
     #[inline]
     fn copy_to_bytes(&mut self, len: usize) -> Bytes {
         self.inner.copy_to_bytes(len)
     }
 }
 
-impl fmt::Write for ChunkedBytes {
+/// Reads the buffered data, advancing the reading position like
+/// `Buf::copy_to_slice` would.
+impl<S: ChunkStorage> io::Read for ChunkedBytesWith<S> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = min(buf.len(), self.remaining());
+        self.copy_to_slice(&mut buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Appends the written data to the buffer.
+///
+/// If the maximum length set with `set_max_len` does not allow all of the
+/// given data to be appended, the write is partial.
+impl<S: ChunkStorage> io::Write for ChunkedBytesWith<S> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = min(buf.len(), self.remaining_mut());
+        self.put_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Copies the data from all of `bufs` into the staging buffer,
+    /// reserving enough capacity for them at once.
+    ///
+    /// An error of kind `OutOfMemory` is returned if the allocation fails.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let total = bufs
+            .iter()
+            .fold(0usize, |sum, buf| sum.saturating_add(buf.len()));
+        let total = min(total, self.remaining_mut());
+        self.try_reserve(total)
+            .map_err(|e| io::Error::new(io::ErrorKind::OutOfMemory, e))?;
+        put_vectored(self, bufs, total);
+        Ok(total)
+    }
+
+    /// Does nothing, since the written data are retained in the buffer.
+    /// To split the staging buffer into a complete chunk, use the inherent
+    /// method `ChunkedBytes::flush`.
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: ChunkStorage> fmt::Write for ChunkedBytesWith<S> {
+    /// Appends the string to the buffer, reserving staging capacity
+    /// as needed.
+    ///
+    /// An error is returned if the allocation fails or the string would not
+    /// fit into the maximum length set with `set_max_len`. Nothing is written
+    /// in either case.
     #[inline]
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        if self.remaining_mut() >= s.len() {
-            self.put_slice(s.as_bytes());
-            Ok(())
-        } else {
-            Err(fmt::Error)
+        if !self.inner.fits(s.len()) {
+            return Err(fmt::Error);
         }
+        self.try_reserve(s.len()).map_err(|_| fmt::Error)?;
+        self.put_slice(s.as_bytes());
+        Ok(())
     }
 
     // The default implementation delegates to
@@ -238,3 +1495,34 @@ impl fmt::Write for ChunkedBytes {
         fmt::write(self, args)
     }
 }
+
+/// Shows the layout of the buffer: the configured chunk size, the
+/// lengths of the complete chunks, and the length and capacity of the staging
+/// buffer. The buffered data are not included in the output;
+/// use `hex_dump` to inspect them.
+impl<S: ChunkStorage> fmt::Debug for ChunkedBytesWith<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("ChunkedBytes");
+        d.field("chunk_size_hint", &self.chunk_size_hint());
+        self.inner.debug_fields(&mut d);
+        d.finish()
+    }
+}
+
+/// Formats the buffered data as a contiguous sequence of hexadecimal digits
+/// in lower case. With the alternate flag `#`, the output is prefixed
+/// with `0x`.
+impl<S: ChunkStorage> fmt::LowerHex for ChunkedBytesWith<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        hex::fmt_lower(&self.inner, f)
+    }
+}
+
+/// Formats the buffered data as a contiguous sequence of hexadecimal digits
+/// in upper case. With the alternate flag `#`, the output is prefixed
+/// with `0x`.
+impl<S: ChunkStorage> fmt::UpperHex for ChunkedBytesWith<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        hex::fmt_upper(&self.inner, f)
+    }
+}
@@ -0,0 +1,138 @@
+//! [LZ4 frame] compression into and decompression from `ChunkedBytes`.
+//!
+//! `CompressWriter` compresses the data written through it with
+//! the frame encoder of the `lz4_flex` crate, appending the compressed
+//! frame to `ChunkedBytes`. When the input is fed with `write_buf`, each
+//! chunk of the input is compressed as a block of its own as soon as it is
+//! written, so the latency of the output does not depend on the block size
+//! of the frame; with the default `BlockSize::Auto`, the block size is
+//! chosen to fit the first chunk. This works best when the input is
+//! buffered in chunks of regular size, such as those of a `ChunkedBytes`
+//! with a suitable chunk size hint. The function `decompress_into` decodes
+//! compressed frames from any `Buf`.
+//!
+//! This module is only available with the `lz4` feature.
+//!
+//! [LZ4 frame]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+
+use crate::ChunkedBytes;
+
+use bytes::buf::{BufMut, Writer};
+use bytes::Buf;
+use lz4_flex::frame::{FrameDecoder, FrameEncoder};
+
+pub use lz4_flex::frame::{BlockMode, BlockSize, FrameInfo};
+
+use std::io::{self, Write};
+
+/// A writer compressing the data written through it into `ChunkedBytes`
+/// as an LZ4 frame.
+///
+/// Data written with `io::Write` is accumulated into blocks of the frame's
+/// block size; `flush` compresses the accumulated data as a block
+/// immediately. The end of the frame is written by `finish`, which must
+/// be called to complete the output.
+#[derive(Debug)]
+pub struct CompressWriter<'a> {
+    inner: FrameEncoder<Writer<&'a mut ChunkedBytes>>,
+}
+
+impl<'a> CompressWriter<'a> {
+    /// Creates a writer compressing into `dst` with the default frame
+    /// parameters.
+    #[inline]
+    pub fn new(dst: &'a mut ChunkedBytes) -> Self {
+        Self::with_frame_info(dst, FrameInfo::new())
+    }
+
+    /// Creates a writer compressing into `dst` with the given frame
+    /// parameters.
+    pub fn with_frame_info(dst: &'a mut ChunkedBytes, info: FrameInfo) -> Self {
+        CompressWriter {
+            inner: FrameEncoder::with_frame_info(info, dst.writer()),
+        }
+    }
+
+    /// Consumes the data remaining in `src`, compressing each of its chunks
+    /// as a block, or as several blocks if the chunk is longer than
+    /// the block size.
+    ///
+    /// Any data previously written with `io::Write` and not yet flushed
+    /// is compressed together with the first chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed output does not fit into
+    /// the maximum length of the buffer.
+    pub fn write_buf<B: Buf>(&mut self, mut src: B) -> io::Result<()> {
+        while src.has_remaining() {
+            let chunk = src.chunk();
+            let len = chunk.len();
+            self.inner.write_all(chunk)?;
+            self.inner.flush()?;
+            src.advance(len);
+        }
+        Ok(())
+    }
+
+    /// Compresses the remaining data and completes the frame, appending
+    /// the output to the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed output does not fit into
+    /// the maximum length of the buffer, or if the frame parameters specify
+    /// a content size different from the length of the data written.
+    pub fn finish(self) -> io::Result<()> {
+        self.inner.finish()?;
+        Ok(())
+    }
+}
+
+impl<'a> Write for CompressWriter<'a> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Consumes the data remaining in `src`, appending it to `dst` compressed
+/// as a complete frame with the given parameters, one block per chunk
+/// of `src`.
+///
+/// # Errors
+///
+/// Returns an error if the compressed output does not fit into
+/// the maximum length of `dst`.
+pub fn compress_into<B: Buf>(
+    src: B,
+    dst: &mut ChunkedBytes,
+    info: FrameInfo,
+) -> io::Result<()> {
+    let mut w = CompressWriter::with_frame_info(dst, info);
+    w.write_buf(src)?;
+    w.finish()
+}
+
+/// Consumes the data remaining in `src` as one or more complete LZ4 frames,
+/// appending the decompressed data to `dst`.
+///
+/// # Errors
+///
+/// Returns an error of kind `InvalidData` if the compressed data is corrupt,
+/// an error of kind `UnexpectedEof` if it ends in the middle of a frame,
+/// or an error if the decompressed output does not fit into the maximum
+/// length of `dst`.
+pub fn decompress_into<B: Buf>(
+    src: B,
+    dst: &mut ChunkedBytes,
+) -> io::Result<()> {
+    let mut decoder = FrameDecoder::new(src.reader());
+    io::copy(&mut decoder, &mut dst.writer())?;
+    Ok(())
+}
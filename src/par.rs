@@ -0,0 +1,54 @@
+use rayon::iter::plumbing::{Consumer, ProducerCallback, UnindexedConsumer};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+/// A parallel iterator over the slices of data buffered in
+/// a `ChunkedBytes` container.
+///
+/// The slices are produced in the order of the buffered data, so
+/// order-preserving adaptors such as `collect` or an associative `reduce`
+/// see them as they would appear sequentially.
+///
+/// This type is only available with the `rayon` feature.
+pub struct ParChunks<'a> {
+    inner: rayon::vec::IntoIter<&'a [u8]>,
+}
+
+impl<'a> ParChunks<'a> {
+    pub(crate) fn new(slices: Vec<&'a [u8]>) -> Self {
+        ParChunks {
+            inner: rayon::iter::IntoParallelIterator::into_par_iter(slices),
+        }
+    }
+}
+
+impl<'a> ParallelIterator for ParChunks<'a> {
+    type Item = &'a [u8];
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.inner.drive_unindexed(consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.inner.opt_len()
+    }
+}
+
+impl<'a> IndexedParallelIterator for ParChunks<'a> {
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.inner.drive(consumer)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(
+        self,
+        callback: CB,
+    ) -> CB::Output {
+        self.inner.with_producer(callback)
+    }
+}
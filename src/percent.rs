@@ -0,0 +1,83 @@
+use bytes::BufMut;
+use percent_encoding::{percent_encode, AsciiSet};
+
+use std::cmp::min;
+use std::fmt;
+use std::io;
+
+/// A writer adapter percent-encoding the data written through it.
+///
+/// This value is produced by the `percent_encode` method of `ChunkedBytes`.
+/// The data written with `fmt::Write` or `io::Write` are appended to
+/// the buffer with the bytes in the given `AsciiSet` and all non-ASCII bytes
+/// encoded as `%` followed by two hexadecimal digits, as done by
+/// `percent_encoding::percent_encode`. The runs of bytes that need
+/// no encoding are appended without intermediate copies.
+///
+/// When the encoded data do not fit into the maximum length of the buffer,
+/// `io::Write::write` appends as much of it as it can, not splitting
+/// the encoded bytes, and returns the number of input bytes consumed.
+/// `fmt::Write::write_str` returns an error in this case, with a part of
+/// the string possibly appended.
+///
+/// This type is only available with the `percent-encoding` feature.
+#[derive(Debug)]
+pub struct PercentEncoder<'a, B: BufMut> {
+    buf: &'a mut B,
+    set: &'static AsciiSet,
+}
+
+impl<'a, B: BufMut> PercentEncoder<'a, B> {
+    #[inline]
+    pub(crate) fn new(buf: &'a mut B, set: &'static AsciiSet) -> Self {
+        PercentEncoder { buf, set }
+    }
+
+    // Appends the encoded `src`, returning the number of bytes consumed.
+    fn encode(&mut self, src: &[u8]) -> usize {
+        let src_range = src.as_ptr_range();
+        let mut consumed = 0;
+        for piece in percent_encode(src, self.set) {
+            let piece = piece.as_bytes();
+            if src_range.contains(&piece.as_ptr()) {
+                // A run of bytes passed through
+                let n = min(piece.len(), self.buf.remaining_mut());
+                self.buf.put_slice(&piece[..n]);
+                consumed += n;
+                if n < piece.len() {
+                    break;
+                }
+            } else {
+                if self.buf.remaining_mut() < piece.len() {
+                    break;
+                }
+                self.buf.put_slice(piece);
+                consumed += 1;
+            }
+        }
+        consumed
+    }
+}
+
+impl<'a, B: BufMut> fmt::Write for PercentEncoder<'a, B> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.encode(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+impl<'a, B: BufMut> io::Write for PercentEncoder<'a, B> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.encode(buf))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use crate::ChunkedBytes;
+
+use bytes::{Buf, BufMut, Bytes};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::cmp::min;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// Creates an in-process byte pipe backed by `ChunkedBytes`.
+///
+/// The data written into the `PipeWriter` half is buffered in a
+/// `ChunkedBytes` container with the preferred chunk size of `chunk_size`,
+/// and can be read from the `PipeReader` half. Writing is suspended while
+/// `max_buffered` or more bytes are buffered in the pipe, until the reader
+/// consumes some of the data.
+///
+/// Unlike `tokio::io::duplex`, the pipe preserves the `Bytes` slices written
+/// with `PipeWriter::write_bytes`: they are passed to the reader without
+/// copying, and the reader can receive them intact with `poll_recv_chunk`,
+/// or with its implementation of `Stream` when the `futures-core` feature
+/// is enabled.
+///
+/// This function is only available with the `tokio` feature.
+///
+/// # Panics
+///
+/// This function panics if `max_buffered` is 0.
+pub fn pipe(
+    chunk_size: usize,
+    max_buffered: usize,
+) -> (PipeWriter, PipeReader) {
+    assert!(max_buffered != 0, "the pipe must be able to buffer data");
+    let shared = Arc::new(Mutex::new(Shared {
+        buf: ChunkedBytes::with_chunk_size_hint(chunk_size),
+        max_buffered,
+        read_waker: None,
+        write_waker: None,
+        writer_closed: false,
+        reader_closed: false,
+    }));
+    (
+        PipeWriter {
+            shared: shared.clone(),
+        },
+        PipeReader { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared {
+    buf: ChunkedBytes,
+    max_buffered: usize,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+    writer_closed: bool,
+    reader_closed: bool,
+}
+
+impl Shared {
+    fn wake_reader(&mut self) {
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_writer(&mut self) {
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_write_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.reader_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if self.buf.remaining() >= self.max_buffered {
+            self.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    // Returns `Poll::Ready(true)` if there is data to read,
+    // or `Poll::Ready(false)` at end of input.
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        if self.buf.has_remaining() {
+            Poll::Ready(true)
+        } else if self.writer_closed {
+            Poll::Ready(false)
+        } else {
+            self.read_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().expect("pipe state mutex poisoned")
+}
+
+/// The writing half of a pipe created with `pipe`.
+///
+/// Dropping the writer closes the pipe for writing: the reader receives
+/// end of input after consuming the buffered data.
+#[derive(Debug)]
+pub struct PipeWriter {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl PipeWriter {
+    /// Polls for the pipe to have room for more data.
+    ///
+    /// An error of kind `ErrorKind::BrokenPipe` is returned if the reader
+    /// has been dropped.
+    pub fn poll_write_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        lock(&self.shared).poll_write_ready(cx)
+    }
+
+    /// Passes a `Bytes` slice to the reader without copying.
+    ///
+    /// This waits until the pipe has room for more data, then appends
+    /// the whole slice to the buffer, which may exceed the limit on
+    /// buffered data set when the pipe was created.
+    ///
+    /// An error of kind `ErrorKind::BrokenPipe` is returned if the reader
+    /// has been dropped.
+    pub async fn write_bytes(&mut self, src: Bytes) -> io::Result<()> {
+        poll_fn(|cx| self.poll_write_ready(cx)).await?;
+        let mut shared = lock(&self.shared);
+        shared.buf.put_bytes(src);
+        shared.wake_reader();
+        Ok(())
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.writer_closed = true;
+            shared.wake_reader();
+        }
+    }
+}
+
+impl AsyncWrite for PipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        src: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut shared = lock(&self.shared);
+        match shared.poll_write_ready(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+        let len = min(src.len(), shared.max_buffered - shared.buf.remaining());
+        shared.buf.put_slice(&src[..len]);
+        shared.wake_reader();
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut shared = lock(&self.shared);
+        shared.writer_closed = true;
+        shared.wake_reader();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The reading half of a pipe created with `pipe`.
+///
+/// Dropping the reader closes the pipe: further writes fail with
+/// `ErrorKind::BrokenPipe`.
+#[derive(Debug)]
+pub struct PipeReader {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl PipeReader {
+    /// Polls for the next chunk of data in the pipe, returning it
+    /// without copying. The `Bytes` slices written with
+    /// `PipeWriter::write_bytes` are received intact.
+    ///
+    /// Returns `Poll::Ready(None)` when the writer has been closed
+    /// and all the data has been read.
+    pub fn poll_recv_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Bytes>> {
+        let mut shared = lock(&self.shared);
+        match shared.poll_read_ready(cx) {
+            Poll::Ready(true) => {}
+            Poll::Ready(false) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        }
+        let len = shared.buf.chunk().len();
+        let chunk = shared.buf.copy_to_bytes(len);
+        shared.wake_writer();
+        Poll::Ready(Some(chunk))
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.reader_closed = true;
+            shared.wake_writer();
+        }
+    }
+}
+
+impl AsyncRead for PipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        dst: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut shared = lock(&self.shared);
+        match shared.poll_read_ready(cx) {
+            Poll::Ready(true) => {}
+            Poll::Ready(false) => return Poll::Ready(Ok(())),
+            Poll::Pending => return Poll::Pending,
+        }
+        let res = Pin::new(&mut shared.buf).poll_read(cx, dst);
+        shared.wake_writer();
+        res
+    }
+}
+
+#[cfg(feature = "futures-core")]
+impl futures_core::Stream for PipeReader {
+    type Item = Bytes;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Bytes>> {
+        self.get_mut().poll_recv_chunk(cx)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{loosely, strictly};
+
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Container types that can be recycled by `ChunkedBytesPool`.
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait Recycle: private::Sealed + Sized + 'static {
+    #[doc(hidden)]
+    fn recycle(self, fresh: Self) -> Self;
+}
+
+impl private::Sealed for loosely::ChunkedBytes {}
+
+impl Recycle for loosely::ChunkedBytes {
+    fn recycle(mut self, mut fresh: Self) -> Self {
+        self.clear();
+        fresh.reuse_allocations(&mut self);
+        fresh
+    }
+}
+
+impl private::Sealed for strictly::ChunkedBytes {}
+
+impl Recycle for strictly::ChunkedBytes {
+    fn recycle(mut self, mut fresh: Self) -> Self {
+        self.clear();
+        fresh.reuse_allocations(&mut self);
+        fresh
+    }
+}
+
+type Factory<B> = dyn Fn() -> B + Send + Sync;
+
+/// A pool of reusable `ChunkedBytes` containers.
+///
+/// The containers handed out by `get` are returned to the pool when
+/// the `Pooled` guard is dropped. A returned container is cleared and
+/// its settings are reset to those of a container newly made by the
+/// pool's factory, but it retains its staging buffer allocation and the
+/// capacity of its chunk queue, so the next user does not need
+/// to allocate them anew.
+/// Up to the configured number of idle containers are kept in the pool;
+/// any excess containers are dropped when returned.
+///
+/// The pool can be shared between threads.
+pub struct ChunkedBytesPool<B = loosely::ChunkedBytes> {
+    idle: Mutex<Vec<B>>,
+    max_idle: usize,
+    factory: Box<Factory<B>>,
+}
+
+impl<B: Recycle + Default> ChunkedBytesPool<B> {
+    /// Creates a pool that keeps up to `max_idle` idle containers,
+    /// creating new containers with default settings when none are idle.
+    pub fn new(max_idle: usize) -> Self {
+        Self::with_factory(max_idle, B::default)
+    }
+}
+
+impl<B: Recycle> ChunkedBytesPool<B> {
+    /// Creates a pool that keeps up to `max_idle` idle containers,
+    /// creating new containers with `factory` when none are idle.
+    ///
+    /// The factory is also called when a container is returned to
+    /// the pool, to reset the settings of the returned container.
+    pub fn with_factory<F>(max_idle: usize, factory: F) -> Self
+    where
+        F: Fn() -> B + Send + Sync + 'static,
+    {
+        ChunkedBytesPool {
+            idle: Mutex::new(Vec::with_capacity(max_idle)),
+            max_idle,
+            factory: Box::new(factory),
+        }
+    }
+
+    /// Takes an idle container from the pool, or creates a new one.
+    pub fn get(&self) -> Pooled<'_, B> {
+        let buf = self.lock().pop().unwrap_or_else(|| (self.factory)());
+        Pooled {
+            buf: ManuallyDrop::new(buf),
+            pool: self,
+        }
+    }
+
+    /// Returns the number of idle containers in the pool.
+    pub fn idle_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn put(&self, buf: B) {
+        if self.lock().len() >= self.max_idle {
+            return;
+        }
+        let buf = buf.recycle((self.factory)());
+        let mut idle = self.lock();
+        if idle.len() < self.max_idle {
+            idle.push(buf);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<B>> {
+        self.idle.lock().expect("pool mutex poisoned")
+    }
+}
+
+impl<B> fmt::Debug for ChunkedBytesPool<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkedBytesPool")
+            .field("max_idle", &self.max_idle)
+            .finish()
+    }
+}
+
+/// A container taken from `ChunkedBytesPool`, returned to the pool
+/// when dropped.
+pub struct Pooled<'a, B: Recycle> {
+    buf: ManuallyDrop<B>,
+    pool: &'a ChunkedBytesPool<B>,
+}
+
+impl<'a, B: Recycle> Pooled<'a, B> {
+    /// Detaches the container from the pool, so that it's not returned
+    /// to the pool when dropped.
+    pub fn detach(self) -> B {
+        let mut this = ManuallyDrop::new(self);
+        // Safety: the guard is not dropped, so the container is only
+        // taken out once.
+        unsafe { ManuallyDrop::take(&mut this.buf) }
+    }
+}
+
+impl<'a, B: Recycle> Deref for Pooled<'a, B> {
+    type Target = B;
+
+    #[inline]
+    fn deref(&self) -> &B {
+        &self.buf
+    }
+}
+
+impl<'a, B: Recycle> DerefMut for Pooled<'a, B> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut B {
+        &mut self.buf
+    }
+}
+
+impl<'a, B: Recycle> Drop for Pooled<'a, B> {
+    fn drop(&mut self) {
+        // Safety: the container is not used after being taken here.
+        let buf = unsafe { ManuallyDrop::take(&mut self.buf) };
+        self.pool.put(buf);
+    }
+}
+
+impl<'a, B: Recycle + fmt::Debug> fmt::Debug for Pooled<'a, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.buf, f)
+    }
+}
@@ -0,0 +1,29 @@
+use bytes::BufMut;
+use postcard::ser_flavors::Flavor;
+
+// A postcard serialization flavor writing into a buffer, failing
+// when the buffer cannot take more bytes.
+pub(crate) struct PutFlavor<'a, B>(pub &'a mut B);
+
+impl<'a, B: BufMut> Flavor for PutFlavor<'a, B> {
+    type Output = ();
+
+    #[inline]
+    fn try_extend(&mut self, data: &[u8]) -> postcard::Result<()> {
+        if self.0.remaining_mut() < data.len() {
+            return Err(postcard::Error::SerializeBufferFull);
+        }
+        self.0.put_slice(data);
+        Ok(())
+    }
+
+    #[inline]
+    fn try_push(&mut self, data: u8) -> postcard::Result<()> {
+        self.try_extend(&[data])
+    }
+
+    #[inline]
+    fn finalize(self) -> postcard::Result<()> {
+        Ok(())
+    }
+}
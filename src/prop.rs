@@ -0,0 +1,151 @@
+//! Strategies for property testing with `proptest`.
+//!
+//! This module is only available with the `proptest` feature.
+//! The strategies generate `ChunkedBytes` containers holding either given
+//! or arbitrary contents, laid out in chunks and the staging buffer
+//! in all the ways the containers can produce: the chunk size is varied,
+//! the data is appended in pieces copied into the staging buffer or
+//! appended as `Bytes` slices, with occasional flushes, and a part of the
+//! staging buffer may have been consumed before the contents were written.
+
+use crate::{loosely, strictly};
+
+use bytes::{Buf, BufMut, Bytes};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use std::cmp::min;
+
+const MAX_CHUNK_SIZE: usize = 64;
+const MAX_PIECES: usize = 16;
+
+trait Build: Buf + BufMut + Sized {
+    fn with_chunk_size(chunk_size: usize) -> Self;
+    fn put_chunk(&mut self, src: Bytes);
+    fn flush(&mut self);
+}
+
+impl Build for loosely::ChunkedBytes {
+    fn with_chunk_size(chunk_size: usize) -> Self {
+        Self::with_chunk_size_hint(chunk_size)
+    }
+
+    fn put_chunk(&mut self, src: Bytes) {
+        self.put_bytes(src)
+    }
+
+    fn flush(&mut self) {
+        self.flush()
+    }
+}
+
+impl Build for strictly::ChunkedBytes {
+    fn with_chunk_size(chunk_size: usize) -> Self {
+        Self::with_chunk_size_limit(chunk_size)
+    }
+
+    fn put_chunk(&mut self, src: Bytes) {
+        self.put_bytes(src)
+    }
+
+    fn flush(&mut self) {
+        self.flush()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Piece {
+    Slice(usize),
+    Bytes(usize),
+    SliceAndFlush(usize),
+}
+
+fn piece() -> impl Strategy<Value = Piece> {
+    prop_oneof![
+        (1..=MAX_CHUNK_SIZE).prop_map(Piece::Slice),
+        (1..=MAX_CHUNK_SIZE).prop_map(Piece::Bytes),
+        (1..=MAX_CHUNK_SIZE).prop_map(Piece::SliceAndFlush),
+    ]
+}
+
+fn build<B: Build>(
+    contents: &[u8],
+    chunk_size: usize,
+    consumed: usize,
+    pieces: &[Piece],
+) -> B {
+    let mut buf = B::with_chunk_size(chunk_size);
+    buf.put_chunk(Bytes::from(vec![0; consumed]));
+    buf.put_slice(&vec![0; consumed][..]);
+    buf.advance(consumed * 2);
+    let mut rest = contents;
+    for &piece in pieces {
+        if rest.is_empty() {
+            break;
+        }
+        match piece {
+            Piece::Slice(len) => {
+                let len = min(len, rest.len());
+                buf.put_slice(&rest[..len]);
+                rest = &rest[len..];
+            }
+            Piece::Bytes(len) => {
+                let len = min(len, rest.len());
+                buf.put_chunk(Bytes::copy_from_slice(&rest[..len]));
+                rest = &rest[len..];
+            }
+            Piece::SliceAndFlush(len) => {
+                let len = min(len, rest.len());
+                buf.put_slice(&rest[..len]);
+                buf.flush();
+                rest = &rest[len..];
+            }
+        }
+    }
+    buf.put_slice(rest);
+    buf
+}
+
+fn with_contents<B>(contents: Vec<u8>) -> impl Strategy<Value = B>
+where
+    B: Build + std::fmt::Debug,
+{
+    (
+        1..=MAX_CHUNK_SIZE,
+        0..MAX_CHUNK_SIZE,
+        vec(piece(), 0..=MAX_PIECES),
+    )
+        .prop_map(move |(chunk_size, consumed, pieces)| {
+            build(&contents, chunk_size, consumed, &pieces)
+        })
+}
+
+/// Generates `loosely::ChunkedBytes` containers holding `contents`
+/// in arbitrary layouts.
+pub fn loosely_with_contents(
+    contents: Vec<u8>,
+) -> impl Strategy<Value = loosely::ChunkedBytes> {
+    with_contents(contents)
+}
+
+/// Generates `strictly::ChunkedBytes` containers holding `contents`
+/// in arbitrary layouts.
+pub fn strictly_with_contents(
+    contents: Vec<u8>,
+) -> impl Strategy<Value = strictly::ChunkedBytes> {
+    with_contents(contents)
+}
+
+/// Generates `loosely::ChunkedBytes` containers with arbitrary contents
+/// and layouts.
+pub fn any_loosely() -> impl Strategy<Value = loosely::ChunkedBytes> {
+    vec(any::<u8>(), 0..MAX_CHUNK_SIZE * MAX_PIECES)
+        .prop_flat_map(loosely_with_contents)
+}
+
+/// Generates `strictly::ChunkedBytes` containers with arbitrary contents
+/// and layouts.
+pub fn any_strictly() -> impl Strategy<Value = strictly::ChunkedBytes> {
+    vec(any::<u8>(), 0..MAX_CHUNK_SIZE * MAX_PIECES)
+        .prop_flat_map(strictly_with_contents)
+}
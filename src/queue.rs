@@ -0,0 +1,493 @@
+use crate::ChunkStorage;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use std::collections::{vec_deque, TryReserveError, VecDeque};
+use std::iter::{FromIterator, FusedIterator};
+use std::ops::Deref;
+use std::{array, mem, slice};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+// The number of chunks held without a heap allocation.
+const INLINE_CAP: usize = 3;
+
+type InlineChunks<S> = [Option<Chunk<S>>; INLINE_CAP];
+
+// A complete chunk in the queue. The blocks split off the staging buffer
+// are kept as `BytesMut` until they need to be handed out as `Bytes`,
+// so they can be appended to without reallocating a shared buffer.
+pub(crate) enum Chunk<S> {
+    Owned(BytesMut),
+    Shared(Bytes),
+    Storage(S),
+}
+
+impl<S: ChunkStorage> Chunk<S> {
+    // Converts the chunk into `Bytes`, which may copy the data of
+    // a storage chunk.
+    #[inline]
+    pub fn into_bytes(self) -> Bytes {
+        match self {
+            Chunk::Owned(buf) => buf.freeze(),
+            Chunk::Shared(bytes) => bytes,
+            Chunk::Storage(storage) => storage.into_bytes(),
+        }
+    }
+
+    #[inline]
+    pub fn advance(&mut self, cnt: usize) {
+        match self {
+            Chunk::Owned(buf) => buf.advance(cnt),
+            Chunk::Shared(bytes) => Buf::advance(bytes, cnt),
+            Chunk::Storage(storage) => storage.advance(cnt),
+        }
+    }
+
+    // Zeroes the first `len` bytes of an owned chunk. Shared and storage
+    // chunks are left as they are, since they may have other owners.
+    #[cfg(feature = "zeroize")]
+    #[inline]
+    pub fn erase(&mut self, len: usize) {
+        if let Chunk::Owned(buf) = self {
+            buf[..len].zeroize();
+        }
+    }
+
+    // Splits off the first `at` bytes as a `Bytes` slice.
+    #[inline]
+    pub fn split_to(&mut self, at: usize) -> Bytes {
+        match self {
+            Chunk::Owned(buf) => buf.split_to(at).freeze(),
+            Chunk::Shared(bytes) => bytes.split_to(at),
+            Chunk::Storage(_) => self.split_chunk_to(at).into_bytes(),
+        }
+    }
+
+    // Splits off the first `at` bytes as a chunk sharing the storage.
+    pub fn split_chunk_to(&mut self, at: usize) -> Chunk<S> {
+        match self {
+            Chunk::Owned(buf) => Chunk::Owned(buf.split_to(at)),
+            Chunk::Shared(bytes) => Chunk::Shared(bytes.split_to(at)),
+            Chunk::Storage(storage) => Chunk::Storage(storage.split_to(at)),
+        }
+    }
+
+    // Appends a copy of `src`, converting a shared chunk into
+    // an owned one.
+    pub fn extend_from_slice(&mut self, src: &[u8]) {
+        if let Chunk::Owned(buf) = self {
+            buf.extend_from_slice(src);
+            return;
+        }
+        let mut buf = BytesMut::with_capacity(self.len() + src.len());
+        buf.extend_from_slice(self);
+        buf.extend_from_slice(src);
+        *self = Chunk::Owned(buf);
+    }
+
+    // Returns the mutable data of the chunk, converting a shared or
+    // storage chunk into an owned copy.
+    pub fn make_mut(&mut self) -> &mut [u8] {
+        if !matches!(self, Chunk::Owned(_)) {
+            *self = Chunk::Owned(BytesMut::from(&self[..]));
+        }
+        match self {
+            Chunk::Owned(buf) => buf,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<S: ChunkStorage> Deref for Chunk<S> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        match self {
+            Chunk::Owned(buf) => buf,
+            Chunk::Shared(bytes) => bytes,
+            Chunk::Storage(storage) => storage.as_slice(),
+        }
+    }
+}
+
+impl<S> From<Bytes> for Chunk<S> {
+    #[inline]
+    fn from(bytes: Bytes) -> Self {
+        Chunk::Shared(bytes)
+    }
+}
+
+impl<S> From<BytesMut> for Chunk<S> {
+    #[inline]
+    fn from(buf: BytesMut) -> Self {
+        Chunk::Owned(buf)
+    }
+}
+
+// A queue of chunks that holds up to `INLINE_CAP` chunks inline,
+// spilling to a `VecDeque` when more are pushed.
+pub(crate) enum ChunkQueue<S> {
+    // The first `len` entries are occupied.
+    Inline(InlineChunks<S>, usize),
+    Heap(VecDeque<Chunk<S>>),
+}
+
+impl<S> Default for ChunkQueue<S> {
+    #[inline]
+    fn default() -> Self {
+        ChunkQueue::Inline(Default::default(), 0)
+    }
+}
+
+impl<S: ChunkStorage> ChunkQueue<S> {
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity > INLINE_CAP {
+            ChunkQueue::Heap(VecDeque::with_capacity(capacity))
+        } else {
+            ChunkQueue::default()
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            ChunkQueue::Inline(_, len) => *len,
+            ChunkQueue::Heap(chunks) => chunks.len(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn front(&self) -> Option<&Chunk<S>> {
+        match self {
+            ChunkQueue::Inline(chunks, _) => chunks[0].as_ref(),
+            ChunkQueue::Heap(chunks) => chunks.front(),
+        }
+    }
+
+    #[inline]
+    pub fn front_mut(&mut self) -> Option<&mut Chunk<S>> {
+        match self {
+            ChunkQueue::Inline(chunks, _) => chunks[0].as_mut(),
+            ChunkQueue::Heap(chunks) => chunks.front_mut(),
+        }
+    }
+
+    #[inline]
+    pub fn back_mut(&mut self) -> Option<&mut Chunk<S>> {
+        match self {
+            ChunkQueue::Inline(_, 0) => None,
+            ChunkQueue::Inline(chunks, len) => chunks[*len - 1].as_mut(),
+            ChunkQueue::Heap(chunks) => chunks.back_mut(),
+        }
+    }
+
+    #[inline]
+    pub fn push_back(&mut self, chunk: Chunk<S>) {
+        match self {
+            ChunkQueue::Inline(chunks, len) if *len < INLINE_CAP => {
+                chunks[*len] = Some(chunk);
+                *len += 1;
+            }
+            ChunkQueue::Inline(..) => {
+                self.spill(VecDeque::new()).push_back(chunk);
+            }
+            ChunkQueue::Heap(chunks) => chunks.push_back(chunk),
+        }
+    }
+
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<Chunk<S>> {
+        match self {
+            ChunkQueue::Inline(_, 0) => None,
+            ChunkQueue::Inline(chunks, len) => {
+                let chunk = chunks[0].take();
+                chunks[..*len].rotate_left(1);
+                *len -= 1;
+                chunk
+            }
+            ChunkQueue::Heap(chunks) => chunks.pop_front(),
+        }
+    }
+
+    // Drops the first `n` chunks.
+    pub fn discard_front(&mut self, n: usize) {
+        match self {
+            ChunkQueue::Inline(chunks, len) => {
+                debug_assert!(n <= *len);
+                for chunk in &mut chunks[..n] {
+                    *chunk = None;
+                }
+                chunks[..*len].rotate_left(n);
+                *len -= n;
+            }
+            ChunkQueue::Heap(chunks) => {
+                chunks.drain(..n);
+            }
+        }
+    }
+
+    pub fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        match self {
+            ChunkQueue::Inline(_, len) if *len + additional <= INLINE_CAP => {
+                Ok(())
+            }
+            ChunkQueue::Inline(_, len) => {
+                let mut heap = VecDeque::new();
+                heap.try_reserve(*len + additional)?;
+                self.spill(heap);
+                Ok(())
+            }
+            ChunkQueue::Heap(chunks) => chunks.try_reserve(additional),
+        }
+    }
+
+    // Moves the inline chunks into `heap`, returning a reference to it
+    // as the new storage of the queue.
+    fn spill(
+        &mut self,
+        mut heap: VecDeque<Chunk<S>>,
+    ) -> &mut VecDeque<Chunk<S>> {
+        if let ChunkQueue::Inline(chunks, len) = self {
+            heap.extend(chunks[..*len].iter_mut().map(take_chunk));
+        }
+        *self = ChunkQueue::Heap(heap);
+        match self {
+            ChunkQueue::Heap(chunks) => chunks,
+            ChunkQueue::Inline(..) => unreachable!(),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, S> {
+        match self {
+            ChunkQueue::Inline(chunks, len) => {
+                Iter::Inline(chunks[..*len].iter())
+            }
+            ChunkQueue::Heap(chunks) => Iter::Heap(chunks.iter()),
+        }
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, S> {
+        match self {
+            ChunkQueue::Inline(chunks, len) => {
+                IterMut::Inline(chunks[..*len].iter_mut())
+            }
+            ChunkQueue::Heap(chunks) => IterMut::Heap(chunks.iter_mut()),
+        }
+    }
+
+    // Removes all chunks, returning an iterator over them
+    // converted to `Bytes`.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, S> {
+        match self {
+            ChunkQueue::Inline(chunks, len) => {
+                let chunks = mem::take(chunks);
+                let len = mem::replace(len, 0);
+                Drain::Inline(into_inline_iter(chunks, len))
+            }
+            ChunkQueue::Heap(chunks) => Drain::Heap(chunks.drain(..)),
+        }
+    }
+
+    // Removes all chunks, returning an iterator over them as they are.
+    #[inline]
+    pub fn drain_chunks(&mut self) -> ChunkDrain<'_, S> {
+        match self {
+            ChunkQueue::Inline(chunks, len) => {
+                let chunks = mem::take(chunks);
+                let len = mem::replace(len, 0);
+                ChunkDrain::Inline(into_inline_iter(chunks, len))
+            }
+            ChunkQueue::Heap(chunks) => ChunkDrain::Heap(chunks.drain(..)),
+        }
+    }
+}
+
+fn take_chunk<S>(chunk: &mut Option<Chunk<S>>) -> Chunk<S> {
+    chunk.take().expect("inline chunk must be occupied")
+}
+
+fn unwrap_chunk<S>(chunk: Option<Chunk<S>>) -> Chunk<S> {
+    chunk.expect("inline chunk must be occupied")
+}
+
+type InlineIntoIter<S> = std::iter::Map<
+    std::iter::Take<array::IntoIter<Option<Chunk<S>>, INLINE_CAP>>,
+    fn(Option<Chunk<S>>) -> Chunk<S>,
+>;
+
+fn into_inline_iter<S>(
+    chunks: InlineChunks<S>,
+    len: usize,
+) -> InlineIntoIter<S> {
+    IntoIterator::into_iter(chunks)
+        .take(len)
+        .map(unwrap_chunk as fn(Option<Chunk<S>>) -> Chunk<S>)
+}
+
+impl<S: ChunkStorage> FromIterator<Bytes> for ChunkQueue<S> {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        let mut queue = ChunkQueue::default();
+        for chunk in iter {
+            queue.push_back(chunk.into());
+        }
+        queue
+    }
+}
+
+impl<S: ChunkStorage> IntoIterator for ChunkQueue<S> {
+    type Item = Bytes;
+    type IntoIter = IntoIter<S>;
+
+    #[inline]
+    fn into_iter(self) -> IntoIter<S> {
+        match self {
+            ChunkQueue::Inline(chunks, len) => {
+                IntoIter::Inline(into_inline_iter(chunks, len))
+            }
+            ChunkQueue::Heap(chunks) => IntoIter::Heap(chunks.into_iter()),
+        }
+    }
+}
+
+pub(crate) enum Iter<'a, S> {
+    Inline(slice::Iter<'a, Option<Chunk<S>>>),
+    Heap(vec_deque::Iter<'a, Chunk<S>>),
+}
+
+impl<'a, S> Iterator for Iter<'a, S> {
+    type Item = &'a Chunk<S>;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a Chunk<S>> {
+        match self {
+            Iter::Inline(iter) => iter.next().map(|chunk| {
+                chunk.as_ref().expect("inline chunk must be occupied")
+            }),
+            Iter::Heap(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Iter::Inline(iter) => iter.size_hint(),
+            Iter::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, S> ExactSizeIterator for Iter<'a, S> {}
+impl<'a, S> FusedIterator for Iter<'a, S> {}
+
+pub(crate) enum IterMut<'a, S> {
+    Inline(slice::IterMut<'a, Option<Chunk<S>>>),
+    Heap(vec_deque::IterMut<'a, Chunk<S>>),
+}
+
+impl<'a, S> Iterator for IterMut<'a, S> {
+    type Item = &'a mut Chunk<S>;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a mut Chunk<S>> {
+        match self {
+            IterMut::Inline(iter) => iter.next().map(|chunk| {
+                chunk.as_mut().expect("inline chunk must be occupied")
+            }),
+            IterMut::Heap(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IterMut::Inline(iter) => iter.size_hint(),
+            IterMut::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, S> ExactSizeIterator for IterMut<'a, S> {}
+impl<'a, S> FusedIterator for IterMut<'a, S> {}
+
+pub(crate) enum ChunkDrain<'a, S> {
+    Inline(InlineIntoIter<S>),
+    Heap(vec_deque::Drain<'a, Chunk<S>>),
+}
+
+impl<'a, S> Iterator for ChunkDrain<'a, S> {
+    type Item = Chunk<S>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Chunk<S>> {
+        match self {
+            ChunkDrain::Inline(iter) => iter.next(),
+            ChunkDrain::Heap(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            ChunkDrain::Inline(iter) => iter.size_hint(),
+            ChunkDrain::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, S> ExactSizeIterator for ChunkDrain<'a, S> {}
+impl<'a, S> FusedIterator for ChunkDrain<'a, S> {}
+
+// Generates an owning iterator type over the inline chunks or
+// a `VecDeque` iterator, yielding the chunks as `Bytes`.
+macro_rules! owning_iter {
+    ($name:ident<$($lt:lifetime,)? S>, $heap:ty) => {
+        pub(crate) enum $name<$($lt,)? S> {
+            Inline(InlineIntoIter<S>),
+            Heap($heap),
+        }
+
+        impl<$($lt,)? S: ChunkStorage> Iterator for $name<$($lt,)? S> {
+            type Item = Bytes;
+
+            #[inline]
+            fn next(&mut self) -> Option<Bytes> {
+                match self {
+                    $name::Inline(iter) => iter.next(),
+                    $name::Heap(iter) => iter.next(),
+                }
+                .map(Chunk::into_bytes)
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                match self {
+                    $name::Inline(iter) => iter.size_hint(),
+                    $name::Heap(iter) => iter.size_hint(),
+                }
+            }
+        }
+
+        impl<$($lt,)? S: ChunkStorage> ExactSizeIterator
+            for $name<$($lt,)? S>
+        {
+        }
+        impl<$($lt,)? S: ChunkStorage> FusedIterator for $name<$($lt,)? S> {}
+    };
+}
+
+owning_iter!(Drain<'a, S>, vec_deque::Drain<'a, Chunk<S>>);
+owning_iter!(IntoIter<S>, vec_deque::IntoIter<Chunk<S>>);
@@ -0,0 +1,710 @@
+use crate::{loosely, strictly, ChunkStorage};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use std::cmp::{max, min};
+use std::fmt;
+use std::io::IoSlice;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, OnceLock};
+
+// Adjacent leaves with at most this many chunks between them are merged
+// into one leaf when ropes are concatenated.
+const MAX_MERGED_CHUNKS: usize = 32;
+
+#[cfg(test)]
+thread_local! {
+    // The number of concatenation nodes allocated on the current thread
+    pub(crate) static CONCAT_NODES: std::cell::Cell<usize> =
+        const { std::cell::Cell::new(0) };
+}
+
+/// An immutable, cheaply clonable sequence of chunks.
+///
+/// `ChunkedRope` is produced by the `freeze` method of either variant of
+/// `ChunkedBytes`, or with `From`. The chunks are shared between clones,
+/// so cloning a rope takes constant time regardless of the amount of data,
+/// and two ropes can be concatenated with `concat` without copying their
+/// chunks.
+///
+/// `ChunkedRope` implements `Buf`, consuming the data of the particular
+/// rope value. To read the same data repeatedly, such as when sending
+/// a message to many recipients, clone the rope for each reader.
+#[derive(Clone)]
+pub struct ChunkedRope {
+    root: Node,
+    // The reading position in the data of `root`
+    pos: usize,
+    // The leaf containing the reading position and its offset in
+    // the data of `root`, valid while any data remain
+    leaf: Arc<Leaf>,
+    leaf_start: usize,
+    // The index of the chunk in `leaf` containing the reading position
+    index: usize,
+}
+
+#[derive(Clone)]
+enum Node {
+    Leaf(Arc<Leaf>),
+    Concat(Arc<Concat>),
+}
+
+#[derive(Default)]
+struct Leaf {
+    chunks: Box<[Bytes]>,
+    // The offset of the end of each chunk from the start of the leaf
+    ends: Box<[usize]>,
+}
+
+struct Concat {
+    left: Node,
+    right: Node,
+    len: usize,
+    depth: usize,
+}
+
+impl Leaf {
+    #[inline]
+    fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+}
+
+// Returns a shared empty leaf, which does not need to be allocated
+// for every empty rope.
+fn empty_leaf() -> Arc<Leaf> {
+    static EMPTY: OnceLock<Arc<Leaf>> = OnceLock::new();
+    EMPTY.get_or_init(Default::default).clone()
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node::Leaf(empty_leaf())
+    }
+}
+
+impl Drop for Concat {
+    fn drop(&mut self) {
+        // Dismantle the subtrees that are not shared with other ropes
+        // in a loop, so that dropping a deep tree does not recurse.
+        let mut stack = Vec::new();
+        for node in [&mut self.left, &mut self.right] {
+            if let Node::Concat(_) = node {
+                stack.push(mem::take(node));
+            }
+        }
+        while let Some(node) = stack.pop() {
+            if let Node::Concat(concat) = node {
+                if let Ok(mut concat) = Arc::try_unwrap(concat) {
+                    stack.push(mem::take(&mut concat.left));
+                    stack.push(mem::take(&mut concat.right));
+                }
+            }
+        }
+    }
+}
+
+impl Node {
+    fn from_chunks(chunks: Vec<Bytes>) -> Self {
+        let mut len = 0;
+        let ends = chunks
+            .iter()
+            .map(|chunk| {
+                len += chunk.len();
+                len
+            })
+            .collect();
+        Node::Leaf(Arc::new(Leaf {
+            chunks: chunks.into(),
+            ends,
+        }))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.len(),
+            Node::Concat(concat) => concat.len,
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Concat(concat) => concat.depth,
+        }
+    }
+
+    // Concatenates two non-empty trees, keeping the depths of the two
+    // subtrees of every node within one of each other, as in an AVL tree.
+    // Only the nodes along the edge of the deeper tree down to the depth
+    // of the other one are rebuilt, which takes time logarithmic in the
+    // number of leaves. Two small leaves meeting at the join are merged.
+    fn concat(left: Node, right: Node) -> Node {
+        match (&left, &right) {
+            (Node::Concat(l), _) if l.depth > right.depth() + 1 => {
+                Node::concat_right(l, right)
+            }
+            (_, Node::Concat(r)) if r.depth > left.depth() + 1 => {
+                Node::concat_left(left, r)
+            }
+            _ => Node::pair_or_merge(left, right),
+        }
+    }
+
+    // Joins `right` onto the right edge of `left`, which is deeper
+    // by more than one level.
+    fn concat_right(left: &Concat, right: Node) -> Node {
+        let edge = match &left.right {
+            Node::Concat(c) if c.depth > right.depth() + 1 => {
+                Node::concat_right(c, right)
+            }
+            node => Node::pair_or_merge(node.clone(), right),
+        };
+        if edge.depth() <= left.left.depth() + 1 {
+            Node::pair(left.left.clone(), edge)
+        } else {
+            Node::rotate_left(left.left.clone(), edge.as_concat())
+        }
+    }
+
+    // Joins `left` onto the left edge of `right`, which is deeper
+    // by more than one level.
+    fn concat_left(left: Node, right: &Concat) -> Node {
+        let edge = match &right.left {
+            Node::Concat(c) if c.depth > left.depth() + 1 => {
+                Node::concat_left(left, c)
+            }
+            node => Node::pair_or_merge(left, node.clone()),
+        };
+        if edge.depth() <= right.right.depth() + 1 {
+            Node::pair(edge, right.right.clone())
+        } else {
+            Node::rotate_right(edge.as_concat(), right.right.clone())
+        }
+    }
+
+    // Balances a pair where `right` is deeper than `left` by two levels.
+    fn rotate_left(left: Node, right: &Concat) -> Node {
+        if right.right.depth() >= right.left.depth() {
+            Node::pair(
+                Node::pair(left, right.left.clone()),
+                right.right.clone(),
+            )
+        } else {
+            let inner = right.left.as_concat();
+            Node::pair(
+                Node::pair(left, inner.left.clone()),
+                Node::pair(inner.right.clone(), right.right.clone()),
+            )
+        }
+    }
+
+    // Balances a pair where `left` is deeper than `right` by two levels.
+    fn rotate_right(left: &Concat, right: Node) -> Node {
+        if left.left.depth() >= left.right.depth() {
+            Node::pair(left.left.clone(), Node::pair(left.right.clone(), right))
+        } else {
+            let inner = left.right.as_concat();
+            Node::pair(
+                Node::pair(left.left.clone(), inner.left.clone()),
+                Node::pair(inner.right.clone(), right),
+            )
+        }
+    }
+
+    fn pair_or_merge(left: Node, right: Node) -> Node {
+        if let (Node::Leaf(l), Node::Leaf(r)) = (&left, &right) {
+            if can_merge(l, r) {
+                return merge_leaves(l, r);
+            }
+        }
+        Node::pair(left, right)
+    }
+
+    fn pair(left: Node, right: Node) -> Node {
+        #[cfg(test)]
+        CONCAT_NODES.with(|count| count.set(count.get() + 1));
+        let len = left.len() + right.len();
+        let depth = max(left.depth(), right.depth()) + 1;
+        Node::Concat(Arc::new(Concat {
+            left,
+            right,
+            len,
+            depth,
+        }))
+    }
+
+    // Returns the node as a concatenation, which it must be judging
+    // by its depth.
+    fn as_concat(&self) -> &Concat {
+        match self {
+            Node::Concat(concat) => concat,
+            Node::Leaf(_) => unreachable!("a leaf was expected to be deeper"),
+        }
+    }
+
+    // Finds the leaf containing `pos`, which must be within the data,
+    // returning it with its offset.
+    fn find_leaf(&self, mut pos: usize) -> (&Arc<Leaf>, usize) {
+        debug_assert!(pos < self.len());
+        let start = pos;
+        let mut node = self;
+        loop {
+            match node {
+                Node::Concat(concat) => {
+                    let left_len = concat.left.len();
+                    if pos < left_len {
+                        node = &concat.left;
+                    } else {
+                        pos -= left_len;
+                        node = &concat.right;
+                    }
+                }
+                Node::Leaf(leaf) => return (leaf, start - pos),
+            }
+        }
+    }
+}
+
+#[inline]
+fn can_merge(left: &Leaf, right: &Leaf) -> bool {
+    left.chunks.len() + right.chunks.len() <= MAX_MERGED_CHUNKS
+}
+
+fn merge_leaves(left: &Leaf, right: &Leaf) -> Node {
+    Node::from_chunks(
+        left.chunks.iter().chain(&*right.chunks).cloned().collect(),
+    )
+}
+
+impl Default for ChunkedRope {
+    #[inline]
+    fn default() -> Self {
+        ChunkedRope::from_node(Node::default())
+    }
+}
+
+impl ChunkedRope {
+    fn new(chunks: impl Iterator<Item = Bytes>) -> Self {
+        let chunks = chunks.filter(|chunk| !chunk.is_empty()).collect();
+        ChunkedRope::from_node(Node::from_chunks(chunks))
+    }
+
+    fn from_node(root: Node) -> Self {
+        let mut rope = ChunkedRope {
+            root,
+            pos: 0,
+            leaf: empty_leaf(),
+            leaf_start: 0,
+            index: 0,
+        };
+        rope.seek();
+        rope
+    }
+
+    /// Returns the length of the data remaining in the rope.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.root.len() - self.pos
+    }
+
+    /// Returns true if no data remain in the rope.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a rope with the remaining data of `self` followed by
+    /// the remaining data of `other`.
+    ///
+    /// If neither rope has been partially consumed, this takes time
+    /// logarithmic in the number of chunks. Otherwise, the references
+    /// to the remaining chunks of the consumed ropes are collected into
+    /// a new rope.
+    pub fn concat(&self, other: &ChunkedRope) -> ChunkedRope {
+        if self.is_empty() {
+            return other.clone();
+        }
+        if other.is_empty() {
+            return self.clone();
+        }
+        let left = self.to_unconsumed_node();
+        let right = other.to_unconsumed_node();
+        ChunkedRope::from_node(Node::concat(left, right))
+    }
+
+    /// Returns a view of the given range of the remaining data.
+    ///
+    /// The returned `FrozenSlice` holds `Bytes` references to the parts of
+    /// the chunks covered by the range, so no data are copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of the bounds of the remaining data,
+    /// or if its start is greater than its end.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> FrozenSlice {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1).expect("out of range"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1).expect("out of range"),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => len,
+        };
+        assert!(
+            start <= end,
+            "range start must not be greater than end: {:?} <= {:?}",
+            start,
+            end
+        );
+        assert!(
+            end <= len,
+            "range end out of bounds: {:?} <= {:?}",
+            end,
+            len
+        );
+        let mut chunks = Vec::new();
+        let mut rest = end - start;
+        if rest != 0 {
+            for (chunk, offset) in Slices::new(&self.root, self.pos + start) {
+                let n = min(chunk.len() - offset, rest);
+                chunks.push(chunk.slice(offset..offset + n));
+                rest -= n;
+                if rest == 0 {
+                    break;
+                }
+            }
+        }
+        FrozenSlice {
+            chunks,
+            head: 0,
+            len: end - start,
+        }
+    }
+
+    // Returns a node holding exactly the remaining data.
+    fn to_unconsumed_node(&self) -> Node {
+        if self.pos == 0 {
+            self.root.clone()
+        } else {
+            let chunks = self
+                .slices()
+                .map(|(chunk, off)| chunk.slice(off..))
+                .collect();
+            Node::from_chunks(chunks)
+        }
+    }
+
+    // Iterates over the remaining chunks with the offsets at which
+    // their remaining data start.
+    fn slices(&self) -> Slices<'_> {
+        Slices::new(&self.root, self.pos)
+    }
+
+    // Returns the chunk containing the reading position, which must be
+    // within the data, and the offset of the position in the chunk.
+    #[inline]
+    fn locate(&self) -> (&Bytes, usize) {
+        debug_assert!(self.pos < self.root.len());
+        let index = self.index;
+        let start = if index == 0 {
+            0
+        } else {
+            self.leaf.ends[index - 1]
+        };
+        let offset = self.pos - self.leaf_start - start;
+        (&self.leaf.chunks[index], offset)
+    }
+
+    // Moves the reading position forward by `cnt` bytes.
+    #[inline]
+    fn consume(&mut self, cnt: usize) {
+        self.pos += cnt;
+        self.seek();
+    }
+
+    // Updates the cached location of the reading position after it has
+    // moved forward, looking up the leaf from the root only when
+    // the position is past the cached leaf.
+    fn seek(&mut self) {
+        if self.pos >= self.root.len() {
+            return;
+        }
+        let mut offset = self.pos - self.leaf_start;
+        if offset >= self.leaf.len() {
+            let (leaf, start) = self.root.find_leaf(self.pos);
+            self.leaf = leaf.clone();
+            self.leaf_start = start;
+            self.index = 0;
+            offset = self.pos - start;
+        }
+        let ends = &self.leaf.ends[self.index..];
+        self.index += ends.partition_point(|&end| end <= offset);
+    }
+}
+
+// A depth-first traversal of the chunks in a rope, starting at
+// a given offset.
+struct Slices<'a> {
+    // The right-hand subtrees yet to be visited, the next one on top
+    pending: Vec<&'a Node>,
+    chunks: &'a [Bytes],
+    // The offset into the first of `chunks`
+    offset: usize,
+}
+
+impl<'a> Slices<'a> {
+    fn new(root: &'a Node, mut pos: usize) -> Self {
+        let mut pending = Vec::new();
+        let mut node = root;
+        loop {
+            match node {
+                Node::Concat(concat) => {
+                    let left_len = concat.left.len();
+                    if pos < left_len {
+                        pending.push(&concat.right);
+                        node = &concat.left;
+                    } else {
+                        pos -= left_len;
+                        node = &concat.right;
+                    }
+                }
+                Node::Leaf(leaf) => {
+                    let index = leaf.ends.partition_point(|&end| end <= pos);
+                    let start =
+                        if index == 0 { 0 } else { leaf.ends[index - 1] };
+                    return Slices {
+                        pending,
+                        chunks: &leaf.chunks[index..],
+                        offset: pos - start,
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Slices<'a> {
+    type Item = (&'a Bytes, usize);
+
+    fn next(&mut self) -> Option<(&'a Bytes, usize)> {
+        while self.chunks.is_empty() {
+            let mut node = self.pending.pop()?;
+            loop {
+                match node {
+                    Node::Concat(concat) => {
+                        self.pending.push(&concat.right);
+                        node = &concat.left;
+                    }
+                    Node::Leaf(leaf) => {
+                        self.chunks = &leaf.chunks;
+                        break;
+                    }
+                }
+            }
+        }
+        let (chunk, rest) = self.chunks.split_first()?;
+        self.chunks = rest;
+        let offset = mem::replace(&mut self.offset, 0);
+        Some((chunk, offset))
+    }
+}
+
+impl Buf for ChunkedRope {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.len()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        if self.is_empty() {
+            return &[];
+        }
+        let (chunk, offset) = self.locate();
+        &chunk[offset..]
+    }
+
+    /// Advances the reading position by `cnt`. The shared chunks are not
+    /// modified, so other clones of the rope are not affected.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `cnt > self.remaining()`.
+    fn advance(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.len(),
+            "cannot advance by {} bytes, remaining = {}",
+            cnt,
+            self.len()
+        );
+        self.consume(cnt);
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let mut n = 0;
+        for ((chunk, offset), slot) in self.slices().zip(dst.iter_mut()) {
+            *slot = IoSlice::new(&chunk[offset..]);
+            n += 1;
+        }
+        n
+    }
+
+    fn copy_to_bytes(&mut self, len: usize) -> Bytes {
+        assert!(len <= self.len(), "`len` greater than remaining");
+        if len != 0 && len <= self.chunk().len() {
+            // Slice the shared chunk rather than copying
+            let (chunk, offset) = self.locate();
+            let bytes = chunk.slice(offset..offset + len);
+            self.consume(len);
+            return bytes;
+        }
+        let mut buf = BytesMut::with_capacity(len);
+        while buf.len() < len {
+            let chunk = self.chunk();
+            let n = min(chunk.len(), len - buf.len());
+            buf.extend_from_slice(&chunk[..n]);
+            self.consume(n);
+        }
+        buf.freeze()
+    }
+}
+
+/// A view of a range of data in a `ChunkedRope`.
+///
+/// `FrozenSlice` is produced by `ChunkedRope::slice`. It holds `Bytes`
+/// slices of the chunks in the range, sharing the memory with the rope.
+/// It implements `Buf` to consume the data, and can be converted into
+/// a `ChunkedRope` with `From`.
+#[derive(Clone, Default)]
+pub struct FrozenSlice {
+    chunks: Vec<Bytes>,
+    // The index of the first unconsumed chunk
+    head: usize,
+    len: usize,
+}
+
+impl FrozenSlice {
+    /// Returns the length of the data remaining in the slice.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no data remain in the slice.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the `Bytes` slices holding the remaining data.
+    #[inline]
+    pub fn chunks(&self) -> &[Bytes] {
+        &self.chunks[self.head..]
+    }
+}
+
+impl Buf for FrozenSlice {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn chunk(&self) -> &[u8] {
+        self.chunks().first().map_or(&[], |chunk| chunk)
+    }
+
+    /// Advances the reading position by `cnt`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `cnt > self.remaining()`.
+    fn advance(&mut self, mut cnt: usize) {
+        assert!(
+            cnt <= self.len,
+            "cannot advance by {} bytes, remaining = {}",
+            cnt,
+            self.len
+        );
+        self.len -= cnt;
+        while cnt != 0 {
+            let chunk = &mut self.chunks[self.head];
+            if cnt < chunk.len() {
+                Buf::advance(chunk, cnt);
+                break;
+            }
+            cnt -= chunk.len();
+            self.chunks[self.head] = Bytes::new();
+            self.head += 1;
+        }
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let mut n = 0;
+        for (chunk, slot) in self.chunks().iter().zip(dst.iter_mut()) {
+            *slot = IoSlice::new(chunk);
+            n += 1;
+        }
+        n
+    }
+
+    fn copy_to_bytes(&mut self, len: usize) -> Bytes {
+        match self.chunks().first() {
+            Some(chunk) if len <= chunk.len() => {
+                let bytes = chunk.slice(..len);
+                self.advance(len);
+                bytes
+            }
+            _ => {
+                assert!(len <= self.len, "`len` greater than remaining");
+                let mut buf = BytesMut::with_capacity(len);
+                buf.put(self.take(len));
+                buf.freeze()
+            }
+        }
+    }
+}
+
+impl From<FrozenSlice> for ChunkedRope {
+    fn from(slice: FrozenSlice) -> Self {
+        let mut chunks = slice.chunks;
+        chunks.drain(..slice.head);
+        ChunkedRope::new(chunks.into_iter())
+    }
+}
+
+impl fmt::Debug for FrozenSlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrozenSlice")
+            .field("chunks", &self.chunks().len())
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<S: ChunkStorage> From<loosely::ChunkedBytesWith<S>> for ChunkedRope {
+    fn from(buf: loosely::ChunkedBytesWith<S>) -> Self {
+        ChunkedRope::new(buf.into_chunks())
+    }
+}
+
+impl<S: ChunkStorage> From<strictly::ChunkedBytesWith<S>> for ChunkedRope {
+    fn from(buf: strictly::ChunkedBytesWith<S>) -> Self {
+        ChunkedRope::new(buf.into_chunks())
+    }
+}
+
+impl fmt::Debug for ChunkedRope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkedRope")
+            .field("len", &self.len())
+            .finish()
+    }
+}
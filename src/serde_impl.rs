@@ -0,0 +1,79 @@
+use crate::{loosely, strictly};
+
+use bytes::{Buf, BufMut, Bytes};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use std::fmt;
+use std::io::IoSlice;
+use std::marker::PhantomData;
+
+// Collects the serialized bytes into a container
+struct ChunkedBytesVisitor<T>(PhantomData<T>);
+
+macro_rules! impl_serde {
+    ($t:ty) => {
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                if self.chunk().len() == self.remaining() {
+                    serializer.serialize_bytes(self.chunk())
+                } else {
+                    // Serializers take the bytes in one contiguous slice
+                    let mut slices =
+                        vec![IoSlice::new(&[]); self.vectored_len()];
+                    let n = self.chunks_vectored(&mut slices);
+                    let mut buf = Vec::with_capacity(self.remaining());
+                    for slice in &slices[..n] {
+                        buf.extend_from_slice(slice);
+                    }
+                    serializer.serialize_bytes(&buf)
+                }
+            }
+        }
+
+        impl<'de> Visitor<'de> for ChunkedBytesVisitor<$t> {
+            type Value = $t;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<$t, E> {
+                let mut buf = <$t>::new();
+                buf.put_slice(v);
+                Ok(buf)
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<$t, E> {
+                Ok(<$t>::from_chunks(Some(Bytes::from(v))))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<$t, A::Error> {
+                let mut buf = <$t>::new();
+                while let Some(b) = seq.next_element::<u8>()? {
+                    buf.put_u8(b);
+                }
+                Ok(buf)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<$t, D::Error> {
+                deserializer.deserialize_byte_buf(ChunkedBytesVisitor::<$t>(
+                    PhantomData,
+                ))
+            }
+        }
+    };
+}
+
+impl_serde!(loosely::ChunkedBytes);
+impl_serde!(strictly::ChunkedBytes);
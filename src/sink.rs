@@ -0,0 +1,63 @@
+use crate::{loosely, strictly, CapacityError};
+
+use bytes::Bytes;
+use futures_sink::Sink;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+macro_rules! impl_sink {
+    ($t:ty) => {
+        /// Appends the sent `Bytes` slices to the buffer without copying.
+        ///
+        /// The sink is ready to accept an item while the buffered length is
+        /// below the high watermark set with `set_watermarks`. As nothing can
+        /// drain the buffer while it is in use as a sink, `poll_ready` returns
+        /// a `CapacityError` when the high watermark is reached, rather than
+        /// waiting. Sending an item that would exceed the maximum length set
+        /// with `set_max_len` also fails.
+        ///
+        /// This implementation is only available with the `futures-sink`
+        /// feature.
+        impl Sink<Bytes> for $t {
+            type Error = CapacityError;
+
+            fn poll_ready(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Result<(), CapacityError>> {
+                if self.is_above_high_watermark() {
+                    Poll::Ready(Err(CapacityError::new(())))
+                } else {
+                    Poll::Ready(Ok(()))
+                }
+            }
+
+            fn start_send(
+                self: Pin<&mut Self>,
+                item: Bytes,
+            ) -> Result<(), CapacityError> {
+                self.get_mut()
+                    .try_put_bytes(item)
+                    .map_err(|_| CapacityError::new(()))
+            }
+
+            fn poll_flush(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Result<(), CapacityError>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_close(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Result<(), CapacityError>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
+
+impl_sink!(loosely::ChunkedBytes);
+impl_sink!(strictly::ChunkedBytes);
@@ -0,0 +1,201 @@
+use crate::loosely::ChunkedBytes;
+
+use bytes::{Buf, BufMut, Bytes};
+
+use std::cmp::min;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::mem;
+
+/// A buffer that spills its data to a temporary file past
+/// an in-memory threshold.
+///
+/// Data written into a `SpillBuffer` are kept in memory until the amount
+/// of buffered data reaches the configured threshold. After that, newly
+/// written data are collected in the memory in pieces of the preferred
+/// chunk size and appended to a temporary file. As the buffered data are
+/// consumed through the `BufRead` or `Read` implementations, the spilled
+/// data are read back from the file, so memory use stays bounded by about
+/// the threshold plus two chunks.
+///
+/// The temporary file is created on the first spill, and is deleted
+/// when the buffer is dropped.
+///
+/// This type is only available with the `spill` feature.
+pub struct SpillBuffer {
+    // The data to be consumed first
+    head: ChunkedBytes,
+    // The data written after the spilled data
+    tail: ChunkedBytes,
+    file: Option<File>,
+    // The positions in the file delimiting the unread spilled data
+    read_pos: u64,
+    write_pos: u64,
+    threshold: usize,
+    spilling: bool,
+}
+
+impl SpillBuffer {
+    /// Creates a buffer that holds up to `threshold` bytes in memory
+    /// before spilling, with the default chunk size.
+    pub fn new(threshold: usize) -> Self {
+        Self::with_chunk_size_hint(
+            threshold,
+            ChunkedBytes::new().chunk_size_hint(),
+        )
+    }
+
+    /// Creates a buffer that holds up to `threshold` bytes in memory
+    /// before spilling, writing to and reading from the file in pieces
+    /// of `chunk_size` bytes.
+    pub fn with_chunk_size_hint(threshold: usize, chunk_size: usize) -> Self {
+        SpillBuffer {
+            head: ChunkedBytes::with_chunk_size_hint(chunk_size),
+            tail: ChunkedBytes::with_chunk_size_hint(chunk_size),
+            file: None,
+            read_pos: 0,
+            write_pos: 0,
+            threshold,
+            spilling: false,
+        }
+    }
+
+    /// Returns the total length of the buffered data, including
+    /// the spilled data.
+    pub fn len(&self) -> u64 {
+        self.head.remaining() as u64
+            + self.spilled_len()
+            + self.tail.remaining() as u64
+    }
+
+    /// Returns true if the buffer holds no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the length of the data currently held in the file.
+    pub fn spilled_len(&self) -> u64 {
+        self.write_pos - self.read_pos
+    }
+
+    /// Appends a `Bytes` slice to the buffer. While the data are
+    /// held in memory, this does not copy the data.
+    pub fn put_bytes(&mut self, src: Bytes) -> io::Result<()> {
+        if self.spilling {
+            self.tail.put_bytes(src);
+            self.spill_tail()
+        } else {
+            self.head.put_bytes(src);
+            self.check_threshold();
+            Ok(())
+        }
+    }
+
+    fn check_threshold(&mut self) {
+        if self.head.remaining() >= self.threshold {
+            self.spilling = true;
+        }
+    }
+
+    // Writes the accumulated tail data to the file once enough have
+    // been collected.
+    fn spill_tail(&mut self) -> io::Result<()> {
+        if self.tail.remaining() < self.tail.chunk_size_hint() {
+            return Ok(());
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(tempfile::tempfile()?),
+        };
+        file.seek(SeekFrom::Start(self.write_pos))?;
+        while self.tail.has_remaining() {
+            let n = self.tail.write_to(file)?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.write_pos += n as u64;
+        }
+        Ok(())
+    }
+
+    // Refills the empty head buffer from the file or the tail.
+    fn refill(&mut self) -> io::Result<()> {
+        debug_assert!(!self.head.has_remaining());
+        if self.spilled_len() != 0 {
+            let max =
+                min(self.spilled_len(), self.head.chunk_size_hint() as u64);
+            let file = self.file.as_mut().expect("spilled data without file");
+            file.seek(SeekFrom::Start(self.read_pos))?;
+            let n = self.head.read_from(file, max as usize)?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.read_pos += n as u64;
+        } else if self.tail.has_remaining() {
+            mem::swap(&mut self.head, &mut self.tail);
+        }
+        if self.spilled_len() == 0 && !self.tail.has_remaining() {
+            // Everything is back in memory, start over
+            self.read_pos = 0;
+            self.write_pos = 0;
+            if let Some(file) = &self.file {
+                file.set_len(0)?;
+            }
+            self.spilling = false;
+            self.check_threshold();
+        }
+        Ok(())
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.spilling {
+            self.tail.put_slice(buf);
+            self.spill_tail()?;
+        } else {
+            self.head.put_slice(buf);
+            self.check_threshold();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl BufRead for SpillBuffer {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.head.has_remaining() {
+            self.refill()?;
+        }
+        Ok(self.head.chunk())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.head.advance(amt)
+    }
+}
+
+impl Read for SpillBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let src = self.fill_buf()?;
+        let n = min(src.len(), buf.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl fmt::Debug for SpillBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpillBuffer")
+            .field("head", &self.head)
+            .field("spilled_len", &self.spilled_len())
+            .field("tail", &self.tail)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+const BUCKET_COUNT: usize = usize::BITS as usize + 1;
+
+/// A histogram of byte lengths with power-of-two buckets.
+///
+/// The bucket with index 0 counts zero lengths, and the bucket with index
+/// `n > 0` counts lengths in the range `2^(n-1) ..= 2^n - 1`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKET_COUNT],
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            buckets: [0; BUCKET_COUNT],
+        }
+    }
+
+    #[inline]
+    fn record(&mut self, len: usize) {
+        let index = (usize::BITS - len.leading_zeros()) as usize;
+        self.buckets[index] += 1;
+    }
+
+    /// Returns the total number of recorded lengths.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns the counts in all buckets, indexed as described in
+    /// the type documentation.
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// Returns an iterator over the non-empty buckets, yielding the range
+    /// of lengths counted in each bucket together with the count.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (RangeInclusive<usize>, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count != 0)
+            .map(|(index, &count)| (bucket_range(index), count))
+    }
+}
+
+fn bucket_range(index: usize) -> RangeInclusive<usize> {
+    match index {
+        0 => 0..=0,
+        _ => {
+            let start = 1 << (index - 1);
+            start..=start.wrapping_shl(1).wrapping_sub(1)
+        }
+    }
+}
+
+impl fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Statistics on the sizes of data chunks passing through `ChunkedBytes`.
+///
+/// Recording of the statistics is enabled by the `record_stats` method
+/// of `ChunkedBytes`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkStats {
+    produced: Histogram,
+    consumed: Histogram,
+}
+
+impl ChunkStats {
+    pub(crate) fn new() -> Self {
+        ChunkStats {
+            produced: Histogram::new(),
+            consumed: Histogram::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_produced(&mut self, len: usize) {
+        self.produced.record(len)
+    }
+
+    #[inline]
+    pub(crate) fn record_consumed(&mut self, len: usize) {
+        self.consumed.record(len)
+    }
+
+    /// Returns the histogram of the sizes of complete chunks, as they were
+    /// split off the staging buffer or appended with `put_bytes`.
+    pub fn produced(&self) -> &Histogram {
+        &self.produced
+    }
+
+    /// Returns the histogram of the lengths by which the reading position
+    /// has been advanced, including the lengths consumed with
+    /// `copy_to_bytes`. Removing the complete chunks with `drain_chunks`
+    /// records their total length. When the buffer is drained with
+    /// vectored output, these are the lengths of data gathered by each
+    /// write.
+    pub fn consumed(&self) -> &Histogram {
+        &self.consumed
+    }
+}
@@ -0,0 +1,163 @@
+use bytes::{Buf, Bytes};
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Backing storage for chunks appended with `put_storage`.
+///
+/// Besides the chunks it allocates itself and those appended as `Bytes`,
+/// a `ChunkedBytes` container holds chunks backed by the storage type
+/// it is parameterized with. The storage type can be implemented with
+/// `Arc<[u8]>`, a segment of shared memory, or a handle to a block from
+/// a memory pool. The default storage type, `SharedStorage`, can hold
+/// any of these behind an `Arc`.
+///
+/// The data of such chunks are available without copying through `chunk`,
+/// `chunks_vectored` and the output methods of the container. Where
+/// a chunk needs to be handed out as `Bytes`, as with `copy_to_bytes`,
+/// `drain_chunks` or `into_chunks`, it is converted with `into_bytes`.
+pub trait ChunkStorage: Sized + Send + Sync + 'static {
+    /// Returns the number of stored bytes.
+    fn len(&self) -> usize;
+
+    /// Returns true if there are no stored bytes.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the stored bytes.
+    fn as_slice(&self) -> &[u8];
+
+    /// Splits the storage in two at the given index, returning the storage
+    /// for the bytes before the index and keeping the rest in `self`.
+    ///
+    /// The implementation may panic if `at` is greater than the length.
+    fn split_to(&mut self, at: usize) -> Self;
+
+    /// Discards the first `cnt` stored bytes.
+    ///
+    /// The implementation may panic if `cnt` is greater than the length.
+    fn advance(&mut self, cnt: usize);
+
+    /// Converts the storage into `Bytes`.
+    ///
+    /// The default implementation copies the data.
+    #[inline]
+    fn into_bytes(self) -> Bytes {
+        Bytes::copy_from_slice(self.as_slice())
+    }
+}
+
+impl ChunkStorage for Bytes {
+    #[inline]
+    fn len(&self) -> usize {
+        Bytes::len(self)
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    #[inline]
+    fn split_to(&mut self, at: usize) -> Self {
+        Bytes::split_to(self, at)
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        Buf::advance(self, cnt)
+    }
+
+    #[inline]
+    fn into_bytes(self) -> Bytes {
+        self
+    }
+}
+
+/// The default chunk storage, sharing the stored bytes of any type
+/// implementing `AsRef<[u8]>`.
+///
+/// The stored value is held in an `Arc`, so the chunk can be split and
+/// partially consumed without copying; the value is dropped, releasing
+/// the memory, when the last part of the chunk is consumed.
+#[derive(Clone)]
+pub struct SharedStorage {
+    storage: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    start: usize,
+    end: usize,
+}
+
+impl SharedStorage {
+    /// Creates storage sharing the bytes of `storage`.
+    ///
+    /// The slice returned by `as_ref` must be the same for every call.
+    pub fn new<T>(storage: T) -> Self
+    where
+        T: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let end = storage.as_ref().len();
+        SharedStorage {
+            storage: Arc::new(storage),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl ChunkStorage for SharedStorage {
+    #[inline]
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        &(*self.storage).as_ref()[self.start..self.end]
+    }
+
+    #[inline]
+    fn split_to(&mut self, at: usize) -> Self {
+        assert!(at <= self.len(), "split index out of bounds");
+        let mut head = self.clone();
+        head.end = self.start + at;
+        self.start += at;
+        head
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        assert!(cnt <= self.len(), "cannot advance past the end");
+        self.start += cnt;
+    }
+}
+
+impl From<Vec<u8>> for SharedStorage {
+    #[inline]
+    fn from(storage: Vec<u8>) -> Self {
+        SharedStorage::new(storage)
+    }
+}
+
+impl From<Box<[u8]>> for SharedStorage {
+    #[inline]
+    fn from(storage: Box<[u8]>) -> Self {
+        SharedStorage::new(storage)
+    }
+}
+
+impl From<Arc<[u8]>> for SharedStorage {
+    #[inline]
+    fn from(storage: Arc<[u8]>) -> Self {
+        SharedStorage::new(storage)
+    }
+}
+
+impl fmt::Debug for SharedStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedStorage")
+            .field("len", &self.len())
+            .finish()
+    }
+}
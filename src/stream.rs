@@ -0,0 +1,74 @@
+use crate::{loosely, strictly, CapacityError};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use std::future::poll_fn;
+use std::io;
+use std::pin::pin;
+
+macro_rules! impl_stream {
+    ($t:ty) => {
+        impl $t {
+            /// Appends all `Bytes` items produced by `stream` to the buffer
+            /// without copying.
+            ///
+            /// # Errors
+            ///
+            /// If appending an item would exceed the maximum length set with
+            /// `set_max_len`, collecting stops and the rejected item is
+            /// returned in the `CapacityError`. The items appended before
+            /// that remain in the buffer.
+            ///
+            /// This method is only available with the `futures-core` feature.
+            pub async fn collect_stream<S>(
+                &mut self,
+                stream: S,
+            ) -> Result<(), CapacityError<Bytes>>
+            where
+                S: Stream<Item = Bytes>,
+            {
+                let mut stream = pin!(stream);
+                while let Some(item) =
+                    poll_fn(|cx| stream.as_mut().poll_next(cx)).await
+                {
+                    self.try_put_bytes(item)?;
+                }
+                Ok(())
+            }
+
+            /// Appends all `Bytes` items produced by a fallible `stream`
+            /// to the buffer without copying, until the stream ends
+            /// or produces an error.
+            ///
+            /// # Errors
+            ///
+            /// An error produced by the stream is returned. If appending
+            /// an item would exceed the maximum length set with
+            /// `set_max_len`, an error of kind `ErrorKind::Other` is
+            /// returned, wrapping the `CapacityError` with the rejected item.
+            /// The items appended before the failure remain in the buffer.
+            ///
+            /// This method is only available with the `futures-core` feature.
+            pub async fn collect_io_stream<S>(
+                &mut self,
+                stream: S,
+            ) -> io::Result<()>
+            where
+                S: Stream<Item = io::Result<Bytes>>,
+            {
+                let mut stream = pin!(stream);
+                while let Some(item) =
+                    poll_fn(|cx| stream.as_mut().poll_next(cx)).await
+                {
+                    self.try_put_bytes(item?)
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_stream!(loosely::ChunkedBytes);
+impl_stream!(strictly::ChunkedBytes);
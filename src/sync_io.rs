@@ -0,0 +1,129 @@
+use crate::ChunkedBytes;
+use bytes::{Buf, BufMut};
+
+use std::cmp::min;
+use std::io::{self, IoSlice, Read, Write};
+use std::slice;
+
+// The number of `IoSlice` entries gathered for one vectored write.
+// This is well within `IOV_MAX` on the platforms that have it.
+pub(crate) const MAX_IO_SLICES: usize = 64;
+
+pub(crate) fn write_to<B, W>(buf: &mut B, out: &mut W) -> io::Result<usize>
+where
+    B: Buf + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut io_bufs = [IoSlice::new(&[]); MAX_IO_SLICES];
+    let io_vec_len = buf.chunks_vectored(&mut io_bufs);
+    let bytes_written = out.write_vectored(&io_bufs[..io_vec_len])?;
+    buf.advance(bytes_written);
+    Ok(bytes_written)
+}
+
+pub(crate) fn read_from<B, R>(
+    buf: &mut B,
+    src: &mut R,
+    max: usize,
+) -> io::Result<usize>
+where
+    B: BufMut,
+    R: Read + ?Sized,
+{
+    let mut total = 0;
+    while total < max && buf.has_remaining_mut() {
+        let dst = buf.chunk_mut();
+        let len = min(dst.len(), max - total);
+        // Safety: the memory is zeroed before it is exposed
+        // as an initialized slice.
+        let dst = unsafe {
+            let ptr = dst.as_mut_ptr();
+            ptr.write_bytes(0, len);
+            slice::from_raw_parts_mut(ptr, len)
+        };
+        match src.read(dst) {
+            Ok(0) => break,
+            Ok(n) => {
+                assert!(n <= len, "reader reported more bytes than read");
+                // Safety: the first `n` bytes have been initialized
+                // within the slice returned by `chunk_mut`.
+                unsafe { buf.advance_mut(n) };
+                total += n;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+/// Extension methods for `std::io::Read` to read data into
+/// `ChunkedBytes`.
+///
+/// The data is accumulated in chunks of the default preferred size,
+/// avoiding the reallocations needed to grow one contiguous buffer.
+pub trait ReadChunkedExt: Read {
+    /// Reads all bytes until end of input into a new `ChunkedBytes`
+    /// container.
+    ///
+    /// If an error other than `ErrorKind::Interrupted` occurs,
+    /// it is returned and the bytes read so far are discarded.
+    fn read_to_chunked(&mut self) -> io::Result<ChunkedBytes> {
+        let mut buf = ChunkedBytes::new();
+        read_from(&mut buf, self, usize::MAX)?;
+        Ok(buf)
+    }
+
+    /// Reads exactly `n` bytes into a new `ChunkedBytes` container.
+    ///
+    /// An error of kind `ErrorKind::UnexpectedEof` is returned if
+    /// end of input is reached before `n` bytes have been read.
+    fn read_chunked_exact(&mut self, n: usize) -> io::Result<ChunkedBytes> {
+        let mut buf = ChunkedBytes::new();
+        if read_from(&mut buf, self, n)? < n {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill the buffer",
+            ));
+        }
+        Ok(buf)
+    }
+}
+
+impl<R: Read + ?Sized> ReadChunkedExt for R {}
+
+/// Extension methods for `std::io::Write` to output data buffered in
+/// `ChunkedBytes`.
+pub trait WriteChunkedExt: Write {
+    /// Writes all of the data from `buf` with vectored writes,
+    /// advancing the reading position of `buf` as the data is written.
+    ///
+    /// This method works with both `loosely::ChunkedBytes` and
+    /// `strictly::ChunkedBytes`, or indeed with any `Buf` implementation.
+    ///
+    /// Writes failing with `ErrorKind::Interrupted` are retried. If a write
+    /// returns 0 while data remains in `buf`, an error of kind
+    /// `ErrorKind::WriteZero` is returned. On any error, the data that
+    /// has not been written remains in `buf`.
+    fn write_all_chunked<B>(&mut self, buf: &mut B) -> io::Result<()>
+    where
+        B: Buf + ?Sized,
+    {
+        while buf.has_remaining() {
+            match write_to(buf, self) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the buffered data",
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> WriteChunkedExt for W {}
@@ -0,0 +1,71 @@
+//! Utilities for testing consumers of the buffered data.
+//!
+//! This module is only available with the `test-util` feature.
+
+use bytes::Buf;
+
+use std::cmp::min;
+
+/// Wraps a `Buf` to expose its data in slices of at most `max_chunk_len`
+/// bytes.
+///
+/// This is useful to test consumers of `Buf` against fragmentation of the
+/// data that is as heavy as can be produced by `ChunkedBytes`, or heavier.
+///
+/// # Panics
+///
+/// This function panics if `max_chunk_len` is zero.
+pub fn fragmented<B: Buf>(buf: B, max_chunk_len: usize) -> Fragmented<B> {
+    assert!(max_chunk_len != 0, "chunk length limit must not be zero");
+    Fragmented { buf, max_chunk_len }
+}
+
+/// A `Buf` adapter limiting the length of the slices it exposes.
+///
+/// This value is created by the `fragmented` function.
+/// The `chunk` method returns at most the configured number of bytes,
+/// and `chunks_vectored` fills only a single `IoSlice` with the same
+/// slice as returned by `chunk`.
+#[derive(Debug)]
+pub struct Fragmented<B> {
+    buf: B,
+    max_chunk_len: usize,
+}
+
+impl<B> Fragmented<B> {
+    /// Returns a reference to the underlying buffer.
+    #[inline]
+    pub fn get_ref(&self) -> &B {
+        &self.buf
+    }
+
+    /// Returns a mutable reference to the underlying buffer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.buf
+    }
+
+    /// Consumes the adapter, returning the underlying buffer.
+    #[inline]
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+impl<B: Buf> Buf for Fragmented<B> {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.buf.remaining()
+    }
+
+    #[inline]
+    fn chunk(&self) -> &[u8] {
+        let chunk = self.buf.chunk();
+        &chunk[..min(chunk.len(), self.max_chunk_len)]
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        self.buf.advance(cnt)
+    }
+}
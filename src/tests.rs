@@ -1,10 +1,97 @@
-use crate::{loosely, strictly, DrainChunks};
-use bytes::{Buf, BufMut};
+use crate::{
+    loosely, strictly, CapacityError, ChunkedRope, DrainChunks, HexDump,
+    Integer, JsonEscape, Layout, Packets, SharedStorage,
+};
+use bytes::{Buf, BufMut, Bytes};
 
-trait TestBuf: Buf + BufMut {
+use std::cmp::min;
+use std::collections::TryReserveError;
+use std::fmt;
+use std::io::{self, IoSlice};
+use std::sync::Arc;
+
+trait TestBuf: Buf + BufMut + Sized {
     fn with_chunk_size(size: usize) -> Self;
+    fn from_chunks(chunks: Vec<Bytes>) -> Self;
+    fn put_itoa<T: Integer>(&mut self, value: T);
+    fn hex_dump(&self) -> HexDump<'_>;
+    fn layout(&self) -> Layout;
+    fn put_chunk(&mut self, src: Bytes);
+    #[cfg(feature = "test-util")]
+    fn set_chunk_boundaries(&mut self, offsets: Vec<usize>);
+    #[cfg(feature = "ryu")]
+    fn put_f64_str(&mut self, value: f64);
     fn drain_chunks(&mut self) -> DrainChunks<'_>;
     fn staging_capacity(&self) -> usize;
+    fn chunk_count(&self) -> usize;
+    fn flush_count(&self) -> u64;
+    fn reserve_count(&self) -> u64;
+    fn set_max_len(&mut self, max_len: usize);
+    fn try_put_slice(&mut self, src: &[u8]) -> Result<(), CapacityError>;
+    fn try_reserve(&mut self, additional: usize)
+        -> Result<(), TryReserveError>;
+    fn write_to(&mut self, out: &mut dyn io::Write) -> io::Result<usize>;
+    fn read_from(
+        &mut self,
+        src: &mut dyn io::Read,
+        max: usize,
+    ) -> io::Result<usize>;
+    fn packets(&mut self, mtu: usize) -> Packets<'_, Self>;
+    fn find_byte(&self, byte: u8) -> Option<usize>;
+    fn find(&self, needle: &[u8]) -> Option<usize>;
+    fn chunks_vectored_limited<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        max_bytes: usize,
+    ) -> (usize, usize);
+    fn vectored_len(&self) -> usize;
+    fn chunks_vectored_at<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        offset: usize,
+    ) -> usize;
+    fn put_storage<T: Into<SharedStorage>>(&mut self, storage: T);
+    fn split_to(&mut self, at: usize) -> Self;
+    fn read_until(&mut self, delimiter: &[u8]) -> Option<Self>;
+    fn read_until_byte(&mut self, byte: u8) -> Option<Self>;
+    #[cfg(feature = "prost")]
+    fn put_message<M: prost::Message>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError>;
+    #[cfg(feature = "prost")]
+    fn put_message_length_delimited<M: prost::Message>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError>;
+    #[cfg(feature = "rmp")]
+    fn put_msgpack_bin(&mut self, data: Bytes);
+    #[cfg(feature = "cbor")]
+    fn put_cbor_bytes(&mut self, data: Bytes);
+    #[cfg(feature = "cbor")]
+    fn put_cbor_bytes_chunked(&mut self, chunks: Vec<Bytes>);
+    #[cfg(feature = "bincode")]
+    fn put_bincode(&mut self, value: &(u32, &str)) -> bincode::Result<()>;
+    #[cfg(feature = "postcard")]
+    fn put_postcard(&mut self, value: &(u32, &str)) -> postcard::Result<()>;
+    fn json_string(&mut self) -> JsonEscape<'_, Self>;
+    fn json_escape(&mut self) -> JsonEscape<'_, Self>;
+    #[cfg(feature = "percent-encoding")]
+    fn percent_encode(
+        &mut self,
+        set: &'static percent_encoding::AsciiSet,
+    ) -> crate::PercentEncoder<'_, Self>;
+    #[cfg(feature = "digest")]
+    fn hash_into<D: digest::Update>(&self, hasher: &mut D);
+    #[cfg(feature = "digest")]
+    fn hashing_writer<D: digest::Update>(
+        &mut self,
+        hasher: D,
+    ) -> crate::HashingWriter<'_, Self, D>;
+    #[cfg(feature = "xxhash")]
+    fn xxh3_64(&self) -> u64;
+    #[cfg(feature = "xxhash")]
+    fn xxh64(&self, seed: u64) -> u64;
 }
 
 impl TestBuf for loosely::ChunkedBytes {
@@ -12,6 +99,36 @@ impl TestBuf for loosely::ChunkedBytes {
         loosely::ChunkedBytes::with_chunk_size_hint(size)
     }
 
+    fn from_chunks(chunks: Vec<Bytes>) -> Self {
+        loosely::ChunkedBytes::from_chunks(chunks)
+    }
+
+    fn put_itoa<T: Integer>(&mut self, value: T) {
+        self.put_itoa(value)
+    }
+
+    fn hex_dump(&self) -> HexDump<'_> {
+        self.hex_dump()
+    }
+
+    fn layout(&self) -> Layout {
+        self.layout()
+    }
+
+    fn put_chunk(&mut self, src: Bytes) {
+        self.put_bytes(src)
+    }
+
+    #[cfg(feature = "test-util")]
+    fn set_chunk_boundaries(&mut self, offsets: Vec<usize>) {
+        self.set_chunk_boundaries(offsets)
+    }
+
+    #[cfg(feature = "ryu")]
+    fn put_f64_str(&mut self, value: f64) {
+        self.put_f64_str(value)
+    }
+
     fn drain_chunks(&mut self) -> DrainChunks<'_> {
         self.drain_chunks()
     }
@@ -19,6 +136,173 @@ impl TestBuf for loosely::ChunkedBytes {
     fn staging_capacity(&self) -> usize {
         self.staging_capacity()
     }
+
+    fn chunk_count(&self) -> usize {
+        self.chunk_count()
+    }
+
+    fn flush_count(&self) -> u64 {
+        self.flush_count()
+    }
+
+    fn reserve_count(&self) -> u64 {
+        self.reserve_count()
+    }
+
+    fn set_max_len(&mut self, max_len: usize) {
+        self.set_max_len(max_len)
+    }
+
+    fn try_put_slice(&mut self, src: &[u8]) -> Result<(), CapacityError> {
+        self.try_put_slice(src)
+    }
+
+    fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        self.try_reserve(additional)
+    }
+
+    fn write_to(&mut self, out: &mut dyn io::Write) -> io::Result<usize> {
+        self.write_to(out)
+    }
+
+    fn read_from(
+        &mut self,
+        src: &mut dyn io::Read,
+        max: usize,
+    ) -> io::Result<usize> {
+        self.read_from(src, max)
+    }
+
+    fn packets(&mut self, mtu: usize) -> Packets<'_, Self> {
+        self.packets(mtu)
+    }
+
+    fn find_byte(&self, byte: u8) -> Option<usize> {
+        self.find_byte(byte)
+    }
+
+    fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.find(needle)
+    }
+
+    fn chunks_vectored_limited<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        max_bytes: usize,
+    ) -> (usize, usize) {
+        self.chunks_vectored_limited(dst, max_bytes)
+    }
+
+    fn vectored_len(&self) -> usize {
+        self.vectored_len()
+    }
+
+    fn chunks_vectored_at<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        offset: usize,
+    ) -> usize {
+        self.chunks_vectored_at(dst, offset)
+    }
+
+    fn put_storage<T: Into<SharedStorage>>(&mut self, storage: T) {
+        self.put_storage(storage)
+    }
+
+    fn split_to(&mut self, at: usize) -> Self {
+        self.split_to(at)
+    }
+
+    fn read_until(&mut self, delimiter: &[u8]) -> Option<Self> {
+        self.read_until(delimiter)
+    }
+
+    fn read_until_byte(&mut self, byte: u8) -> Option<Self> {
+        self.read_until_byte(byte)
+    }
+
+    #[cfg(feature = "prost")]
+    fn put_message<M: prost::Message>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError> {
+        self.put_message(msg)
+    }
+
+    #[cfg(feature = "prost")]
+    fn put_message_length_delimited<M: prost::Message>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError> {
+        self.put_message_length_delimited(msg)
+    }
+
+    #[cfg(feature = "rmp")]
+    fn put_msgpack_bin(&mut self, data: Bytes) {
+        self.put_msgpack_bin(data)
+    }
+
+    #[cfg(feature = "cbor")]
+    fn put_cbor_bytes(&mut self, data: Bytes) {
+        self.put_cbor_bytes(data)
+    }
+
+    #[cfg(feature = "cbor")]
+    fn put_cbor_bytes_chunked(&mut self, chunks: Vec<Bytes>) {
+        self.put_cbor_bytes_chunked(chunks)
+    }
+
+    #[cfg(feature = "bincode")]
+    fn put_bincode(&mut self, value: &(u32, &str)) -> bincode::Result<()> {
+        self.put_bincode(value)
+    }
+
+    #[cfg(feature = "postcard")]
+    fn put_postcard(&mut self, value: &(u32, &str)) -> postcard::Result<()> {
+        self.put_postcard(value)
+    }
+
+    fn json_string(&mut self) -> JsonEscape<'_, Self> {
+        self.json_string()
+    }
+
+    fn json_escape(&mut self) -> JsonEscape<'_, Self> {
+        self.json_escape()
+    }
+
+    #[cfg(feature = "percent-encoding")]
+    fn percent_encode(
+        &mut self,
+        set: &'static percent_encoding::AsciiSet,
+    ) -> crate::PercentEncoder<'_, Self> {
+        self.percent_encode(set)
+    }
+
+    #[cfg(feature = "digest")]
+    fn hash_into<D: digest::Update>(&self, hasher: &mut D) {
+        self.hash_into(hasher)
+    }
+
+    #[cfg(feature = "digest")]
+    fn hashing_writer<D: digest::Update>(
+        &mut self,
+        hasher: D,
+    ) -> crate::HashingWriter<'_, Self, D> {
+        self.hashing_writer(hasher)
+    }
+
+    #[cfg(feature = "xxhash")]
+    fn xxh3_64(&self) -> u64 {
+        self.xxh3_64()
+    }
+
+    #[cfg(feature = "xxhash")]
+    fn xxh64(&self, seed: u64) -> u64 {
+        self.xxh64(seed)
+    }
 }
 
 impl TestBuf for strictly::ChunkedBytes {
@@ -26,6 +310,36 @@ impl TestBuf for strictly::ChunkedBytes {
         strictly::ChunkedBytes::with_chunk_size_limit(size)
     }
 
+    fn from_chunks(chunks: Vec<Bytes>) -> Self {
+        strictly::ChunkedBytes::from_chunks(chunks)
+    }
+
+    fn put_itoa<T: Integer>(&mut self, value: T) {
+        self.put_itoa(value)
+    }
+
+    fn hex_dump(&self) -> HexDump<'_> {
+        self.hex_dump()
+    }
+
+    fn layout(&self) -> Layout {
+        self.layout()
+    }
+
+    fn put_chunk(&mut self, src: Bytes) {
+        self.put_bytes(src)
+    }
+
+    #[cfg(feature = "test-util")]
+    fn set_chunk_boundaries(&mut self, offsets: Vec<usize>) {
+        self.set_chunk_boundaries(offsets)
+    }
+
+    #[cfg(feature = "ryu")]
+    fn put_f64_str(&mut self, value: f64) {
+        self.put_f64_str(value)
+    }
+
     fn drain_chunks(&mut self) -> DrainChunks<'_> {
         self.drain_chunks()
     }
@@ -33,6 +347,173 @@ impl TestBuf for strictly::ChunkedBytes {
     fn staging_capacity(&self) -> usize {
         self.staging_capacity()
     }
+
+    fn chunk_count(&self) -> usize {
+        self.chunk_count()
+    }
+
+    fn flush_count(&self) -> u64 {
+        self.flush_count()
+    }
+
+    fn reserve_count(&self) -> u64 {
+        self.reserve_count()
+    }
+
+    fn set_max_len(&mut self, max_len: usize) {
+        self.set_max_len(max_len)
+    }
+
+    fn try_put_slice(&mut self, src: &[u8]) -> Result<(), CapacityError> {
+        self.try_put_slice(src)
+    }
+
+    fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        self.try_reserve(additional)
+    }
+
+    fn write_to(&mut self, out: &mut dyn io::Write) -> io::Result<usize> {
+        self.write_to(out)
+    }
+
+    fn read_from(
+        &mut self,
+        src: &mut dyn io::Read,
+        max: usize,
+    ) -> io::Result<usize> {
+        self.read_from(src, max)
+    }
+
+    fn packets(&mut self, mtu: usize) -> Packets<'_, Self> {
+        self.packets(mtu)
+    }
+
+    fn find_byte(&self, byte: u8) -> Option<usize> {
+        self.find_byte(byte)
+    }
+
+    fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.find(needle)
+    }
+
+    fn chunks_vectored_limited<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        max_bytes: usize,
+    ) -> (usize, usize) {
+        self.chunks_vectored_limited(dst, max_bytes)
+    }
+
+    fn vectored_len(&self) -> usize {
+        self.vectored_len()
+    }
+
+    fn chunks_vectored_at<'a>(
+        &'a self,
+        dst: &mut [IoSlice<'a>],
+        offset: usize,
+    ) -> usize {
+        self.chunks_vectored_at(dst, offset)
+    }
+
+    fn put_storage<T: Into<SharedStorage>>(&mut self, storage: T) {
+        self.put_storage(storage)
+    }
+
+    fn split_to(&mut self, at: usize) -> Self {
+        self.split_to(at)
+    }
+
+    fn read_until(&mut self, delimiter: &[u8]) -> Option<Self> {
+        self.read_until(delimiter)
+    }
+
+    fn read_until_byte(&mut self, byte: u8) -> Option<Self> {
+        self.read_until_byte(byte)
+    }
+
+    #[cfg(feature = "prost")]
+    fn put_message<M: prost::Message>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError> {
+        self.put_message(msg)
+    }
+
+    #[cfg(feature = "prost")]
+    fn put_message_length_delimited<M: prost::Message>(
+        &mut self,
+        msg: &M,
+    ) -> Result<(), prost::EncodeError> {
+        self.put_message_length_delimited(msg)
+    }
+
+    #[cfg(feature = "rmp")]
+    fn put_msgpack_bin(&mut self, data: Bytes) {
+        self.put_msgpack_bin(data)
+    }
+
+    #[cfg(feature = "cbor")]
+    fn put_cbor_bytes(&mut self, data: Bytes) {
+        self.put_cbor_bytes(data)
+    }
+
+    #[cfg(feature = "cbor")]
+    fn put_cbor_bytes_chunked(&mut self, chunks: Vec<Bytes>) {
+        self.put_cbor_bytes_chunked(chunks)
+    }
+
+    #[cfg(feature = "bincode")]
+    fn put_bincode(&mut self, value: &(u32, &str)) -> bincode::Result<()> {
+        self.put_bincode(value)
+    }
+
+    #[cfg(feature = "postcard")]
+    fn put_postcard(&mut self, value: &(u32, &str)) -> postcard::Result<()> {
+        self.put_postcard(value)
+    }
+
+    fn json_string(&mut self) -> JsonEscape<'_, Self> {
+        self.json_string()
+    }
+
+    fn json_escape(&mut self) -> JsonEscape<'_, Self> {
+        self.json_escape()
+    }
+
+    #[cfg(feature = "percent-encoding")]
+    fn percent_encode(
+        &mut self,
+        set: &'static percent_encoding::AsciiSet,
+    ) -> crate::PercentEncoder<'_, Self> {
+        self.percent_encode(set)
+    }
+
+    #[cfg(feature = "digest")]
+    fn hash_into<D: digest::Update>(&self, hasher: &mut D) {
+        self.hash_into(hasher)
+    }
+
+    #[cfg(feature = "digest")]
+    fn hashing_writer<D: digest::Update>(
+        &mut self,
+        hasher: D,
+    ) -> crate::HashingWriter<'_, Self, D> {
+        self.hashing_writer(hasher)
+    }
+
+    #[cfg(feature = "xxhash")]
+    fn xxh3_64(&self) -> u64 {
+        self.xxh3_64()
+    }
+
+    #[cfg(feature = "xxhash")]
+    fn xxh64(&self, seed: u64) -> u64 {
+        self.xxh64(seed)
+    }
 }
 
 #[generic_tests::define]
@@ -76,9 +557,2583 @@ mod properties {
         );
     }
 
-    #[instantiate_tests(<loosely::ChunkedBytes>)]
-    mod loosely_chunked_bytes {}
+    #[test]
+    fn from_chunks_skips_empty<B: TestBuf>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"foo"),
+            Bytes::new(),
+            Bytes::from_static(b"bar"),
+        ]);
+        assert_eq!(buf.remaining(), 6);
+        assert_eq!(buf.chunk(), b"foo");
+        let chunks = buf.drain_chunks().collect::<Vec<_>>();
+        assert_eq!(chunks, [&b"foo"[..], &b"bar"[..]]);
+    }
 
-    #[instantiate_tests(<strictly::ChunkedBytes>)]
-    mod strictly_chunked_bytes {}
+    #[test]
+    fn max_len_bounds_writes<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.set_max_len(12);
+        assert_eq!(buf.remaining_mut(), 12);
+        buf.try_put_slice(&[0; 10]).unwrap();
+        assert_eq!(buf.remaining_mut(), 2);
+        assert!(buf.chunk_mut().len() <= 2);
+        buf.try_put_slice(&[0; 3]).unwrap_err();
+        assert_eq!(buf.remaining(), 10);
+        buf.advance(4);
+        buf.try_put_slice(&[0; 6]).unwrap();
+        assert_eq!(buf.remaining(), 12);
+        assert_eq!(buf.remaining_mut(), 0);
+    }
+
+    #[test]
+    fn try_reserve_extends_chunk_mut<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.try_reserve(3).unwrap();
+        assert!(buf.chunk_mut().len() >= 3);
+        buf.put_slice(&[0; 5]);
+        buf.try_reserve(6).unwrap();
+        assert!(buf.chunk_mut().len() >= 6);
+        assert_eq!(buf.remaining(), 5);
+    }
+
+    #[test]
+    fn write_fmt_grows_buffer<B: TestBuf + fmt::Write>() {
+        let mut buf = B::with_chunk_size(8);
+        let n = 42;
+        write!(buf, "long enough to span chunks-{}", n).unwrap();
+        let mut out = vec![0; buf.remaining()];
+        buf.copy_to_slice(&mut out);
+        assert_eq!(out, b"long enough to span chunks-42");
+    }
+
+    #[test]
+    fn put_itoa_formats_decimal<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_itoa(0u8);
+        buf.put_u8(b' ');
+        buf.put_itoa(-1234567i32);
+        buf.put_u8(b' ');
+        buf.put_itoa(i64::MIN);
+        buf.put_u8(b' ');
+        buf.put_itoa(u128::MAX);
+        let mut out = vec![0; buf.remaining()];
+        buf.copy_to_slice(&mut out);
+        let expected = format!("0 -1234567 {} {}", i64::MIN, u128::MAX);
+        assert_eq!(out, expected.as_bytes());
+    }
+
+    #[cfg(feature = "ryu")]
+    #[test]
+    fn put_f64_str_formats_shortest<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_f64_str(0.1);
+        buf.put_u8(b' ');
+        buf.put_f64_str(-1.5e300);
+        buf.put_u8(b' ');
+        buf.put_f64_str(f64::NAN);
+        let mut out = vec![0; buf.remaining()];
+        buf.copy_to_slice(&mut out);
+        assert_eq!(out, b"0.1 -1.5e300 NaN");
+    }
+
+    #[test]
+    fn hex_formatting_spans_chunks<B: TestBuf + fmt::LowerHex>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"Hello, "),
+            Bytes::from_static(b"world!\n"),
+        ]);
+        buf.put_slice(b"\x00\xff");
+        assert_eq!(format!("{:#x}", buf), "0x48656c6c6f2c20776f726c64210a00ff");
+        assert_eq!(
+            buf.hex_dump().to_string(),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  \
+             |Hello, world!...|\n"
+        );
+        buf.advance(2);
+        assert_eq!(
+            buf.hex_dump().to_string(),
+            "00000000  6c 6c 6f 2c 20 77 6f 72  6c 64 21 0a 00 ff        \
+             |llo, world!...|\n"
+        );
+    }
+
+    #[test]
+    fn put_bytes_flushes_staging<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_slice(b"foo");
+        buf.put_chunk(Bytes::from_static(b"quux"));
+        let layout = buf.layout();
+        assert_eq!(layout.chunks, [3, 4]);
+        assert_eq!(layout.staging_len, 0);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn forced_chunk_boundaries<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.set_chunk_boundaries(vec![5, 3, 12]);
+        buf.put_slice(&[0; 10]);
+        buf.put_chunk(Bytes::from(vec![0; 6]));
+        assert_eq!(buf.layout().chunks, [3, 2, 5, 2, 4]);
+    }
+
+    #[test]
+    fn counts_flushes_and_reserves<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_slice(&[0; 4]);
+        assert_eq!(buf.flush_count(), 0);
+        assert_eq!(buf.reserve_count(), 1);
+        let cap = buf.staging_capacity();
+        buf.put_slice(&vec![0; cap][..]);
+        assert_eq!(buf.flush_count(), 1);
+        assert_eq!(buf.reserve_count(), 2);
+        assert_eq!(buf.chunk_count(), 1);
+    }
+
+    #[test]
+    fn read_across_chunks<B: TestBuf + io::Read>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"foo"),
+            Bytes::from_static(b"bar"),
+        ]);
+        buf.put_slice(b"baz");
+        let mut out = [0; 4];
+        assert_eq!(buf.read(&mut out).unwrap(), 4);
+        assert_eq!(&out, b"foob");
+        let mut rest = Vec::new();
+        buf.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"arbaz");
+        assert!(!buf.has_remaining());
+    }
+
+    #[test]
+    fn write_vectored_copies_all<B: TestBuf + io::Write>() {
+        let mut buf = B::with_chunk_size(8);
+        let bufs = [
+            IoSlice::new(b"Hello"),
+            IoSlice::new(b", "),
+            IoSlice::new(b"world!"),
+        ];
+        assert_eq!(buf.write_vectored(&bufs).unwrap(), 13);
+        buf.write_all(b"!!").unwrap();
+        let mut out = Vec::new();
+        io::Read::read_to_end(&mut buf.reader(), &mut out).unwrap();
+        assert_eq!(out, b"Hello, world!!!");
+    }
+
+    #[test]
+    fn write_is_partial_at_max_len<B: TestBuf + io::Write>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.set_max_len(4);
+        assert_eq!(buf.write(b"Hello").unwrap(), 4);
+        assert_eq!(buf.write(b"!").unwrap(), 0);
+    }
+
+    #[test]
+    fn write_to_advances_by_written<B: TestBuf>() {
+        struct Limited(Vec<u8>);
+
+        impl io::Write for Limited {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let len = min(buf.len(), 5);
+                self.0.extend_from_slice(&buf[..len]);
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut buf = B::with_chunk_size(4);
+        buf.put_slice(b"Hello, world!");
+        let mut out = Limited(Vec::new());
+        while buf.has_remaining() {
+            let remaining = buf.remaining();
+            let written = buf.write_to(&mut out).unwrap();
+            assert!(written > 0);
+            assert_eq!(buf.remaining(), remaining - written);
+        }
+        assert_eq!(out.0, b"Hello, world!");
+    }
+
+    #[test]
+    fn read_from_rolls_chunks<B: TestBuf>() {
+        let data = (0..40).collect::<Vec<u8>>();
+        let mut buf = B::with_chunk_size(8);
+        let mut src = &data[..];
+        assert_eq!(buf.read_from(&mut src, 30).unwrap(), 30);
+        assert_eq!(buf.remaining(), 30);
+        assert!(buf.chunk_count() >= 3);
+        assert_eq!(buf.read_from(&mut src, 30).unwrap(), 10);
+        assert_eq!(buf.copy_to_bytes(40), data);
+    }
+
+    #[test]
+    fn packets_coalesce_and_split<B: TestBuf>() {
+        let large = Bytes::from(vec![1; 20]);
+        let mut buf = B::with_chunk_size(32);
+        buf.put_slice(&[0; 3]);
+        buf.put_chunk(large.clone());
+        buf.put_slice(&[2; 5]);
+        let packets = buf.packets(8).collect::<Vec<_>>();
+        assert!(!buf.has_remaining());
+        let lens = packets.iter().map(|p| p.len()).collect::<Vec<_>>();
+        assert_eq!(lens, [8, 8, 8, 4]);
+        assert_eq!(&packets[0][..], [0, 0, 0, 1, 1, 1, 1, 1]);
+        assert_eq!(packets[1].as_ptr(), large[5..].as_ptr());
+        assert_eq!(&packets[3][..], [2; 4]);
+    }
+
+    #[test]
+    fn put_slice_splits_once_at_flush<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_slice(b"foo");
+        let cap = buf.staging_capacity();
+        let src = (0..cap as u8 + 5).collect::<Vec<_>>();
+        buf.put_slice(&src);
+        let layout = buf.layout();
+        assert_eq!(layout.chunks, [cap]);
+        assert_eq!(layout.staging_len, 8);
+        assert_eq!(buf.reserve_count(), 2);
+        let mut out = vec![0; buf.remaining()];
+        buf.copy_to_slice(&mut out);
+        assert_eq!(&out[..3], b"foo");
+        assert_eq!(&out[3..], &src[..]);
+    }
+
+    #[test]
+    fn copy_to_slice_across_chunks<B: TestBuf>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"foo"),
+            Bytes::from_static(b"bar"),
+            Bytes::from_static(b"baz"),
+            Bytes::from_static(b"quux"),
+        ]);
+        buf.put_slice(b"staged");
+        let mut dst = [0; 8];
+        buf.copy_to_slice(&mut dst);
+        assert_eq!(&dst, b"foobarba");
+        assert_eq!(buf.layout().chunks, [1, 4]);
+        let mut dst = [0; 8];
+        buf.copy_to_slice(&mut dst);
+        assert_eq!(&dst, b"zquuxsta");
+        let layout = buf.layout();
+        assert!(layout.chunks.is_empty());
+        assert_eq!(layout.staging_len, 3);
+        assert_eq!(buf.chunk(), b"ged");
+    }
+
+    #[test]
+    fn find_spans_chunks<B: TestBuf>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"hello, w"),
+            Bytes::from_static(b"o"),
+            Bytes::from_static(b"rld\r"),
+        ]);
+        buf.put_slice(b"\nbye");
+        assert_eq!(buf.find_byte(b'o'), Some(4));
+        assert_eq!(buf.find_byte(b'\n'), Some(13));
+        assert_eq!(buf.find_byte(b'z'), None);
+        assert_eq!(buf.find(b""), Some(0));
+        assert_eq!(buf.find(b"world"), Some(7));
+        assert_eq!(buf.find(b"\r\n"), Some(12));
+        assert_eq!(buf.find(b"bye"), Some(14));
+        assert_eq!(buf.find(b"byes"), None);
+        buf.advance(5);
+        assert_eq!(buf.find(b"wor"), Some(2));
+    }
+
+    #[test]
+    fn chunks_vectored_limited_stops_at_budget<B: TestBuf>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"foo"),
+            Bytes::from_static(b"bar"),
+        ]);
+        buf.put_slice(b"baz");
+        let mut dst = [IoSlice::new(&[]); 4];
+        assert_eq!(buf.chunks_vectored_limited(&mut dst, 5), (2, 5));
+        assert_eq!(&dst[0][..], b"foo");
+        assert_eq!(&dst[1][..], b"ba");
+        assert_eq!(buf.chunks_vectored_limited(&mut dst, 6), (2, 6));
+        assert_eq!(buf.chunks_vectored_limited(&mut dst, 100), (3, 9));
+        assert_eq!(&dst[2][..], b"baz");
+        assert_eq!(buf.chunks_vectored_limited(&mut dst[..1], 100), (1, 3));
+        assert_eq!(buf.chunks_vectored_limited(&mut dst, 0), (0, 0));
+    }
+
+    #[test]
+    fn vectored_len_counts_staging<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        assert_eq!(buf.vectored_len(), 0);
+        buf.put_slice(b"foo");
+        assert_eq!(buf.vectored_len(), 1);
+        buf.put_chunk(Bytes::from_static(b"quux"));
+        assert_eq!(buf.vectored_len(), 2);
+        buf.put_slice(b"bar");
+        let mut dst = vec![IoSlice::new(&[]); buf.vectored_len()];
+        assert_eq!(buf.chunks_vectored(&mut dst), 3);
+    }
+
+    #[test]
+    fn chunks_vectored_at_offset<B: TestBuf>() {
+        let mut buf = B::from_chunks(vec![
+            Bytes::from_static(b"foo"),
+            Bytes::from_static(b"bar"),
+        ]);
+        buf.put_slice(b"baz");
+        let mut dst = [IoSlice::new(&[]); 4];
+        assert_eq!(buf.chunks_vectored_at(&mut dst, 0), 3);
+        assert_eq!(buf.chunks_vectored_at(&mut dst, 3), 2);
+        assert_eq!(&dst[0][..], b"bar");
+        assert_eq!(buf.chunks_vectored_at(&mut dst, 4), 2);
+        assert_eq!(&dst[0][..], b"ar");
+        assert_eq!(&dst[1][..], b"baz");
+        assert_eq!(buf.chunks_vectored_at(&mut dst, 8), 1);
+        assert_eq!(&dst[0][..], b"z");
+        assert_eq!(buf.chunks_vectored_at(&mut dst, 9), 0);
+        assert_eq!(buf.chunks_vectored_at(&mut dst, 100), 0);
+        assert_eq!(buf.remaining(), 9);
+    }
+
+    #[test]
+    fn put_storage_shares_data<B: TestBuf>() {
+        let storage: Arc<[u8]> = Arc::from(&b"hello, world"[..]);
+        let mut buf = B::with_chunk_size(8);
+        buf.put_slice(b">> ");
+        buf.put_storage(storage.clone());
+        buf.put_storage(Vec::new());
+        buf.put_storage(b"!".to_vec());
+        assert_eq!(buf.remaining(), 16);
+        buf.advance(3);
+        assert_eq!(buf.chunk().as_ptr(), storage.as_ptr());
+        buf.advance(9);
+        assert_eq!(buf.chunk(), b"rld");
+        assert_eq!(buf.copy_to_bytes(4), "rld!");
+        assert!(!buf.has_remaining());
+        assert_eq!(Arc::strong_count(&storage), 1);
+    }
+
+    #[test]
+    fn split_to_moves_chunks<B: TestBuf>() {
+        let chunk = Bytes::from(vec![1; 16]);
+        let mut buf = B::with_chunk_size(8);
+        buf.put_chunk(chunk.clone());
+        buf.put_slice(b"staged");
+        let mut head = buf.split_to(20);
+        assert_eq!(head.remaining(), 20);
+        assert_eq!(head.chunk().as_ptr(), chunk.as_ptr());
+        head.advance(16);
+        assert_eq!(head.chunk(), b"stag");
+        assert_eq!(buf.remaining(), 2);
+        assert_eq!(buf.chunk(), b"ed");
+        buf.put_slice(b" more");
+        assert_eq!(buf.copy_to_bytes(7), "ed more");
+
+        let mut buf = B::from_chunks(vec![Bytes::from("hello, world")]);
+        let mut head = buf.split_to(5);
+        assert_eq!(head.copy_to_bytes(5), "hello");
+        assert_eq!(buf.chunk(), b", world");
+        assert!(!buf.split_to(0).has_remaining());
+        assert_eq!(buf.remaining(), 7);
+    }
+
+    #[test]
+    fn read_until_delimiter<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_chunk(Bytes::from("GET / HTTP/1.1\r"));
+        buf.put_slice(b"\nHost: x\r\n\r\nbody");
+        let mut line = buf.read_until(b"\r\n").unwrap();
+        assert_eq!(line.copy_to_bytes(line.remaining()), "GET / HTTP/1.1\r\n");
+        let mut line = buf.read_until(b"\r\n").unwrap();
+        assert_eq!(line.copy_to_bytes(line.remaining()), "Host: x\r\n");
+        assert_eq!(buf.read_until(b"\r\n").unwrap().remaining(), 2);
+        assert!(buf.read_until(b"\r\n").is_none());
+        assert_eq!(buf.remaining(), 4);
+
+        buf.put_slice(b"\nrest");
+        let mut line = buf.read_until_byte(b'\n').unwrap();
+        assert_eq!(line.copy_to_bytes(5), "body\n");
+        assert!(buf.read_until_byte(b'\n').is_none());
+        assert_eq!(buf.copy_to_bytes(4), "rest");
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn put_message_encodes_protobuf<B: TestBuf>() {
+        let msg = String::from("hello");
+        let mut buf = B::with_chunk_size(8);
+        buf.put_message(&msg).unwrap();
+        buf.put_message_length_delimited(&msg).unwrap();
+        assert_eq!(buf.remaining(), 7 + 8);
+        assert_eq!(buf.copy_to_bytes(7), &b"\x0a\x05hello"[..]);
+        assert_eq!(buf.copy_to_bytes(8), &b"\x07\x0a\x05hello"[..]);
+
+        buf.set_max_len(6);
+        assert!(buf.put_message(&msg).is_err());
+        assert!(!buf.has_remaining());
+    }
+
+    #[cfg(feature = "rmp")]
+    #[test]
+    fn put_msgpack_bin_shares_data<B: TestBuf>() {
+        let data = Bytes::from(vec![9; 600]);
+        let mut buf = B::with_chunk_size(1024);
+        rmp::encode::write_array_len(&mut (&mut buf).writer(), 2).unwrap();
+        buf.put_msgpack_bin(data.clone());
+        buf.put_msgpack_bin(Bytes::from_static(b"ab"));
+        assert_eq!(buf.remaining(), 1 + 3 + 600 + 2 + 2);
+        assert_eq!(buf.copy_to_bytes(4), &[0x92, 0xc5, 0x02, 0x58][..]);
+        assert_eq!(buf.chunk().as_ptr(), data.as_ptr());
+        buf.advance(600);
+        assert_eq!(buf.copy_to_bytes(4), &b"\xc4\x02ab"[..]);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn put_cbor_bytes_shares_data<B: TestBuf>() {
+        let data = Bytes::from(vec![3; 600]);
+        let mut buf = B::with_chunk_size(1024);
+        buf.put_cbor_bytes(data.clone());
+        buf.put_cbor_bytes_chunked(vec![
+            Bytes::from_static(b"ab"),
+            Bytes::new(),
+            data.clone(),
+        ]);
+        assert_eq!(buf.remaining(), 3 + 600 + 1 + 3 + 1 + 3 + 600 + 1);
+        assert_eq!(buf.copy_to_bytes(3), &[0x59, 0x02, 0x58][..]);
+        assert_eq!(buf.chunk().as_ptr(), data.as_ptr());
+        buf.advance(600);
+        assert_eq!(buf.copy_to_bytes(5), &b"\x5f\x42ab\x40"[..]);
+        assert_eq!(buf.copy_to_bytes(3), &[0x59, 0x02, 0x58][..]);
+        assert_eq!(buf.chunk().as_ptr(), data.as_ptr());
+        buf.advance(600);
+        assert_eq!(buf.copy_to_bytes(1), &[0xff][..]);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn put_bincode_serializes<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_bincode(&(7, "hello")).unwrap();
+        assert_eq!(buf.remaining(), 4 + 8 + 5);
+        assert_eq!(buf.copy_to_bytes(12), &b"\x07\0\0\0\x05\0\0\0\0\0\0\0"[..]);
+        assert_eq!(buf.copy_to_bytes(5), "hello");
+
+        buf.set_max_len(16);
+        let err = buf.put_bincode(&(7, "hello")).unwrap_err();
+        assert!(matches!(*err, bincode::ErrorKind::SizeLimit));
+        assert!(!buf.has_remaining());
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn put_postcard_serializes<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(4);
+        buf.put_postcard(&(300, "hello")).unwrap();
+        assert_eq!(buf.copy_to_bytes(8), &b"\xac\x02\x05hello"[..]);
+
+        buf.set_max_len(4);
+        let err = buf.put_postcard(&(7, "hello")).unwrap_err();
+        assert_eq!(err, postcard::Error::SerializeBufferFull);
+    }
+
+    #[test]
+    fn json_escape_text<B: TestBuf>() {
+        use std::fmt::Write as _;
+        use std::io::Write as _;
+
+        let mut buf = B::with_chunk_size(4);
+        {
+            let mut out = buf.json_string();
+            fmt::Write::write_fmt(
+                &mut out,
+                format_args!("say \"{}\"\n", "hi\\there"),
+            )
+            .unwrap();
+            out.write_all(b"\x01\tnul\x00 \xc3\xa9").unwrap();
+        }
+        buf.json_escape().write_str("\u{8}\u{c}\u{1f}").unwrap();
+        let expected = b"\"say \\\"hi\\\\there\\\"\\n\\u0001\\tnul\\u0000 \xc3\xa9\"\\b\\f\\u001f";
+        assert_eq!(buf.copy_to_bytes(buf.remaining()), &expected[..]);
+
+        buf.set_max_len(4);
+        let mut out = buf.json_escape();
+        assert!(out.write_str("ab\"c").is_err());
+        assert_eq!(out.write(b"c").unwrap(), 0);
+        drop(out);
+        assert_eq!(buf.copy_to_bytes(buf.remaining()), "ab\\\"");
+        buf.put_slice(b"ab");
+        assert_eq!(buf.json_escape().write(b"c\n").unwrap(), 1);
+    }
+
+    #[cfg(feature = "percent-encoding")]
+    #[test]
+    fn percent_encode_query<B: TestBuf>() {
+        use percent_encoding::NON_ALPHANUMERIC;
+        use std::fmt::Write as _;
+        use std::io::Write as _;
+
+        let mut buf = B::with_chunk_size(4);
+        buf.put_slice(b"q=");
+        let mut out = buf.percent_encode(NON_ALPHANUMERIC);
+        out.write_str("a b&c").unwrap();
+        out.write_all("é/100%".as_bytes()).unwrap();
+        assert_eq!(
+            buf.copy_to_bytes(buf.remaining()),
+            "q=a%20b%26c%C3%A9%2F100%25"
+        );
+
+        buf.set_max_len(4);
+        let mut out = buf.percent_encode(NON_ALPHANUMERIC);
+        assert_eq!(out.write(b"ab cd").unwrap(), 2);
+        assert!(out.write_str("xyz").is_err());
+        assert_eq!(buf.copy_to_bytes(buf.remaining()), "abxy");
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn hash_chunked_data<B: TestBuf>() {
+        use std::io::Write as _;
+
+        #[derive(Default)]
+        struct Collect(Vec<u8>);
+
+        impl digest::Update for Collect {
+            fn update(&mut self, data: &[u8]) {
+                self.0.extend_from_slice(data);
+            }
+        }
+
+        let mut buf = B::with_chunk_size(4);
+        buf.put_slice(b"etag:");
+        let mut w = buf.hashing_writer(Collect::default());
+        w.write_all(b"hello, ").unwrap();
+        fmt::Write::write_str(&mut w, "world").unwrap();
+        assert_eq!(w.hasher().0, b"hello, world");
+        buf.put_chunk(Bytes::from_static(b"!"));
+
+        let mut hasher = Collect::default();
+        buf.hash_into(&mut hasher);
+        assert_eq!(hasher.0, b"etag:hello, world!");
+        assert_eq!(buf.remaining(), hasher.0.len());
+
+        buf.advance(buf.remaining());
+        buf.set_max_len(4);
+        let mut w = buf.hashing_writer(Collect::default());
+        assert_eq!(w.write(b"abcdef").unwrap(), 4);
+        assert_eq!(w.into_hasher().0, b"abcd");
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn xxhash_chunked_data<B: TestBuf>() {
+        use crate::xxhash::{Xxh3, Xxh64};
+        use std::io::Write as _;
+        use xxhash_rust::{xxh3::xxh3_64, xxh64::xxh64};
+
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut buf = B::with_chunk_size(64);
+        buf.put_slice(&data[..300]);
+        buf.put_chunk(Bytes::copy_from_slice(&data[300..700]));
+        let mut w = buf.hashing_writer(Xxh3::new());
+        w.write_all(&data[700..]).unwrap();
+        assert_eq!(w.hasher().digest(), xxh3_64(&data[700..]));
+
+        assert_eq!(buf.xxh3_64(), xxh3_64(&data));
+        assert_eq!(buf.xxh64(42), xxh64(&data, 42));
+        let mut hasher = Xxh64::default();
+        buf.hash_into(&mut hasher);
+        assert_eq!(hasher.digest(), xxh64(&data, 0));
+    }
+
+    #[test]
+    fn copy_to_bytes_hands_out_whole_chunks<B: TestBuf>() {
+        let first = Bytes::from(vec![1; 16]);
+        let second = Bytes::from(vec![2; 16]);
+        let mut buf = B::from_chunks(vec![first.clone(), second]);
+        let bytes = buf.copy_to_bytes(16);
+        assert_eq!(bytes.as_ptr(), first.as_ptr());
+        buf.advance(16);
+        assert_eq!(buf.remaining(), 0);
+        assert_eq!(buf.chunk_count(), 0);
+    }
+
+    #[test]
+    fn flush_before_reallocation_keeps_data<B: TestBuf>() {
+        let mut buf = B::with_chunk_size(8);
+        buf.put_slice(b"abc");
+        buf.put_slice(&[1; 20]);
+        buf.put_slice(b"de");
+        buf.advance(2);
+        assert_eq!(buf.remaining(), 23);
+        let mut expected = b"c".to_vec();
+        expected.extend_from_slice(&[1; 20]);
+        expected.extend_from_slice(b"de");
+        assert_eq!(buf.copy_to_bytes(23), expected);
+    }
+
+    #[instantiate_tests(<loosely::ChunkedBytes>)]
+    mod loosely_chunked_bytes {}
+
+    #[instantiate_tests(<strictly::ChunkedBytes>)]
+    mod strictly_chunked_bytes {}
+}
+
+#[test]
+fn try_reserve_reports_capacity_overflow() {
+    let mut buf = loosely::ChunkedBytes::new();
+    buf.put_slice(b"foo");
+    buf.try_reserve(usize::MAX).unwrap_err();
+    assert_eq!(buf.chunk(), b"foo");
+}
+
+#[test]
+fn try_reserve_allocates_aligned_staging_buffer() {
+    let mut buf = loosely::ChunkedBytes::with_aligned_chunks(64, 64);
+    buf.put_slice(&[1; 10]);
+    buf.try_reserve(200).unwrap();
+    let capacity = buf.staging_capacity();
+    assert!(capacity >= 200);
+    assert_eq!(buf.chunk_mut().as_mut_ptr() as usize % 64, 0);
+    assert_eq!(buf.layout().chunks, [10]);
+    buf.try_reserve(isize::MAX as usize).unwrap_err();
+    assert_eq!(buf.remaining(), 10);
+    assert_eq!(buf.staging_capacity(), capacity);
+}
+
+#[test]
+fn debug_shows_chunk_layout() {
+    let mut buf = loosely::ChunkedBytes::from_chunks(vec![
+        Bytes::from_static(b"foo"),
+        Bytes::from_static(b"quux"),
+    ]);
+    buf.put_slice(b"ab");
+    let staging_capacity = buf.staging_capacity();
+    assert_eq!(
+        format!("{:?}", buf),
+        format!(
+            "ChunkedBytes {{ chunk_size_hint: 4096, chunks: [3, 4], \
+             staging_len: 2, staging_capacity: {} }}",
+            staging_capacity
+        )
+    );
+}
+
+#[test]
+fn layout_reflects_chunk_size_limit() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    buf.put_slice(&[0; 20]);
+    buf.put_bytes(Bytes::from(vec![0; 10]));
+    let layout = buf.layout();
+    assert_eq!(layout.total_len(), 30);
+    assert_eq!(layout.staging_len, 0);
+    assert!(layout.chunks.iter().all(|&len| len <= 8));
+    assert_eq!(&layout.chunks[layout.chunks.len() - 2..], [8, 2]);
+}
+
+#[test]
+fn strictly_flush_keeps_chunk_size_limit() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    buf.put_slice(&[0; 3]);
+    buf.flush();
+    buf.put_slice(&[0; 30]);
+    buf.flush();
+    let layout = buf.layout();
+    assert_eq!(layout.total_len(), 33);
+    assert!(layout.chunks.iter().all(|&len| len <= 8));
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn fragmented_limits_chunks() {
+    use crate::test_util::fragmented;
+    use std::io::IoSlice;
+
+    let buf = loosely::ChunkedBytes::with_first_chunk(Bytes::from("Hello"));
+    let mut buf = fragmented(buf, 2);
+    let mut io_bufs = [IoSlice::new(&[]); 4];
+    assert_eq!(buf.chunks_vectored(&mut io_bufs), 1);
+    assert_eq!(&*io_bufs[0], b"He");
+    let mut out = Vec::new();
+    while buf.has_remaining() {
+        out.push(buf.chunk().to_vec());
+        buf.advance(buf.chunk().len());
+    }
+    assert_eq!(out, [&b"He"[..], b"ll", b"o"]);
+}
+
+#[cfg(feature = "proptest")]
+mod prop {
+    use crate::prop::{any_strictly, loosely_with_contents};
+    use bytes::Buf;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn generated_loosely_has_contents(
+            mut buf in loosely_with_contents(b"Hello, world!".to_vec())
+        ) {
+            let mut out = [0; 13];
+            buf.copy_to_slice(&mut out);
+            prop_assert_eq!(&out, b"Hello, world!");
+            prop_assert!(!buf.has_remaining());
+        }
+
+        #[test]
+        fn generated_strictly_keeps_limit(buf in any_strictly()) {
+            let limit = buf.chunk_size_limit();
+            let layout = buf.layout();
+            prop_assert!(layout.chunks.iter().all(|&len| len <= limit));
+            prop_assert!(layout.staging_len <= limit);
+        }
+    }
+}
+
+#[test]
+fn strictly_keeps_limit_after_partial_fill() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(3);
+    buf.put_slice(&[0]);
+    buf.put_slice(&[0; 3]);
+    assert_eq!(buf.layout().chunks, [3]);
+    assert_eq!(buf.layout().staging_len, 1);
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn arbitrary_strictly_keeps_limit() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    let data = (0..=255).cycle().take(4096).collect::<Vec<u8>>();
+    let mut u = Unstructured::new(&data);
+    let buf = strictly::ChunkedBytes::arbitrary(&mut u).unwrap();
+    let limit = buf.chunk_size_limit();
+    let layout = buf.layout();
+    assert!(layout.chunks.iter().all(|&len| len <= limit));
+    assert!(layout.staging_len <= limit);
+}
+
+#[test]
+fn stats_record_chunk_sizes() {
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    assert!(buf.stats().is_none());
+    buf.record_stats();
+    buf.put_bytes(Bytes::from_static(b"foo"));
+    buf.put_bytes(Bytes::from(vec![0; 100]));
+    buf.put_slice(b"12345");
+    buf.flush();
+    buf.advance(4);
+    buf.advance(104);
+    let stats = buf.stats().unwrap();
+    let produced = stats.produced().iter().collect::<Vec<_>>();
+    assert_eq!(produced, [(2..=3, 1), (4..=7, 1), (64..=127, 1)]);
+    let consumed = stats.consumed().iter().collect::<Vec<_>>();
+    assert_eq!(consumed, [(4..=7, 1), (64..=127, 1)]);
+}
+
+#[test]
+fn stats_record_drained_chunks() {
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    buf.record_stats();
+    buf.put_bytes(Bytes::from(vec![0; 100]));
+    buf.put_slice(b"12345");
+    buf.flush();
+    assert_eq!(buf.drain_chunks().count(), 2);
+    let consumed = buf.stats().unwrap().consumed().iter().collect::<Vec<_>>();
+    assert_eq!(consumed, [(64..=127, 1)]);
+}
+
+#[test]
+fn advance_out_of_bounds_leaves_stats_and_budget() {
+    use crate::MemoryBudget;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let budget = MemoryBudget::new(100);
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    buf.record_stats();
+    buf.set_memory_budget(Some(budget.clone()));
+    buf.put_bytes(Bytes::from_static(b"foo"));
+    buf.put_slice(b"bar");
+    catch_unwind(AssertUnwindSafe(|| buf.advance(7))).unwrap_err();
+    catch_unwind(AssertUnwindSafe(|| buf.copy_to_bytes(7))).unwrap_err();
+    assert_eq!(buf.stats().unwrap().consumed().count(), 0);
+    assert_eq!(budget.used(), 6);
+    assert_eq!(buf.remaining(), 6);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(start_paused = true)]
+async fn poll_flush_after_linger() {
+    use futures::future::poll_fn;
+    use std::time::Duration;
+    use tokio::time::{sleep, Instant};
+
+    let mut buf = loosely::ChunkedBytes::new();
+    let linger = Duration::from_millis(10);
+    buf.put_slice(b"foo");
+    assert_eq!(buf.staged_at(), None);
+    buf.flush();
+    buf.record_staged_at();
+    buf.put_slice(b"bar");
+    let start = Instant::now();
+    assert_eq!(buf.staged_at(), Some(start));
+    let timer = sleep(Duration::from_secs(3600));
+    tokio::pin!(timer);
+    poll_fn(|cx| buf.poll_flush_after(cx, timer.as_mut(), linger)).await;
+    assert_eq!(Instant::now() - start, linger);
+    assert_eq!(buf.chunk_count(), 2);
+    assert_eq!(buf.staged_at(), None);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn write_all_to_async_writer() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(4);
+    buf.put_slice(b"Hello, ");
+    buf.put_bytes(Bytes::from_static(b"world!"));
+    let mut out = Vec::new();
+    buf.write_all_to(&mut out).await.unwrap();
+    assert!(!buf.has_remaining());
+    assert_eq!(out, b"Hello, world!");
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn poll_read_from_async_reader() {
+    use futures::future::poll_fn;
+    use std::pin::Pin;
+
+    let data = (0..20).collect::<Vec<u8>>();
+    let mut src = &data[..];
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    loop {
+        let n = poll_fn(|cx| buf.poll_read_from(cx, Pin::new(&mut src)))
+            .await
+            .unwrap();
+        if n == 0 {
+            break;
+        }
+    }
+    assert_eq!(buf.chunk_count(), 2);
+    assert_eq!(buf.copy_to_bytes(20), data);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_read_from_chunks() {
+    use futures::future::poll_fn;
+    use std::pin::Pin;
+    use tokio::io::{AsyncRead, ReadBuf};
+
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(4);
+    buf.put_slice(b"Hello, ");
+    buf.put_bytes(Bytes::from_static(b"world!"));
+    let mut out = [0; 10];
+    let mut read_buf = ReadBuf::new(&mut out);
+    poll_fn(|cx| Pin::new(&mut buf).poll_read(cx, &mut read_buf))
+        .await
+        .unwrap();
+    assert_eq!(read_buf.filled(), b"Hello, wor");
+    let mut read_buf = ReadBuf::new(&mut out);
+    poll_fn(|cx| Pin::new(&mut buf).poll_read(cx, &mut read_buf))
+        .await
+        .unwrap();
+    assert_eq!(read_buf.filled(), b"ld!");
+    assert!(!buf.has_remaining());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_write_appends() {
+    use futures::future::poll_fn;
+    use std::pin::Pin;
+    use tokio::io::AsyncWrite;
+
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(4);
+    let bufs = [IoSlice::new(b"Hello, "), IoSlice::new(b"world!")];
+    let n = poll_fn(|cx| Pin::new(&mut buf).poll_write_vectored(cx, &bufs))
+        .await
+        .unwrap();
+    assert_eq!(n, 13);
+    assert_eq!(buf.copy_to_bytes(13), "Hello, world!");
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn buf_reader_consumes_bytes() {
+    use crate::ChunkedBufReader;
+    use futures::future::poll_fn;
+    use std::pin::Pin;
+    use tokio::io::AsyncBufRead;
+
+    let data = Bytes::from_static(b"Hello, world!");
+    let mut reader = ChunkedBufReader::new(&data[..]);
+    let len = poll_fn(|cx| {
+        Pin::new(&mut reader)
+            .poll_fill_buf(cx)
+            .map_ok(|chunk| chunk.len())
+    })
+    .await
+    .unwrap();
+    assert_eq!(len, 13);
+    assert_eq!(reader.consume_bytes(5), "Hello");
+    Pin::new(&mut reader).consume(2);
+    assert_eq!(reader.buffer().chunk(), b"world!");
+}
+
+#[cfg(feature = "futures-sink")]
+#[tokio::test]
+async fn sink_stops_at_high_watermark() {
+    use futures::stream::{self, StreamExt};
+
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    buf.set_watermarks(0, 8);
+    let items = stream::iter(vec![Bytes::from_static(b"Hello"); 3]).map(Ok);
+    items.forward(&mut buf).await.unwrap_err();
+    assert_eq!(buf.chunk_count(), 2);
+    assert_eq!(buf.remaining(), 10);
+}
+
+#[cfg(feature = "futures-core")]
+#[tokio::test]
+async fn collect_stream_up_to_max_len() {
+    use futures::stream;
+
+    let items = vec![Bytes::from_static(b"Hello"); 3];
+    let mut buf = strictly::ChunkedBytes::new();
+    buf.collect_stream(stream::iter(items.clone()))
+        .await
+        .unwrap();
+    assert_eq!(buf.chunk_count(), 3);
+    assert_eq!(buf.remaining(), 15);
+
+    let mut buf = loosely::ChunkedBytes::new();
+    buf.set_max_len(12);
+    let err = buf.collect_stream(stream::iter(items)).await.unwrap_err();
+    assert_eq!(err.into_inner(), "Hello");
+    assert_eq!(buf.remaining(), 10);
+
+    let items = vec![
+        Ok(Bytes::from_static(b"Hello")),
+        Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+    ];
+    let mut buf = loosely::ChunkedBytes::new();
+    let err = buf
+        .collect_io_stream(stream::iter(items))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(buf.remaining(), 5);
+}
+
+#[cfg(feature = "http-body")]
+#[tokio::test]
+async fn http_body_yields_chunks() {
+    use crate::ChunkedBody;
+    use futures::future::poll_fn;
+    use http_body::Body;
+    use std::pin::Pin;
+
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    buf.put_slice(b"Hello, world!");
+    let mut body = ChunkedBody::from(buf);
+    assert_eq!(body.size_hint().exact(), Some(13));
+    let mut frames = Vec::new();
+    while let Some(frame) =
+        poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await
+    {
+        frames.push(frame.unwrap().into_data().unwrap());
+    }
+    assert_eq!(frames, ["Hello, w", "orld!"]);
+    assert!(body.is_end_stream());
+    assert_eq!(body.size_hint().exact(), Some(0));
+}
+
+#[cfg(feature = "actix-http")]
+#[tokio::test]
+async fn actix_message_body_yields_chunks() {
+    use crate::ChunkedBody;
+    use actix_http::body::{BodySize, MessageBody};
+    use futures::future::poll_fn;
+    use std::pin::Pin;
+
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    buf.put_slice(b"Hello, ");
+    buf.put_bytes(Bytes::from_static(b"world!"));
+    let mut body = ChunkedBody::from(buf);
+    assert_eq!(body.size(), BodySize::Sized(13));
+    let mut chunks = Vec::new();
+    while let Some(chunk) =
+        poll_fn(|cx| Pin::new(&mut body).poll_next(cx)).await
+    {
+        chunks.push(chunk.unwrap());
+    }
+    assert_eq!(chunks, ["Hello, ", "world!"]);
+    assert_eq!(body.size(), BodySize::Sized(0));
+
+    let single = ChunkedBody::from(loosely::ChunkedBytes::from_chunks(vec![
+        Bytes::from_static(b"foo"),
+    ]));
+    assert_eq!(single.try_into_bytes().unwrap(), "foo");
+}
+
+#[cfg(feature = "codec")]
+#[tokio::test]
+async fn framed_write_encodes_without_copying() {
+    use crate::codec::{Encoder, FramedWrite};
+    use futures::SinkExt;
+
+    struct LengthPrefixed;
+
+    impl Encoder<Bytes> for LengthPrefixed {
+        type Error = io::Error;
+
+        fn encode(
+            &mut self,
+            item: Bytes,
+            dst: &mut loosely::ChunkedBytes,
+        ) -> io::Result<()> {
+            dst.put_u16(item.len() as u16);
+            dst.put_bytes(item);
+            Ok(())
+        }
+    }
+
+    let payload = Bytes::from(vec![0xa5; 1000]);
+    let mut framed = FramedWrite::new(Vec::new(), LengthPrefixed);
+    framed.write_buffer_mut().set_watermarks(0, 2000);
+    framed.feed(payload.clone()).await.unwrap();
+    assert_eq!(framed.write_buffer().chunk_count(), 2);
+    framed.feed(payload.clone()).await.unwrap();
+    assert!(framed.get_ref().is_empty());
+    framed.feed(payload.clone()).await.unwrap();
+    assert_eq!(framed.get_ref().len(), 2004);
+    framed.flush().await.unwrap();
+    let out = framed.into_inner();
+    assert_eq!(out.len(), 3006);
+    assert_eq!(&out[..2], &[3, 232]);
+    assert_eq!(out[2..1002], payload);
+}
+
+#[cfg(feature = "codec")]
+#[tokio::test]
+async fn framed_read_decodes_frames() {
+    use crate::codec::{Decoder, FramedRead};
+    use futures::StreamExt;
+
+    struct LengthPrefixed;
+
+    impl Decoder for LengthPrefixed {
+        type Item = Bytes;
+        type Error = io::Error;
+
+        fn decode(
+            &mut self,
+            src: &mut loosely::ChunkedBytes,
+        ) -> io::Result<Option<Bytes>> {
+            if src.remaining() < 2 {
+                return Ok(None);
+            }
+            let mut len = [0; 2];
+            let mut peek = src.chunk();
+            if peek.len() < 2 {
+                return Ok(None);
+            }
+            peek.copy_to_slice(&mut len);
+            let len = u16::from_be_bytes(len) as usize;
+            if src.remaining() < 2 + len {
+                return Ok(None);
+            }
+            src.advance(2);
+            Ok(Some(src.copy_to_bytes(len)))
+        }
+    }
+
+    let input = b"\x00\x05Hello\x00\x06world!\x00";
+    let mut framed = FramedRead::new(&input[..], LengthPrefixed);
+    assert_eq!(framed.next().await.unwrap().unwrap(), "Hello");
+    assert_eq!(framed.next().await.unwrap().unwrap(), "world!");
+    let err = framed.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(framed.next().await.is_none());
+}
+
+#[cfg(feature = "codec")]
+#[tokio::test]
+async fn framed_read_terminates_after_decode_error() {
+    use crate::codec::{Decoder, FramedRead};
+    use futures::StreamExt;
+
+    struct Corrupt(usize);
+
+    impl Decoder for Corrupt {
+        type Item = Bytes;
+        type Error = io::Error;
+
+        fn decode(
+            &mut self,
+            _src: &mut loosely::ChunkedBytes,
+        ) -> io::Result<Option<Bytes>> {
+            self.0 += 1;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt frame"))
+        }
+    }
+
+    let mut framed = FramedRead::new(&b"garbage"[..], Corrupt(0));
+    let err = framed.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(framed.next().await.is_none());
+    assert_eq!(framed.decoder().0, 1);
+}
+
+#[cfg(all(feature = "tokio", feature = "futures-core"))]
+#[tokio::test]
+async fn pipe_passes_bytes_through() {
+    use futures::future::poll_fn;
+    use futures::StreamExt;
+    use std::pin::Pin;
+    use tokio::io::AsyncWrite;
+
+    let (mut writer, reader) = crate::pipe(8, 16);
+    let blob = Bytes::from(vec![0xa5; 20]);
+    let producer = async move {
+        let n = poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, b"Hello"))
+            .await
+            .unwrap();
+        assert_eq!(n, 5);
+        writer.write_bytes(blob).await.unwrap();
+        // This write needs to wait for the reader to consume the data.
+        let mut data = &[b'.'; 20][..];
+        while !data.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, data))
+                .await
+                .unwrap();
+            data = &data[n..];
+        }
+    };
+    let consumer = reader.collect::<Vec<_>>();
+    let ((), chunks) = tokio::join!(producer, consumer);
+    assert_eq!(chunks[0], "Hello");
+    assert_eq!(chunks[1], [0xa5; 20][..]);
+    let tail = chunks[2..].iter().map(|c| c.len()).sum::<usize>();
+    assert_eq!(tail, 20);
+}
+
+#[cfg(feature = "futures-io")]
+#[tokio::test]
+async fn buf_writer_buffers_small_writes() {
+    use crate::ChunkedBufWriter;
+    use futures::io::AsyncWriteExt;
+
+    let mut writer = ChunkedBufWriter::with_capacity(16, Vec::new());
+    writer.write_all(b"Hello, ").await.unwrap();
+    writer.write_all(b"world!").await.unwrap();
+    assert_eq!(writer.buffer().remaining(), 13);
+    assert!(writer.get_ref().is_empty());
+    writer.write_all(b"!!!!").await.unwrap();
+    assert_eq!(writer.get_ref(), b"Hello, world!");
+    writer.write_all(&[b'.'; 4096]).await.unwrap();
+    assert!(!writer.buffer().has_remaining());
+    assert_eq!(writer.get_ref().len(), 13 + 4 + 4096);
+    writer.close().await.unwrap();
+}
+
+#[cfg(feature = "futures-io")]
+#[tokio::test]
+async fn futures_io_async_write_appends() {
+    use futures::io::AsyncWriteExt;
+
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(4);
+    buf.write_all(b"Hello, world!").await.unwrap();
+    buf.close().await.unwrap();
+    assert_eq!(buf.copy_to_bytes(13), "Hello, world!");
+}
+
+#[cfg(feature = "futures-io")]
+#[tokio::test]
+async fn futures_io_read_and_write() {
+    use crate::async_futures::{poll_read_from, write_all_to};
+    use futures::future::poll_fn;
+    use std::pin::Pin;
+
+    let data = (0..20).collect::<Vec<u8>>();
+    let mut src = &data[..];
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    while poll_fn(|cx| poll_read_from(&mut buf, cx, Pin::new(&mut src)))
+        .await
+        .unwrap()
+        != 0
+    {}
+    assert_eq!(buf.chunk_count(), 2);
+    let mut out = Vec::new();
+    write_all_to(&mut buf, &mut out).await.unwrap();
+    assert!(!buf.has_remaining());
+    assert_eq!(out, data);
+}
+
+#[test]
+fn watermarks() {
+    let mut buf = loosely::ChunkedBytes::new();
+    assert!(!buf.is_above_high_watermark());
+    assert!(buf.is_below_low_watermark());
+    buf.set_watermarks(4, 8);
+    buf.put_slice(&[0; 6]);
+    assert!(!buf.is_above_high_watermark());
+    assert!(!buf.is_below_low_watermark());
+    buf.put_bytes(Bytes::from_static(&[0; 2]));
+    assert!(buf.is_above_high_watermark());
+    buf.advance(4);
+    assert!(buf.is_below_low_watermark());
+}
+
+#[test]
+fn chunk_observer_sees_new_chunks() {
+    use std::sync::{Arc, Mutex};
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    buf.set_chunk_observer({
+        let seen = seen.clone();
+        move |len| seen.lock().unwrap().push(len)
+    });
+    buf.put_slice(b"foo");
+    buf.put_bytes(Bytes::from(vec![0; 10]));
+    buf.flush();
+    buf.clear_chunk_observer();
+    buf.put_bytes(Bytes::from_static(b"bar"));
+    assert_eq!(*seen.lock().unwrap(), [3, 8, 2]);
+}
+
+#[test]
+fn read_to_chunked_reads_whole_input() {
+    use crate::ReadChunkedExt;
+
+    let data = (0..10000u32).map(|i| i as u8).collect::<Vec<u8>>();
+    let mut buf = (&data[..]).read_to_chunked().unwrap();
+    assert_eq!(buf.remaining(), data.len());
+    assert!(buf.chunk_count() > 0);
+    assert_eq!(buf.copy_to_bytes(data.len()), data);
+
+    let mut buf = (&data[..]).read_chunked_exact(100).unwrap();
+    assert_eq!(buf.copy_to_bytes(100), &data[..100]);
+
+    let err = (&data[..10]).read_chunked_exact(100).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn write_all_chunked_retries_and_fails_on_zero() {
+    use crate::WriteChunkedExt;
+
+    struct Flaky {
+        out: Vec<u8>,
+        interrupt: bool,
+        capacity: usize,
+    }
+
+    impl io::Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let len = min(min(buf.len(), 3), self.capacity - self.out.len());
+            self.out.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(4);
+    buf.put_slice(b"Hello, world!");
+    let mut out = Flaky {
+        out: Vec::new(),
+        interrupt: false,
+        capacity: 100,
+    };
+    out.write_all_chunked(&mut buf).unwrap();
+    assert!(!buf.has_remaining());
+    assert_eq!(out.out, b"Hello, world!");
+
+    buf.put_slice(b"Hello, world!");
+    let mut out = Flaky {
+        out: Vec::new(),
+        interrupt: false,
+        capacity: 5,
+    };
+    let err = out.write_all_chunked(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    assert_eq!(buf.remaining(), 8);
+}
+
+#[test]
+fn sync_buf_writer_keeps_bytes() {
+    use crate::ChunkedBufWriter;
+    use std::io::Write;
+
+    let blob = Bytes::from(vec![0xa5; 1000]);
+    let mut writer = ChunkedBufWriter::with_capacity(2000, Vec::new());
+    writer.write_all(b"Hello").unwrap();
+    writer.write_bytes(blob.clone()).unwrap();
+    assert_eq!(writer.buffer().remaining(), 1005);
+    assert_eq!(writer.buffer().chunk_count(), 2);
+    assert!(writer.get_ref().is_empty());
+    writer.flush().unwrap();
+    assert!(!writer.buffer().has_remaining());
+    let out = writer.get_ref();
+    assert_eq!(&out[..5], b"Hello");
+    assert_eq!(out[5..], blob);
+}
+
+#[test]
+fn sync_buf_writer_passes_through_writes_not_fitting() {
+    use crate::ChunkedBufWriter;
+    use std::io::Write;
+
+    let mut writer = ChunkedBufWriter::new(Vec::new());
+    writer.write_all(&[0; 100]).unwrap();
+    let staging_capacity = writer.buffer().staging_capacity();
+    let len = writer.buffer().chunk_size_hint() - 1;
+    assert!(len > staging_capacity - 100);
+    writer.write_all(&vec![1; len]).unwrap();
+    assert!(!writer.buffer().has_remaining());
+    assert_eq!(writer.get_ref().len(), 100 + len);
+}
+
+#[test]
+fn poll_capacity_wakes_on_advance() {
+    use futures::task::{waker, ArcWake};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl ArcWake for Flag {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let flag = Arc::new(Flag::default());
+    let waker = waker(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut buf = loosely::ChunkedBytes::new();
+    buf.set_watermarks(0, 10);
+    buf.put_slice(&[0; 8]);
+    assert_eq!(buf.poll_capacity(&mut cx, 2), Poll::Ready(()));
+    assert_eq!(buf.poll_capacity(&mut cx, 4), Poll::Pending);
+    buf.advance(1);
+    assert!(flag.0.load(Ordering::SeqCst));
+    assert_eq!(buf.poll_capacity(&mut cx, 4), Poll::Pending);
+    buf.advance(1);
+    assert_eq!(buf.poll_capacity(&mut cx, 4), Poll::Ready(()));
+}
+
+#[test]
+fn cursor_seeks_across_chunks() {
+    use crate::ChunkedCursor;
+    use std::io::{BufRead, Read, Seek, SeekFrom};
+
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(4);
+    buf.put_slice(b"0123456789");
+    let mut cursor = ChunkedCursor::from(buf);
+    assert_eq!(cursor.len(), 10);
+    assert_eq!(cursor.seek(SeekFrom::Start(3)).unwrap(), 3);
+    assert_eq!(cursor.fill_buf().unwrap(), b"3");
+    let mut dst = [0; 6];
+    assert_eq!(cursor.read(&mut dst).unwrap(), 6);
+    assert_eq!(&dst, b"345678");
+    assert_eq!(cursor.seek(SeekFrom::End(-8)).unwrap(), 2);
+    assert_eq!(cursor.seek(SeekFrom::Current(-1)).unwrap(), 1);
+    let mut rest = Vec::new();
+    cursor.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"123456789");
+    assert!(cursor.seek(SeekFrom::Current(-11)).is_err());
+    cursor.set_position(20);
+    assert_eq!(cursor.read(&mut dst).unwrap(), 0);
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn send_mmsg_sends_gathered_messages() -> io::Result<()> {
+    use crate::unix::send_mmsg;
+    use std::net::UdpSocket;
+
+    let receiver = UdpSocket::bind("127.0.0.1:0")?;
+    let sender = UdpSocket::bind("127.0.0.1:0")?;
+    sender.connect(receiver.local_addr()?)?;
+    let mut msgs = vec![
+        loosely::ChunkedBytes::with_chunk_size_hint(4),
+        loosely::ChunkedBytes::with_chunk_size_hint(4),
+    ];
+    msgs[0].put_slice(b"Hello, ");
+    msgs[0].put_bytes(Bytes::from_static(b"world!"));
+    msgs[1].put_slice(b"Goodbye");
+    assert_eq!(send_mmsg(&sender, &mut msgs)?, 2);
+    assert!(msgs.iter().all(|msg| msg.is_empty()));
+    let mut dst = [0; 32];
+    let len = receiver.recv(&mut dst)?;
+    assert_eq!(&dst[..len], b"Hello, world!");
+    let len = receiver.recv(&mut dst)?;
+    assert_eq!(&dst[..len], b"Goodbye");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn write_to_at_writes_at_offset() -> io::Result<()> {
+    use std::fs::{self, OpenOptions};
+
+    let path = std::env::temp_dir()
+        .join(format!("chunked-bytes-write-at-{}", std::process::id()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(4);
+    buf.put_slice(b"Hello, ");
+    buf.put_bytes(Bytes::from_static(b"world!"));
+    assert_eq!(buf.write_to_at(&file, 3)?, 13);
+    assert!(buf.is_empty());
+    buf.put_slice(b"abc");
+    assert_eq!(buf.write_to_at(&file, 0)?, 3);
+    let contents = fs::read(&path);
+    fs::remove_file(&path)?;
+    assert_eq!(contents?, b"abcHello, world!");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn owned_io_vec_holds_chunks() {
+    use crate::OwnedIoVec;
+
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(4);
+    buf.put_slice(b"Hello, world!");
+    let io_vec = OwnedIoVec::from(buf);
+    assert_eq!(io_vec.len(), 13);
+    let slices = io_vec.as_io_slices();
+    assert_eq!(slices.len(), 4);
+    let data = slices.iter().flat_map(|s| s.iter().copied());
+    assert_eq!(data.collect::<Vec<_>>(), b"Hello, world!");
+    assert_eq!(io_vec.as_iovecs()[3].iov_len, 1);
+    #[cfg(feature = "monoio")]
+    {
+        use monoio::buf::IoVecBuf;
+        assert_eq!(io_vec.read_iovec_len(), 4);
+        assert_eq!(
+            io_vec.read_iovec_ptr() as *const u8,
+            slices.as_ptr() as *const u8
+        );
+    }
+}
+
+#[test]
+fn aligned_chunks_start_at_aligned_addresses() {
+    let mut buf = strictly::ChunkedBytes::with_aligned_chunks(512, 512);
+    assert_eq!(buf.alignment(), 512);
+    buf.put_slice(&[0; 1500]);
+    buf.flush();
+    buf.put_slice(&[1; 700]);
+    let chunks = buf.into_chunks().collect::<Vec<_>>();
+    let lens = chunks.iter().map(|c| c.len()).collect::<Vec<_>>();
+    assert_eq!(lens, [512, 512, 476, 512, 188]);
+    for chunk in &chunks {
+        assert_eq!(chunk.as_ptr() as usize % 512, 0);
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn wsa_bufs_refer_to_chunks() {
+    use crate::windows::WsaBufs;
+
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(2);
+    buf.put_slice(&[0; 41]);
+    let bufs = WsaBufs::new(&buf);
+    assert_eq!(bufs.count(), 21);
+    assert_eq!(bufs.data_len(), 41);
+    assert_eq!(bufs.as_slice()[20].len, 1);
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_fills_and_drains() {
+    use crate::ffi::*;
+
+    let data = b"Hello, world!";
+    unsafe {
+        let buf = chunked_bytes_new(4);
+        chunked_bytes_put(buf, data.as_ptr(), data.len());
+        assert_eq!(chunked_bytes_remaining(buf), 13);
+        #[cfg(unix)]
+        {
+            let mut iovecs = [libc::iovec {
+                iov_base: std::ptr::null_mut(),
+                iov_len: 0,
+            }; 8];
+            let n = chunked_bytes_iovecs(buf, iovecs.as_mut_ptr(), 8);
+            let len = iovecs[..n].iter().map(|v| v.iov_len).sum::<usize>();
+            assert_eq!(len, 13);
+        }
+        assert_eq!(chunked_bytes_advance(buf, 7), 7);
+        assert_eq!(chunked_bytes_advance(buf, 10), 6);
+        assert_eq!(chunked_bytes_remaining(buf), 0);
+        chunked_bytes_free(buf);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(2);
+    buf.put_slice(&[1, 2, 3, 4, 5]);
+    let json = serde_json::to_string(&buf).unwrap();
+    assert_eq!(json, "[1,2,3,4,5]");
+    let mut buf: loosely::ChunkedBytes = serde_json::from_str(&json).unwrap();
+    assert_eq!(buf.copy_to_bytes(5), [1, 2, 3, 4, 5][..]);
+}
+
+#[cfg(feature = "zeroize")]
+#[test]
+fn zeroize_erases_consumed_staging() {
+    let mut buf = loosely::ChunkedBytes::new();
+    buf.set_zeroize(true);
+    buf.put_slice(b"secret");
+    let ptr = buf.chunk().as_ptr();
+    buf.advance(3);
+    // The consumed bytes remain in the allocation of the staging buffer
+    let consumed = unsafe { std::slice::from_raw_parts(ptr, 3) };
+    assert_eq!(consumed, [0; 3]);
+    assert_eq!(buf.chunk(), b"ret");
+}
+
+#[cfg(feature = "zeroize")]
+#[test]
+fn zeroize_erases_consumed_owned_chunks() {
+    let mut buf = loosely::ChunkedBytes::new();
+    buf.set_zeroize(true);
+    buf.put_slice(b"secret");
+    buf.flush();
+    let shared = Bytes::from(vec![1; 1000]);
+    buf.put_bytes(shared.clone());
+    buf.put_slice(b"more");
+    let ptr = buf.chunk().as_ptr();
+    buf.advance(3);
+    // The flushed chunk shares the allocation with the staging buffer
+    let chunk = unsafe { std::slice::from_raw_parts(ptr, 6) };
+    assert_eq!(chunk, b"\0\0\0ret");
+    buf.advance(1003);
+    assert_eq!(chunk, [0; 6]);
+    assert_eq!(shared, vec![1; 1000]);
+    assert_eq!(buf.chunk(), b"more");
+}
+
+#[cfg(feature = "subtle")]
+#[test]
+fn ct_eq_compares_across_chunks() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(3);
+    buf.put_slice(b"Hello, ");
+    buf.put_bytes(Bytes::from_static(b"world!"));
+    assert!(bool::from(buf.ct_eq(b"Hello, world!")));
+    assert!(!bool::from(buf.ct_eq(b"Hello, World!")));
+    assert!(!bool::from(buf.ct_eq(b"Hello")));
+}
+
+#[test]
+fn chunk_queue_spills_in_order() {
+    let chunks = (0..5u8).map(|i| Bytes::from(vec![i; 2]));
+    let mut buf = loosely::ChunkedBytes::from_chunks(chunks);
+    assert_eq!(buf.chunk_count(), 5);
+    buf.advance(5);
+    assert_eq!(buf.chunk(), [2]);
+    let drained = buf.drain_chunks().collect::<Vec<_>>();
+    assert_eq!(drained, [&[2][..], &[3, 3], &[4, 4]]);
+    buf.put_bytes(Bytes::from_static(&[5]));
+    assert_eq!(buf.into_chunks().collect::<Vec<_>>(), [&[5][..]]);
+}
+
+#[test]
+fn put_bytes_copies_small_slices() {
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(64);
+    buf.put_slice(b"foo");
+    for _ in 0..4 {
+        buf.put_bytes(Bytes::from_static(b"bar"));
+    }
+    assert_eq!(buf.chunk_count(), 0);
+    assert_eq!(buf.chunk(), b"foobarbarbarbar");
+    let large = Bytes::from(vec![0; 8]);
+    buf.put_bytes(large.clone());
+    assert_eq!(buf.chunk_count(), 2);
+    buf.advance(15);
+    assert_eq!(buf.chunk().as_ptr(), large.as_ptr());
+}
+
+#[test]
+fn copy_threshold_is_configurable() {
+    let mut buf = loosely::ChunkedBytes::new();
+    assert_eq!(buf.copy_threshold(), 512);
+    buf.set_copy_threshold(0);
+    let small = Bytes::from_static(b"foo");
+    buf.put_bytes(small.clone());
+    assert_eq!(buf.chunk_count(), 1);
+    assert_eq!(buf.chunk().as_ptr(), small.as_ptr());
+    buf.set_copy_threshold(2000);
+    buf.put_bytes(Bytes::from(vec![0; 1000]));
+    assert_eq!(buf.chunk_count(), 1);
+    assert_eq!(buf.remaining(), 1003);
+}
+
+#[test]
+fn strictly_caps_large_chunk_lazily() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    buf.put_slice(b"foo");
+    let large = Bytes::from(vec![1; 20]);
+    buf.put_bytes(large.clone());
+    assert_eq!(buf.chunk_count(), 4);
+    assert_eq!(buf.layout().chunks, [3, 8, 8, 4]);
+    let mut slices = [IoSlice::new(&[]); 3];
+    assert_eq!(buf.chunks_vectored(&mut slices), 3);
+    assert_eq!(slices[1].as_ptr(), large.as_ptr());
+    assert_eq!(slices[2].len(), 8);
+    buf.advance(5);
+    assert_eq!(buf.chunk().len(), 8);
+    assert_eq!(buf.chunk().as_ptr(), large[2..].as_ptr());
+    let drained = buf.drain_chunks();
+    assert_eq!(drained.len(), 3);
+    let lens = drained.map(|c| c.len()).collect::<Vec<_>>();
+    assert_eq!(lens, [8, 8, 2]);
+}
+
+#[test]
+fn strictly_min_chunk_size_merges_tiny_pieces() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(16);
+    buf.set_min_chunk_size(4);
+    assert_eq!(buf.min_chunk_size(), 4);
+    buf.put_slice(b"a");
+    buf.put_bytes(Bytes::from_static(b"bc"));
+    buf.put_bytes(Bytes::from_static(b"defghijk"));
+    buf.put_slice(b"l");
+    buf.put_bytes(Bytes::from_static(b"mnopq"));
+    assert_eq!(buf.layout().chunks, [4, 7]);
+    assert_eq!(buf.staging_len(), 6);
+    assert_eq!(buf.copy_to_bytes(17), "abcdefghijklmnopq");
+}
+
+#[test]
+fn compact_merges_small_chunks() {
+    let chunks = [3, 100, 2, 2, 5, 1, 40, 4]
+        .iter()
+        .enumerate()
+        .map(|(i, &len)| Bytes::from(vec![i as u8; len]))
+        .collect::<Vec<_>>();
+    let mut buf = loosely::ChunkedBytes::from_chunks(chunks.clone());
+    buf.compact(8);
+    assert_eq!(buf.layout().chunks, [3, 100, 10, 40, 4]);
+    assert_eq!(buf.chunk().as_ptr(), chunks[0].as_ptr());
+    let expected = chunks.concat();
+    assert_eq!(buf.copy_to_bytes(expected.len()), expected);
+
+    let mut strict = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    for chunk in chunks {
+        strict.put_bytes(chunk);
+    }
+    strict.compact(8);
+    assert_eq!(strict.chunk_count(), 1 + 13 + 2 + 5 + 1);
+    assert_eq!(strict.copy_to_bytes(expected.len()), expected);
+}
+
+#[test]
+fn compact_keeps_storage_chunks_without_copying() {
+    let storage: Arc<[u8]> = vec![7; 100].into();
+    let mut buf = loosely::ChunkedBytes::new();
+    buf.set_copy_threshold(0);
+    buf.put_bytes(Bytes::from_static(b"ab"));
+    buf.put_bytes(Bytes::from_static(b"cd"));
+    buf.put_storage(storage.clone());
+    buf.put_bytes(Bytes::from_static(b"ef"));
+    assert_eq!(buf.layout().chunks, [2, 2, 100, 2]);
+    buf.compact(8);
+    assert_eq!(buf.layout().chunks, [4, 100, 2]);
+    buf.advance(4);
+    assert_eq!(buf.chunk().as_ptr(), storage.as_ptr());
+    assert_eq!(buf.chunk().len(), 100);
+}
+
+#[test]
+fn coalesce_threshold_merges_tiny_chunks() {
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    buf.set_copy_threshold(0);
+    buf.set_coalesce_threshold(4);
+    assert_eq!(buf.coalesce_threshold(), 4);
+    buf.put_slice(b"a");
+    buf.put_bytes(Bytes::from_static(b"bc"));
+    buf.put_bytes(Bytes::from_static(b"d"));
+    buf.put_bytes(Bytes::from_static(b"efghij"));
+    buf.put_bytes(Bytes::from_static(b"k"));
+    assert_eq!(buf.layout().chunks, [4, 6, 1]);
+    assert_eq!(buf.copy_to_bytes(11), "abcdefghijk");
+}
+
+#[test]
+fn loosely_aligned_chunks_start_at_aligned_addresses() {
+    let mut buf = loosely::ChunkedBytes::with_aligned_chunks(256, 64);
+    assert_eq!(buf.alignment(), 64);
+    for i in 0..10u8 {
+        buf.put_slice(&[i; 150]);
+        if i % 3 == 0 {
+            buf.flush();
+        }
+    }
+    buf.flush();
+    assert!(buf.chunk_count() > 4);
+    for chunk in buf.drain_chunks() {
+        assert_eq!(chunk.as_ptr() as usize % 64, 0);
+    }
+}
+
+#[test]
+fn reserve_exact_allocates_chunk_size() {
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(100);
+    buf.set_reserve_exact(true);
+    for _ in 0..5 {
+        buf.put_slice(&[0; 30]);
+        assert!(buf.staging_capacity() <= 100);
+    }
+    buf.advance(50);
+    buf.put_slice(&[1; 90]);
+    assert!(buf.staging_capacity() <= 100);
+    assert_eq!(buf.layout().chunks, [50, 100]);
+    assert_eq!(buf.remaining(), 190);
+}
+
+#[test]
+fn pool_recycles_containers() {
+    use crate::ChunkedBytesPool;
+
+    let pool = ChunkedBytesPool::with_factory(1, || {
+        strictly::ChunkedBytes::with_chunk_size_limit(64)
+    });
+    {
+        let mut buf = pool.get();
+        buf.put_slice(b"hello");
+        buf.put_bytes(Bytes::from(vec![0; 100]));
+        buf.put_slice(b"world");
+    }
+    assert_eq!(pool.idle_count(), 1);
+    let mut buf = pool.get();
+    assert_eq!(pool.idle_count(), 0);
+    assert!(buf.is_empty());
+    assert!(buf.staging_capacity() >= 64);
+    assert_eq!(buf.chunk_size_limit(), 64);
+    buf.put_slice(&[1; 100]);
+    assert_eq!(buf.layout().chunks, [64]);
+    let other = pool.get();
+    drop(buf);
+    drop(other);
+    assert_eq!(pool.idle_count(), 1);
+    let detached = pool.get().detach();
+    assert!(detached.is_empty());
+    assert_eq!(pool.idle_count(), 0);
+}
+
+#[test]
+fn pool_resets_settings_of_returned_containers() {
+    use crate::ChunkedBytesPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let pool = ChunkedBytesPool::with_factory(1, || {
+        let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(64);
+        buf.set_max_len(1000);
+        buf
+    });
+    let observed = Arc::new(AtomicUsize::new(0));
+    {
+        let mut buf = pool.get();
+        buf.put_slice(&[0; 10]);
+        buf.set_max_len(10);
+        buf.set_watermarks(5, 8);
+        buf.set_ring_limit(20);
+        buf.set_coalesce_threshold(16);
+        let observed = observed.clone();
+        buf.set_chunk_observer(move |len| {
+            observed.fetch_add(len, Ordering::Relaxed);
+        });
+    }
+    assert_eq!(pool.idle_count(), 1);
+    let mut buf = pool.get();
+    assert!(buf.is_empty());
+    assert!(buf.staging_capacity() >= 64);
+    assert_eq!(buf.max_len(), 1000);
+    assert_eq!(buf.watermarks(), (0, usize::MAX));
+    assert_eq!(buf.coalesce_threshold(), 0);
+    assert_eq!(buf.evicted_len(), 0);
+    assert_eq!(buf.flush_count(), 0);
+    buf.put_slice(&[1; 100]);
+    assert_eq!(buf.remaining(), 100);
+    assert_eq!(buf.layout().chunks, [64]);
+    assert_eq!(observed.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn staging_alloc_supplies_and_recycles_buffers() {
+    use crate::StagingAlloc;
+    use bytes::BytesMut;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Pool {
+        allocated: Mutex<usize>,
+        recycled: Mutex<Vec<BytesMut>>,
+    }
+
+    impl StagingAlloc for Pool {
+        fn allocate(&self, capacity: usize) -> BytesMut {
+            *self.allocated.lock().unwrap() += 1;
+            BytesMut::with_capacity(capacity)
+        }
+
+        fn recycle(&self, buf: BytesMut) {
+            self.recycled.lock().unwrap().push(buf);
+        }
+    }
+
+    let pool = Arc::new(Pool::default());
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(16);
+    buf.set_staging_alloc(pool.clone());
+    buf.put_slice(&[0; 10]);
+    buf.put_slice(&[1; 30]);
+    assert!(*pool.allocated.lock().unwrap() >= 2);
+    assert_eq!(buf.remaining(), 40);
+    let recycled = pool.recycled.lock().unwrap().len();
+    drop(buf);
+    let recycled_bufs = pool.recycled.lock().unwrap();
+    assert_eq!(recycled_bufs.len(), recycled + 1);
+    assert!(recycled_bufs.iter().all(|b| b.is_empty()));
+}
+
+#[test]
+fn memory_budget_is_shared() {
+    use crate::MemoryBudget;
+
+    let budget = MemoryBudget::new(100);
+    let mut a = loosely::ChunkedBytes::new();
+    let mut b = strictly::ChunkedBytes::new();
+    a.set_memory_budget(Some(budget.clone()));
+    b.set_memory_budget(Some(budget.clone()));
+    a.put_slice(&[0; 40]);
+    a.put_bytes(Bytes::from(vec![1; 20]));
+    b.put_slice(&[2; 30]);
+    assert_eq!(budget.used(), 90);
+    assert_eq!(b.remaining_mut(), 10);
+    assert!(a.try_put_slice(&[3; 11]).is_err());
+    a.advance(50);
+    assert_eq!(budget.used(), 40);
+    b.try_put_slice(&[3; 20]).unwrap();
+    drop(a);
+    assert_eq!(budget.used(), 50);
+    b.drain_chunks();
+    b.clear();
+    assert_eq!(budget.used(), 0);
+}
+
+#[cfg(feature = "spill")]
+#[test]
+fn spill_buffer_reads_back_spilled_data() {
+    use crate::SpillBuffer;
+    use std::io::{Read, Write};
+
+    let mut buf = SpillBuffer::with_chunk_size_hint(100, 32);
+    let data = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+    for piece in data[..500].chunks(7) {
+        buf.write_all(piece).unwrap();
+    }
+    buf.put_bytes(Bytes::copy_from_slice(&data[500..600]))
+        .unwrap();
+    assert!(buf.spilled_len() > 0);
+    assert_eq!(buf.len(), 600);
+    let mut out = vec![0; 300];
+    buf.read_exact(&mut out).unwrap();
+    assert_eq!(out, data[..300]);
+    buf.write_all(&data[600..]).unwrap();
+    let mut rest = Vec::new();
+    buf.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, data[300..]);
+    assert!(buf.is_empty());
+    assert_eq!(buf.spilled_len(), 0);
+}
+
+#[test]
+fn ring_limit_evicts_oldest_chunks() {
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(10);
+    buf.set_ring_limit(25);
+    for i in 0..10u8 {
+        buf.put_slice(&[i; 5]);
+    }
+    assert_eq!(buf.evicted_len(), 30);
+    assert_eq!(buf.layout().chunks, [10]);
+    let rest = buf.copy_to_bytes(buf.remaining());
+    assert_eq!(rest[..], [[6; 5], [7; 5], [8; 5], [9; 5]].concat());
+}
+
+#[test]
+fn find_matches_contiguous_search() {
+    let data = b"abcabdabcabcdabd";
+    let needles: &[&[u8]] = &[
+        b"abd",
+        b"cabc",
+        b"bcd",
+        b"dabd",
+        b"abcabdabcabcdabd",
+        b"abe",
+    ];
+    for split in 1..data.len() {
+        let chunks = data.chunks(split).map(Bytes::copy_from_slice);
+        let buf = loosely::ChunkedBytes::from_chunks(chunks);
+        for needle in needles {
+            let expected =
+                data.windows(needle.len()).position(|w| w == *needle);
+            assert_eq!(buf.find(needle), expected, "split by {}", split);
+        }
+    }
+}
+
+#[test]
+fn is_utf8_across_chunk_boundaries() {
+    let valid = "aé€𝄞z".as_bytes();
+    assert!(loosely::ChunkedBytes::new().is_utf8());
+    for split in 1..valid.len() {
+        let chunks = valid.chunks(split).map(Bytes::copy_from_slice);
+        let buf = strictly::ChunkedBytes::from_chunks(chunks);
+        assert!(buf.is_utf8(), "split by {}", split);
+    }
+    let invalid: &[&[u8]] = &[
+        b"\xE2\x82",
+        b"a\xF0\x9D\x84z",
+        b"\xC3\x28ab",
+        b"ab\xFF",
+        b"\xED\xA0\x80",
+    ];
+    for data in invalid {
+        for split in 1..=data.len() {
+            let chunks = data.chunks(split).map(Bytes::copy_from_slice);
+            let buf = loosely::ChunkedBytes::from_chunks(chunks);
+            assert!(!buf.is_utf8(), "{:x?} split by {}", data, split);
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_chunks_preserve_order() {
+    use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+    let data = (0..=255u8).cycle().take(5000).collect::<Vec<_>>();
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(256);
+    for chunk in data.chunks(700) {
+        buf.put_bytes(Bytes::copy_from_slice(chunk));
+    }
+    let par = buf.par_chunks();
+    assert_eq!(par.len(), buf.chunk_count());
+    let slices = par.collect::<Vec<_>>();
+    assert!(slices.iter().all(|s| s.len() <= 256));
+    assert_eq!(slices.concat(), data);
+
+    let sum = buf.par_fold(
+        || 0u64,
+        |acc, s| acc + s.iter().map(|&b| b as u64).sum::<u64>(),
+        |a, b| a + b,
+    );
+    assert_eq!(sum, data.iter().map(|&b| b as u64).sum::<u64>());
+    let concat = buf.par_fold(
+        Vec::new,
+        |mut acc, s| {
+            acc.extend_from_slice(s);
+            acc
+        },
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+    );
+    assert_eq!(concat, data);
+}
+
+#[test]
+fn rope_clones_and_concatenates() {
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    buf.put_bytes(Bytes::from_static(b"hello, "));
+    buf.put_slice(b"world");
+    let rope = buf.freeze();
+    assert_eq!(rope.len(), 12);
+
+    let mut reader = rope.clone();
+    assert_eq!(reader.copy_to_bytes(7), "hello, ");
+    assert_eq!(reader.chunk(), b"world");
+    assert_eq!(rope.len(), 12);
+
+    let bang = strictly::ChunkedBytes::from_chunks(vec![
+        Bytes::from_static(b"!"),
+        Bytes::from_static(b"!"),
+    ])
+    .freeze();
+    let both = rope.concat(&bang).concat(&reader);
+    assert_eq!(both.len(), 19);
+    let mut slices = [IoSlice::new(&[]); 8];
+    let n = both.chunks_vectored(&mut slices);
+    let gathered = slices[..n].iter().flat_map(|s| s.iter().copied());
+    assert_eq!(gathered.collect::<Vec<_>>(), b"hello, world!!world");
+
+    let mut reader = both.clone();
+    reader.advance(10);
+    assert_eq!(reader.chunk(), b"ld");
+    assert_eq!(reader.copy_to_bytes(5), "ld!!w");
+    let n = reader.chunks_vectored(&mut slices);
+    assert_eq!(n, 1);
+    assert_eq!(&slices[0][..], b"orld");
+    let tail = reader.concat(&bang);
+    assert_eq!(tail.clone().copy_to_bytes(6), "orld!!");
+    reader.advance(4);
+    assert!(reader.is_empty());
+    assert_eq!(reader.chunk(), b"");
+    assert_eq!(reader.chunks_vectored(&mut [IoSlice::new(&[])]), 0);
+}
+
+#[test]
+fn rope_survives_deep_concatenation() {
+    let piece =
+        loosely::ChunkedBytes::with_first_chunk(Bytes::from_static(b"ab"))
+            .freeze();
+    let mut rope = ChunkedRope::default();
+    let mut prepended = ChunkedRope::default();
+    for _ in 0..100_000 {
+        rope = rope.concat(&piece);
+        prepended = piece.concat(&prepended);
+    }
+    assert_eq!(rope.len(), 200_000);
+    assert_eq!(prepended.len(), 200_000);
+    let mut reader = rope.clone();
+    let mut count = 0;
+    while reader.has_remaining() {
+        assert_eq!(reader.chunk(), b"ab");
+        reader.advance(2);
+        count += 1;
+    }
+    assert_eq!(count, 100_000);
+    let mut reader = prepended.clone();
+    reader.advance(1);
+    while reader.remaining() > 1 {
+        assert_eq!(reader.copy_to_bytes(2), "ba");
+    }
+    drop(rope);
+    drop(prepended);
+
+    // Pieces too large to be merged make the tree grow
+    let piece = loosely::ChunkedBytes::from_chunks(
+        (0..20u8).map(|i| Bytes::from(vec![i; 3])),
+    )
+    .freeze();
+    let mut rope = ChunkedRope::default();
+    for _ in 0..10_000 {
+        rope = rope.concat(&piece);
+    }
+    assert_eq!(rope.len(), 600_000);
+    let mut reader = rope.slice(30..);
+    for i in 0..199_990 {
+        assert_eq!(reader.chunk(), [((i + 10) % 20) as u8; 3]);
+        reader.advance(3);
+    }
+    assert!(reader.is_empty());
+    let mut reader = rope.clone();
+    reader.advance(599_999);
+    assert_eq!(reader.chunk(), [19]);
+}
+
+#[test]
+fn rope_concatenation_scales() {
+    use crate::rope::CONCAT_NODES;
+
+    let piece = loosely::ChunkedBytes::from_chunks(
+        (0..17u8).map(|i| Bytes::from(vec![i; 2])),
+    )
+    .freeze();
+    let count: usize = 40_000;
+    let before = CONCAT_NODES.with(|nodes| nodes.get());
+    let mut rope = ChunkedRope::default();
+    for _ in 0..count {
+        rope = rope.concat(&piece);
+        rope = piece.concat(&rope);
+    }
+    let allocated = CONCAT_NODES.with(|nodes| nodes.get()) - before;
+    // Each concatenation rebuilds a bounded number of nodes on every
+    // level of a balanced tree
+    let levels = (usize::BITS - (2 * count).leading_zeros()) as usize;
+    assert!(
+        allocated <= 2 * count * 2 * levels,
+        "{} nodes allocated",
+        allocated
+    );
+    assert_eq!(rope.len(), 2 * count * 34);
+    let mut reader = rope.slice(34 * count - 2..);
+    assert_eq!(reader.copy_to_bytes(4), [16, 16, 0, 0][..]);
+}
+
+#[test]
+fn rope_slices_share_chunks() {
+    let first = Bytes::from_static(b"hello, ");
+    let rope = loosely::ChunkedBytes::from_chunks(vec![
+        first.clone(),
+        Bytes::from_static(b"wor"),
+        Bytes::from_static(b"ld"),
+    ])
+    .freeze()
+    .concat(
+        &strictly::ChunkedBytes::with_first_chunk(Bytes::from_static(b"!"))
+            .freeze(),
+    );
+
+    let mut slice = rope.slice(2..11);
+    assert_eq!(slice.len(), 9);
+    assert_eq!(slice.chunks().len(), 3);
+    assert_eq!(slice.chunk().as_ptr(), first[2..].as_ptr());
+    assert_eq!(slice.copy_to_bytes(5), "llo, ");
+    assert_eq!(slice.chunk(), b"wor");
+    slice.advance(2);
+    assert_eq!(slice.copy_to_bytes(2), "rl");
+    assert!(slice.is_empty());
+    assert!(slice.chunks().is_empty());
+
+    assert_eq!(rope.slice(..).len(), 13);
+    assert_eq!(rope.slice(12..=12).copy_to_bytes(1), "!");
+    assert!(rope.slice(5..5).is_empty());
+    let mut consumed = rope.clone();
+    consumed.advance(7);
+    let mut tail = ChunkedRope::from(consumed.slice(3..));
+    assert_eq!(tail.copy_to_bytes(3), "ld!");
+}
+
+#[test]
+fn try_into_bytes_without_copying() {
+    use std::convert::TryFrom;
+
+    let chunk = Bytes::from(vec![1; 32]);
+    let buf = loosely::ChunkedBytes::with_first_chunk(chunk.clone());
+    let bytes = Bytes::try_from(buf).unwrap();
+    assert_eq!(bytes.as_ptr(), chunk.as_ptr());
+
+    let mut buf = strictly::ChunkedBytes::new();
+    buf.put_slice(b"staged");
+    assert_eq!(Bytes::try_from(buf).unwrap(), "staged");
+    assert!(Bytes::try_from(loosely::ChunkedBytes::new())
+        .unwrap()
+        .is_empty());
+
+    let mut buf = loosely::ChunkedBytes::with_first_chunk(chunk);
+    buf.put_slice(b"staged");
+    let mut buf = Bytes::try_from(buf).unwrap_err();
+    assert_eq!(buf.remaining(), 38);
+    buf.advance(32);
+    assert_eq!(Bytes::try_from(buf).unwrap(), "staged");
+
+    let mut buf = strictly::ChunkedBytes::new();
+    buf.put_storage(vec![0; 100]);
+    let buf = Bytes::try_from(buf).unwrap_err();
+    assert_eq!(buf.remaining(), 100);
+}
+
+#[test]
+fn bytes_storage_is_handed_out_without_copying() {
+    let chunk = Bytes::from(vec![1; 32]);
+    let mut buf = loosely::ChunkedBytesWith::<Bytes>::with_chunk_size_hint(8);
+    buf.put_slice(b"staged");
+    buf.put_storage(chunk.clone());
+    buf.advance(6);
+    let head = buf.split_to(8);
+    assert_eq!(head.chunk().as_ptr(), chunk.as_ptr());
+    let mut chunks = buf.into_chunks();
+    let rest = chunks.next().unwrap();
+    assert_eq!(rest.as_ptr(), chunk[8..].as_ptr());
+    assert_eq!(rest.len(), 24);
+    assert!(chunks.next().is_none());
+
+    let mut buf = strictly::ChunkedBytesWith::<Bytes>::with_chunk_size_limit(8);
+    buf.put_storage(chunk.clone());
+    assert_eq!(buf.copy_to_bytes(4).as_ptr(), chunk.as_ptr());
+    let pieces = buf.drain_chunks().collect::<Vec<_>>();
+    assert_eq!(pieces.len(), 4);
+    for (i, piece) in pieces.iter().enumerate() {
+        assert_eq!(piece.as_ptr(), chunk[4 + i * 8..].as_ptr());
+    }
+}
+
+#[test]
+fn length_delimited_frames() {
+    use crate::framing::LengthDelimited;
+
+    let framer = LengthDelimited::new();
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    let payload = Bytes::from(vec![7; 64]);
+    framer.write_frame(&mut buf, payload.clone()).unwrap();
+    framer
+        .write_frame_with(&mut buf, |dst| dst.put_slice(b"hello"))
+        .unwrap();
+    assert_eq!(buf.remaining(), 4 + 64 + 4 + 5);
+
+    let mut frame = framer.read_frame(&mut buf).unwrap().unwrap();
+    assert_eq!(frame.remaining(), 64);
+    assert_eq!(frame.chunk().as_ptr(), payload.as_ptr());
+    frame.advance(64);
+    assert_eq!(framer.read_frame_bytes(&mut buf).unwrap().unwrap(), "hello");
+    assert!(buf.is_empty());
+
+    // Incomplete frames are left in the buffer
+    let framer = LengthDelimited::with_prefix_width(2);
+    buf.put_slice(&[0]);
+    assert!(framer.read_frame(&mut buf).unwrap().is_none());
+    buf.put_slice(&[3, b'a', b'b']);
+    assert!(framer.read_frame_bytes(&mut buf).unwrap().is_none());
+    assert_eq!(buf.remaining(), 4);
+    buf.put_slice(b"c");
+    assert_eq!(framer.read_frame_bytes(&mut buf).unwrap().unwrap(), "abc");
+
+    let mut framer = LengthDelimited::with_prefix_width(1);
+    let err = framer
+        .write_frame(&mut buf, Bytes::from(vec![0; 256]))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(buf.is_empty());
+    framer.set_max_frame_len(4);
+    buf.put_slice(&[5, 0, 0, 0, 0, 0]);
+    let err = framer.read_frame(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn cobs_round_trip() {
+    use crate::cobs::Cobs;
+
+    let cobs = Cobs::new();
+    let mut data = vec![0, 1, 2, 0, 0, 3];
+    data.extend_from_slice(&[7; 300]);
+    data.push(0);
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(16);
+    cobs.encode_frame(&data[..], &mut buf);
+    cobs.encode_frame(&[][..], &mut buf);
+    cobs.encode_frame(Bytes::from_static(&[0xff; 254]), &mut buf);
+    assert_eq!(buf.find_byte(0), Some(data.len() + 2));
+
+    // Feed the encoded data in pieces
+    let mut encoded = buf.copy_to_bytes(buf.remaining());
+    let mut received = loosely::ChunkedBytes::with_chunk_size_hint(16);
+    received.put_u8(0);
+    let mut frames = Vec::new();
+    while encoded.has_remaining() {
+        received.put_bytes(encoded.split_to(min(encoded.len(), 50)));
+        while let Some(mut frame) = cobs.decode_frame(&mut received).unwrap() {
+            frames.push(frame.copy_to_bytes(frame.remaining()));
+        }
+    }
+    assert!(received.is_empty());
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0], data);
+    assert!(frames[1].is_empty());
+    assert_eq!(frames[2], &[0xff; 254][..]);
+
+    received.put_slice(&[3, 1, 0]);
+    let err = cobs.decode_frame(&mut received).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(received.is_empty());
+    let mut cobs = cobs;
+    cobs.set_max_frame_len(4);
+    received.put_slice(&[6, 1, 2, 3, 4, 5]);
+    let err = cobs.decode_frame(&mut received).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(received.is_empty());
+}
+
+#[test]
+fn websocket_frames_masked_in_place() {
+    use crate::websocket::{FrameHeader, Opcode};
+
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(8);
+    FrameHeader::new(Opcode::Text).write_frame(&mut buf, Bytes::from("hi"));
+    assert_eq!(buf.copy_to_bytes(4), &[0x81, 2, b'h', b'i'][..]);
+
+    let key = [1, 2, 3, 4];
+    let header = FrameHeader {
+        fin: false,
+        opcode: Opcode::Binary,
+        mask: Some(key),
+    };
+    let shared = Bytes::from(vec![0xf0; 200]);
+    header.write_frame(&mut buf, shared.clone());
+    assert_eq!(buf.copy_to_bytes(8), &[0x02, 0xfe, 0, 200, 1, 2, 3, 4][..]);
+    let masked = buf.copy_to_bytes(200);
+    assert!(masked
+        .iter()
+        .enumerate()
+        .all(|(i, b)| *b == 0xf0 ^ key[i % 4]));
+    assert!(shared.iter().all(|b| *b == 0xf0));
+
+    header.write_frame_with(&mut buf, |dst| {
+        dst.put_slice(&[0; 20]);
+        dst.put_bytes(shared.clone());
+    });
+    assert_eq!(buf.copy_to_bytes(4), &[0x02, 0xfe, 0, 220][..]);
+    assert_eq!(buf.copy_to_bytes(4), &key[..]);
+    let masked = buf.copy_to_bytes(220);
+    assert!(masked[..20]
+        .iter()
+        .enumerate()
+        .all(|(i, b)| *b == key[i % 4]));
+    assert!(masked[20..]
+        .iter()
+        .enumerate()
+        .all(|(i, b)| *b == 0xf0 ^ key[i % 4]));
+    assert!(!buf.has_remaining());
+}
+
+#[test]
+fn grpc_messages() {
+    use crate::grpc::MessageFraming;
+
+    let framing = MessageFraming::new();
+    let body = Bytes::from(vec![5; 100]);
+    let mut buf = loosely::ChunkedBytes::with_chunk_size_hint(16);
+    framing
+        .write_message(&mut buf, false, body.clone())
+        .unwrap();
+    framing
+        .write_message(&mut buf, true, &b"zipped"[..])
+        .unwrap();
+    assert_eq!(buf.remaining(), 5 + 100 + 5 + 6);
+
+    let mut msg = framing.read_message(&mut buf).unwrap().unwrap();
+    assert!(!msg.compressed);
+    assert_eq!(msg.data.chunk().as_ptr(), body.as_ptr());
+    assert_eq!(msg.data.copy_to_bytes(100), body);
+    let mut received = loosely::ChunkedBytes::new();
+    received.put_bytes(buf.copy_to_bytes(8));
+    assert!(framing.read_message(&mut received).unwrap().is_none());
+    received.put_bytes(buf.copy_to_bytes(3));
+    let mut msg = framing.read_message(&mut received).unwrap().unwrap();
+    assert!(msg.compressed);
+    assert_eq!(msg.data.copy_to_bytes(6), "zipped");
+    assert!(received.is_empty());
+
+    received.put_slice(&[2, 0, 0, 0, 0]);
+    let err = framing.read_message(&mut received).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let mut framing = framing;
+    framing.set_max_message_len(10);
+    received.clear();
+    received.put_slice(&[0, 0, 0, 0, 11]);
+    let err = framing.read_message(&mut received).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn base64_streaming() {
+    use crate::base64::{Base64Decoder, Base64Encoder};
+    use ::base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+    use ::base64::{DecodeError, Engine};
+
+    let data: Vec<u8> = (0..=255).cycle().take(2000).collect();
+    let mut encoder = Base64Encoder::new();
+    let mut encoded = loosely::ChunkedBytes::with_chunk_size_hint(64);
+    for piece in data.chunks(7) {
+        let (a, b) = piece.split_at(piece.len() / 2);
+        encoder.encode(a.chain(b), &mut encoded);
+    }
+    encoder.finish(&mut encoded);
+    let encoded = encoded.copy_to_bytes(encoded.remaining());
+    assert_eq!(encoded, STANDARD.encode(&data));
+
+    let mut decoder = Base64Decoder::new();
+    let mut decoded = loosely::ChunkedBytes::new();
+    for piece in encoded.chunks(5) {
+        decoder.decode(piece, &mut decoded).unwrap();
+    }
+    decoder.finish(&mut decoded).unwrap();
+    assert_eq!(decoded.copy_to_bytes(decoded.remaining()), data);
+
+    let mut decoder = Base64Decoder::with_engine(STANDARD_NO_PAD);
+    decoder.decode(&b"aGVsbG"[..], &mut decoded).unwrap();
+    decoder.decode(&b"8"[..], &mut decoded).unwrap();
+    decoder.finish(&mut decoded).unwrap();
+    assert_eq!(decoded.copy_to_bytes(5), "hello");
+
+    let mut decoder = Base64Decoder::new();
+    decoder.decode(&b"aGk=aGk="[..], &mut decoded).unwrap_err();
+    let mut decoder = Base64Decoder::new();
+    decoder.decode(&b"aGk="[..], &mut decoded).unwrap();
+    assert_eq!(
+        decoder.decode(&b"aG"[..], &mut decoded),
+        Err(DecodeError::InvalidPadding)
+    );
+    let mut decoder = Base64Decoder::new();
+    decoder.decode(&b"aGVsbG8g"[..], &mut decoded).unwrap();
+    assert_eq!(
+        decoder.decode(&b"d2*y"[..], &mut decoded),
+        Err(DecodeError::InvalidByte(10, b'*'))
+    );
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+fn flate_round_trip() {
+    use crate::flate::{
+        compress_into, decompress_into, Compression, DecompressWriter, Format,
+    };
+    use std::io::Write;
+
+    let data: Vec<u8> = b"squeamish ossifrage ".repeat(500);
+    for &format in &[Format::Deflate, Format::Zlib, Format::Gzip] {
+        let mut src = loosely::ChunkedBytes::with_chunk_size_hint(64);
+        for piece in data.chunks(1000) {
+            src.put_bytes(Bytes::copy_from_slice(piece));
+        }
+        let mut compressed = loosely::ChunkedBytes::with_chunk_size_hint(256);
+        compress_into(&mut src, &mut compressed, format, Compression::best())
+            .unwrap();
+        assert!(!src.has_remaining());
+        assert!(compressed.remaining() < data.len() / 10);
+
+        let mut decompressed = loosely::ChunkedBytes::new();
+        let mut w = DecompressWriter::new(&mut decompressed, format);
+        while compressed.has_remaining() {
+            let n = min(compressed.chunk().len(), 17);
+            w.write_all(&compressed.chunk()[..n]).unwrap();
+            compressed.advance(n);
+        }
+        w.finish().unwrap();
+        assert_eq!(decompressed.copy_to_bytes(decompressed.remaining()), data);
+    }
+
+    let mut compressed = loosely::ChunkedBytes::new();
+    compress_into(
+        &b"hello"[..],
+        &mut compressed,
+        Format::Gzip,
+        Default::default(),
+    )
+    .unwrap();
+    let mut corrupt = compressed.copy_to_bytes(compressed.remaining()).to_vec();
+    let last = corrupt.len() - 8;
+    corrupt[last] ^= 1;
+    let mut decompressed = loosely::ChunkedBytes::new();
+    decompress_into(&corrupt[..], &mut decompressed, Format::Gzip).unwrap_err();
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_round_trip() {
+    use crate::zstd::{
+        compress_into, decompress_into, CompressWriter, DecompressWriter,
+    };
+
+    let data: Vec<u8> = b"squeamish ossifrage ".repeat(500);
+    let mut src = loosely::ChunkedBytes::with_chunk_size_hint(64);
+    for piece in data.chunks(1000) {
+        src.put_bytes(Bytes::copy_from_slice(piece));
+    }
+    let mut compressed = loosely::ChunkedBytes::with_chunk_size_hint(256);
+    let mut w = CompressWriter::new(&mut compressed, 19).unwrap();
+    w.set_window_log(20).unwrap();
+    w.write_buf(&mut src).unwrap();
+    w.finish().unwrap();
+    assert!(!src.has_remaining());
+    assert!(compressed.remaining() < data.len() / 10);
+    let compressed = compressed.copy_to_bytes(compressed.remaining());
+
+    let mut decompressed = loosely::ChunkedBytes::new();
+    let mut w = DecompressWriter::new(&mut decompressed).unwrap();
+    w.set_window_log_max(20).unwrap();
+    for piece in compressed.chunks(17) {
+        w.write_buf(piece).unwrap();
+    }
+    w.finish().unwrap();
+    assert_eq!(decompressed.copy_to_bytes(decompressed.remaining()), data);
+
+    let mut decompressed = loosely::ChunkedBytes::new();
+    let truncated = &compressed[..compressed.len() - 1];
+    decompress_into(truncated, &mut decompressed).unwrap_err();
+
+    let mut compressed = loosely::ChunkedBytes::new();
+    compress_into(&b"hello"[..], &mut compressed, 0).unwrap();
+    compress_into(&b", world"[..], &mut compressed, 0).unwrap();
+    let mut decompressed = loosely::ChunkedBytes::new();
+    decompress_into(&mut compressed, &mut decompressed).unwrap();
+    assert_eq!(decompressed.copy_to_bytes(12), "hello, world");
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn lz4_block_per_chunk() {
+    use crate::lz4::{
+        compress_into, decompress_into, BlockMode, CompressWriter, FrameInfo,
+    };
+    use std::io::Write;
+
+    let data: Vec<u8> = b"squeamish ossifrage ".repeat(500);
+    let mut src = loosely::ChunkedBytes::with_chunk_size_hint(64);
+    for piece in data.chunks(1000) {
+        src.put_bytes(Bytes::copy_from_slice(piece));
+    }
+    let info = FrameInfo::new()
+        .block_mode(BlockMode::Linked)
+        .content_checksum(true);
+    let mut compressed = loosely::ChunkedBytes::with_chunk_size_hint(256);
+    compress_into(&mut src, &mut compressed, info).unwrap();
+    assert!(!src.has_remaining());
+    assert!(compressed.remaining() < data.len() / 10);
+    let compressed = compressed.copy_to_bytes(compressed.remaining());
+
+    let mut decompressed = loosely::ChunkedBytes::new();
+    decompress_into(&compressed[..], &mut decompressed).unwrap();
+    assert_eq!(decompressed.copy_to_bytes(decompressed.remaining()), data);
+
+    let truncated = &compressed[..compressed.len() - 1];
+    decompress_into(truncated, &mut loosely::ChunkedBytes::new()).unwrap_err();
+
+    let mut compressed = loosely::ChunkedBytes::new();
+    let mut w = CompressWriter::new(&mut compressed);
+    w.write_buf(&b"hello"[..]).unwrap();
+    w.write_all(b", world").unwrap();
+    w.finish().unwrap();
+    decompress_into(&mut compressed, &mut decompressed).unwrap();
+    assert_eq!(decompressed.copy_to_bytes(12), "hello, world");
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn wasm_copy_stops_at_max_len() {
+    use crate::wasm::put_copied;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::ptr;
+
+    let src: Vec<u8> = (0..20).collect();
+    let copy = |offset: u32, n: u32, dst: *mut u8| unsafe {
+        let piece = &src[offset as usize..(offset + n) as usize];
+        ptr::copy_nonoverlapping(piece.as_ptr(), dst, piece.len());
+    };
+    let mut buf = strictly::ChunkedBytes::with_chunk_size_limit(8);
+    buf.set_max_len(20);
+    unsafe { put_copied(&mut buf, 20, copy) };
+    assert_eq!(buf.remaining_mut(), 0);
+    catch_unwind(AssertUnwindSafe(|| unsafe {
+        put_copied(&mut buf, 1, copy)
+    }))
+    .unwrap_err();
+    assert_eq!(buf.copy_to_bytes(20), src);
 }
@@ -0,0 +1,168 @@
+//! Unix-specific I/O helpers.
+//!
+//! This module is only available on Unix platforms.
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::sync_io::MAX_IO_SLICES;
+
+use bytes::Buf;
+
+use std::convert::TryFrom;
+use std::io;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::io::IoSlice;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+// The maximum number of `iovec` entries for a single system call.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const IOV_MAX: usize = 1024;
+
+// The maximum number of messages submitted in one `sendmmsg` call.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MAX_MESSAGES: usize = 1024;
+
+// Gathers all chunks of `buf` at the end of `slices`, failing if the data
+// can't be covered by the `IOV_MAX` entries.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn gather_all<'a, B: Buf + ?Sized>(
+    buf: &'a B,
+    slices: &mut Vec<IoSlice<'a>>,
+) -> io::Result<()> {
+    let start = slices.len();
+    slices.resize(start + IOV_MAX, IoSlice::new(&[]));
+    let n = buf.chunks_vectored(&mut slices[start..]);
+    slices.truncate(start + n);
+    let len = slices[start..].iter().map(|s| s.len()).sum::<usize>();
+    if len < buf.remaining() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the message consists of too many chunks",
+        ));
+    }
+    Ok(())
+}
+
+/// Sends a batch of messages on a connected socket with `sendmmsg`.
+///
+/// The remaining data of each buffer in `msgs` is submitted as one message,
+/// gathered from its chunks. On success, returns the number of messages
+/// sent; each of the sent buffers is advanced by the number of bytes
+/// accepted for its message. Up to 1024 messages are submitted per call.
+///
+/// An error of kind `ErrorKind::InvalidInput` is returned, and no messages
+/// are sent, if the data of a message cannot be gathered into
+/// the maximum number of I/O vector entries supported by the system.
+///
+/// This function is only available on Linux and Android.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn send_mmsg<S, B>(socket: &S, msgs: &mut [B]) -> io::Result<usize>
+where
+    S: AsRawFd + ?Sized,
+    B: Buf,
+{
+    let count = msgs.len().min(MAX_MESSAGES);
+    let msgs = &mut msgs[..count];
+    let mut slices = Vec::new();
+    let mut bounds = Vec::with_capacity(msgs.len());
+    for msg in msgs.iter() {
+        let start = slices.len();
+        gather_all(msg, &mut slices)?;
+        bounds.push((start, slices.len() - start));
+    }
+    let mut headers = bounds
+        .into_iter()
+        .map(|(start, len)| {
+            // Safety: `mmsghdr` is a plain C structure, for which
+            // all zero bytes is a valid value.
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            // `IoSlice` is ABI compatible with `iovec` on Unix.
+            // The array is not modified by `sendmmsg`.
+            header.msg_hdr.msg_iov =
+                slices[start..].as_ptr() as *mut libc::iovec;
+            header.msg_hdr.msg_iovlen = len as _;
+            header
+        })
+        .collect::<Vec<_>>();
+    if headers.is_empty() {
+        return Ok(0);
+    }
+    // Safety: the headers point to valid `iovec` arrays borrowing
+    // the buffer data, which outlive the call.
+    let res = unsafe {
+        libc::sendmmsg(
+            socket.as_raw_fd(),
+            headers.as_mut_ptr(),
+            headers.len() as _,
+            0,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sent = res as usize;
+    let lens = headers[..sent]
+        .iter()
+        .map(|header| header.msg_len as usize)
+        .collect::<Vec<_>>();
+    drop(slices);
+    for (msg, len) in msgs.iter_mut().zip(lens) {
+        msg.advance(len);
+    }
+    Ok(sent)
+}
+
+pub(crate) fn write_to_at<B, F>(
+    buf: &mut B,
+    file: &F,
+    offset: u64,
+) -> io::Result<usize>
+where
+    B: Buf + ?Sized,
+    F: AsRawFd + ?Sized,
+{
+    let offset = libc::off_t::try_from(offset).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "file offset is too large")
+    })?;
+    let res = pwrite_chunks(&*buf, file.as_raw_fd(), offset);
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let bytes_written = res as usize;
+    buf.advance(bytes_written);
+    Ok(bytes_written)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn pwrite_chunks<B: Buf + ?Sized>(
+    buf: &B,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> libc::ssize_t {
+    let mut io_bufs = [IoSlice::new(&[]); MAX_IO_SLICES];
+    let io_vec_len = buf.chunks_vectored(&mut io_bufs);
+    // Safety: `IoSlice` is ABI compatible with `iovec` on Unix,
+    // and the slices borrow the buffer data for the duration of the call.
+    unsafe {
+        libc::pwritev(
+            fd,
+            io_bufs.as_ptr() as *const libc::iovec,
+            io_vec_len as _,
+            offset,
+        )
+    }
+}
+
+// Platforms without `pwritev` in `libc` fall back to writing
+// the first chunk with `pwrite`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn pwrite_chunks<B: Buf + ?Sized>(
+    buf: &B,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> libc::ssize_t {
+    let chunk = buf.chunk();
+    // Safety: the slice is valid for the duration of the call.
+    unsafe { libc::pwrite(fd, chunk.as_ptr() as *const _, chunk.len(), offset) }
+}
@@ -0,0 +1,52 @@
+#[cfg(feature = "simdutf8")]
+use simdutf8::compat::from_utf8;
+
+use std::cmp::min;
+#[cfg(not(feature = "simdutf8"))]
+use std::str::from_utf8;
+
+// Checks that the concatenation of `slices` is valid UTF-8, carrying
+// the bytes of a code point split between slices over to the next slice.
+pub(crate) fn validate<'a, I>(slices: I) -> bool
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut carry = [0; 4];
+    let mut carry_len = 0;
+    for mut slice in slices {
+        if carry_len != 0 {
+            let width = char_width(carry[0]);
+            let n = min(width - carry_len, slice.len());
+            carry[carry_len..carry_len + n].copy_from_slice(&slice[..n]);
+            carry_len += n;
+            slice = &slice[n..];
+            if carry_len < width {
+                continue;
+            }
+            if from_utf8(&carry[..width]).is_err() {
+                return false;
+            }
+            carry_len = 0;
+        }
+        if let Err(e) = from_utf8(slice) {
+            if e.error_len().is_some() {
+                return false;
+            }
+            // The slice ends with an incomplete code point
+            let tail = &slice[e.valid_up_to()..];
+            carry[..tail.len()].copy_from_slice(tail);
+            carry_len = tail.len();
+        }
+    }
+    carry_len == 0
+}
+
+// Returns the length of the UTF-8 sequence starting with `lead`,
+// which is a valid leading byte of a multi-byte sequence.
+fn char_width(lead: u8) -> usize {
+    match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
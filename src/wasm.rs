@@ -0,0 +1,79 @@
+use crate::{loosely, strictly};
+
+use bytes::{Buf, BufMut};
+use js_sys::{Array, Uint8Array};
+
+use std::cmp::min;
+use std::io::IoSlice;
+
+// Appends `len` bytes to `buf`, calling `copy` with the offset and
+// the length of each piece to write into the memory at the pointer.
+//
+// Panics if `buf` does not have room for `len` more bytes.
+//
+// Safety: `copy` must initialize the given number of bytes.
+pub(crate) unsafe fn put_copied<B, F>(buf: &mut B, len: u32, mut copy: F)
+where
+    B: BufMut,
+    F: FnMut(u32, u32, *mut u8),
+{
+    assert!(
+        buf.remaining_mut() >= len as usize,
+        "buffer overflow; remaining = {}; src = {}",
+        buf.remaining_mut(),
+        len
+    );
+    let mut offset = 0;
+    while offset < len {
+        let dst = buf.chunk_mut();
+        let n = min(dst.len(), (len - offset) as usize) as u32;
+        copy(offset, n, dst.as_mut_ptr());
+        buf.advance_mut(n as usize);
+        offset += n;
+    }
+}
+
+macro_rules! impl_wasm {
+    ($t:ty) => {
+        impl $t {
+            /// Appends the contents of a JavaScript `Uint8Array`, copying
+            /// them directly into the staging buffer.
+            ///
+            /// # Panics
+            ///
+            /// This method panics if appending the array would exceed
+            /// the maximum length set with `set_max_len`.
+            ///
+            /// This method is only available with the `wasm` feature.
+            pub fn put_uint8_array(&mut self, src: &Uint8Array) {
+                // Safety: `raw_copy_to_ptr` writes exactly `n` bytes.
+                unsafe {
+                    put_copied(self, src.length(), |offset, n, dst| {
+                        src.subarray(offset, offset + n).raw_copy_to_ptr(dst)
+                    })
+                }
+            }
+
+            /// Returns a JavaScript array of `Uint8Array` objects holding
+            /// copies of the buffered chunks, in order.
+            ///
+            /// The arrays are allocated in JavaScript memory, so they
+            /// remain valid when passed to asynchronous consumers such as
+            /// `WritableStream`, unlike views into the WebAssembly memory.
+            /// The buffer is not modified.
+            ///
+            /// This method is only available with the `wasm` feature.
+            pub fn to_uint8_arrays(&self) -> Array {
+                let mut slices = vec![IoSlice::new(&[]); self.vectored_len()];
+                let n = self.chunks_vectored(&mut slices);
+                slices[..n]
+                    .iter()
+                    .map(|slice| Uint8Array::from(&slice[..]))
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_wasm!(loosely::ChunkedBytes);
+impl_wasm!(strictly::ChunkedBytes);
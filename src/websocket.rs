@@ -0,0 +1,136 @@
+//! Writing [WebSocket] frames into `ChunkedBytes`.
+//!
+//! `FrameHeader` writes the header of a frame into the staging buffer
+//! and appends the payload. When the frame is masked, as required for
+//! frames sent by a client, the masking is applied in place to the bytes
+//! in the staging buffer and in the chunks split off it; only the payload
+//! data shared with other owners, such as `Bytes` appended without copying,
+//! are copied to be masked.
+//!
+//! [WebSocket]: https://datatracker.ietf.org/doc/html/rfc6455
+
+use crate::ChunkedBytes;
+
+use bytes::{Buf, BufMut, Bytes};
+
+/// The opcode of a WebSocket frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    /// A continuation frame of a fragmented message.
+    Continuation,
+    /// A text frame.
+    Text,
+    /// A binary frame.
+    Binary,
+    /// A connection close frame.
+    Close,
+    /// A ping frame.
+    Ping,
+    /// A pong frame.
+    Pong,
+}
+
+impl Opcode {
+    #[inline]
+    fn bits(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xa,
+        }
+    }
+}
+
+/// The header fields of a WebSocket frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// True if this is the final frame of a message.
+    pub fin: bool,
+    /// The opcode of the frame.
+    pub opcode: Opcode,
+    /// The masking key, if the payload is to be masked.
+    pub mask: Option<[u8; 4]>,
+}
+
+impl FrameHeader {
+    /// Creates the header of an unmasked final frame with the given opcode.
+    #[inline]
+    pub fn new(opcode: Opcode) -> Self {
+        FrameHeader {
+            fin: true,
+            opcode,
+            mask: None,
+        }
+    }
+
+    fn put(&self, dst: &mut ChunkedBytes, len: usize) {
+        dst.put_u8((self.fin as u8) << 7 | self.opcode.bits());
+        let mask_bit = (self.mask.is_some() as u8) << 7;
+        if len < 126 {
+            dst.put_u8(mask_bit | len as u8);
+        } else if len <= u16::MAX as usize {
+            dst.put_u8(mask_bit | 126);
+            dst.put_u16(len as u16);
+        } else {
+            dst.put_u8(mask_bit | 127);
+            dst.put_u64(len as u64);
+        }
+        if let Some(key) = &self.mask {
+            dst.put_slice(key);
+        }
+    }
+
+    /// Writes a frame with this header and the given payload into `dst`.
+    ///
+    /// The payload is appended with `put_bytes`. If the frame is masked and
+    /// the payload has not been copied, the payload is copied once to apply
+    /// the masking.
+    pub fn write_frame(&self, dst: &mut ChunkedBytes, payload: Bytes) {
+        let len = payload.len();
+        self.put(dst, len);
+        dst.put_bytes(payload);
+        if let Some(key) = self.mask {
+            apply_mask(dst, len, key);
+        }
+    }
+
+    /// Writes a frame with this header and the payload produced by
+    /// the closure `f` into `dst`.
+    ///
+    /// The closure writes the payload into a separate buffer with the same
+    /// preferred chunk size as `dst`, so that its length is known before
+    /// the header is written. If the frame is masked, the masking is applied
+    /// in place to the data written into the buffer, copying only the chunks
+    /// appended with `put_bytes` without copying. The chunks of the payload
+    /// are then appended to `dst` after the header.
+    pub fn write_frame_with<F>(&self, dst: &mut ChunkedBytes, f: F)
+    where
+        F: FnOnce(&mut ChunkedBytes),
+    {
+        let mut payload =
+            ChunkedBytes::with_chunk_size_hint(dst.chunk_size_hint());
+        f(&mut payload);
+        let len = payload.remaining();
+        if let Some(key) = self.mask {
+            apply_mask(&mut payload, len, key);
+        }
+        self.put(dst, len);
+        for chunk in payload.into_chunks() {
+            dst.put_bytes(chunk);
+        }
+    }
+}
+
+// Masks the last `len` bytes in the buffer.
+fn apply_mask(buf: &mut ChunkedBytes, len: usize, key: [u8; 4]) {
+    let mut pos = 0;
+    buf.for_each_tail_mut(len, |data| {
+        for b in data {
+            *b ^= key[pos & 3];
+            pos += 1;
+        }
+    });
+}
@@ -0,0 +1,91 @@
+//! Windows-specific I/O helpers.
+//!
+//! This module is only available on Windows.
+
+use bytes::Buf;
+use windows_sys::Win32::Networking::WinSock::WSABUF;
+
+use std::fmt;
+use std::io::IoSlice;
+use std::marker::PhantomData;
+
+/// An array of `WSABUF` entries referring to the chunks of a buffer,
+/// for vectored output with `WSASend` and similar functions.
+///
+/// The entries borrow the data of the buffer they were gathered from,
+/// so the buffer cannot be modified while the array is in use.
+pub struct WsaBufs<'a> {
+    bufs: Vec<WSABUF>,
+    len: usize,
+    _marker: PhantomData<&'a [u8]>,
+}
+
+impl<'a> WsaBufs<'a> {
+    /// Gathers all the remaining data of `buf` into an array of `WSABUF`
+    /// entries, one per chunk.
+    pub fn new<B: Buf + ?Sized>(buf: &'a B) -> Self {
+        let remaining = buf.remaining();
+        let mut slices = vec![IoSlice::new(&[]); 16];
+        loop {
+            let n = buf.chunks_vectored(&mut slices);
+            let len = slices[..n].iter().map(|s| s.len()).sum::<usize>();
+            if len == remaining || n < slices.len() {
+                slices.truncate(n);
+                break;
+            }
+            slices.resize(slices.len() * 2, IoSlice::new(&[]));
+        }
+        let bufs = slices
+            .iter()
+            .map(|s| WSABUF {
+                // `IoSlice` guarantees that the length fits on Windows
+                len: s.len() as u32,
+                buf: s.as_ptr() as *mut u8,
+            })
+            .collect();
+        WsaBufs {
+            bufs,
+            len: remaining,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the first entry of the array.
+    #[inline]
+    pub fn as_ptr(&self) -> *const WSABUF {
+        self.bufs.as_ptr()
+    }
+
+    /// Returns the array of entries as a slice.
+    #[inline]
+    pub fn as_slice(&self) -> &[WSABUF] {
+        &self.bufs
+    }
+
+    /// Returns the number of entries in the array, as passed to `WSASend`
+    /// in the `dwBufferCount` parameter.
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.bufs.len() as u32
+    }
+
+    /// Returns the total length of the data referred to by the entries.
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.len
+    }
+}
+
+// Safety: the entries only refer to the borrowed data,
+// which can be shared between threads.
+unsafe impl<'a> Send for WsaBufs<'a> {}
+unsafe impl<'a> Sync for WsaBufs<'a> {}
+
+impl<'a> fmt::Debug for WsaBufs<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WsaBufs")
+            .field("count", &self.bufs.len())
+            .field("data_len", &self.len)
+            .finish()
+    }
+}
@@ -0,0 +1,98 @@
+//! Fast non-cryptographic [xxHash] checksums of data buffered in
+//! `ChunkedBytes`.
+//!
+//! `Xxh3` and `Xxh64` are streaming hashers of the `xxhash-rust` crate
+//! wrapped to implement `digest::Update`, so that they can be fed
+//! the buffered data chunk by chunk with `hash_into`, or fed the data as it
+//! is appended through the `HashingWriter` adapter produced by
+//! `hashing_writer`. The methods `xxh3_64` and `xxh64` of `ChunkedBytes`
+//! compute the checksums of the buffered data in one call. These are
+//! suitable for deduplication and cache keys, where a cryptographic hash
+//! is not needed.
+//!
+//! This module is only available with the `xxhash` feature.
+//!
+//! [xxHash]: https://xxhash.com/
+
+use std::fmt;
+
+/// A streaming XXH3 hasher.
+#[derive(Clone, Default)]
+pub struct Xxh3(xxhash_rust::xxh3::Xxh3);
+
+impl Xxh3 {
+    /// Creates a hasher with the default seed of 0.
+    #[inline]
+    pub fn new() -> Self {
+        Xxh3(xxhash_rust::xxh3::Xxh3::new())
+    }
+
+    /// Creates a hasher with the given seed.
+    #[inline]
+    pub fn with_seed(seed: u64) -> Self {
+        Xxh3(xxhash_rust::xxh3::Xxh3::with_seed(seed))
+    }
+
+    /// Returns the 64-bit hash of the data fed so far.
+    #[inline]
+    pub fn digest(&self) -> u64 {
+        self.0.digest()
+    }
+
+    /// Returns the 128-bit hash of the data fed so far.
+    #[inline]
+    pub fn digest128(&self) -> u128 {
+        self.0.digest128()
+    }
+}
+
+impl digest::Update for Xxh3 {
+    #[inline]
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
+
+impl fmt::Debug for Xxh3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Xxh3").finish()
+    }
+}
+
+/// A streaming XXH64 hasher.
+#[derive(Clone)]
+pub struct Xxh64(xxhash_rust::xxh64::Xxh64);
+
+impl Default for Xxh64 {
+    #[inline]
+    fn default() -> Self {
+        Xxh64::new(0)
+    }
+}
+
+impl Xxh64 {
+    /// Creates a hasher with the given seed.
+    #[inline]
+    pub fn new(seed: u64) -> Self {
+        Xxh64(xxhash_rust::xxh64::Xxh64::new(seed))
+    }
+
+    /// Returns the hash of the data fed so far.
+    #[inline]
+    pub fn digest(&self) -> u64 {
+        self.0.digest()
+    }
+}
+
+impl digest::Update for Xxh64 {
+    #[inline]
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
+
+impl fmt::Debug for Xxh64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Xxh64").finish()
+    }
+}
@@ -0,0 +1,235 @@
+//! Streaming [Zstandard] compression into and decompression
+//! from `ChunkedBytes`.
+//!
+//! `CompressWriter` and `DecompressWriter` are writers feeding the data
+//! through a streaming encoder or decoder of the `zstd` crate, appending
+//! the output to `ChunkedBytes`. As in the `flate` module, the input can be
+//! written from any `Buf` chunk by chunk with `write_buf`, and the functions
+//! `compress_into` and `decompress_into` process the whole of the input
+//! in one call. The compression level and the window size are configurable.
+//!
+//! This module is only available with the `zstd` feature.
+//!
+//! [Zstandard]: https://datatracker.ietf.org/doc/html/rfc8878
+
+use crate::ChunkedBytes;
+
+use ::zstd::stream::raw::{self, CParameter, DParameter};
+use ::zstd::stream::zio;
+use bytes::buf::{BufMut, Writer};
+use bytes::Buf;
+
+use std::fmt;
+use std::io::{self, Write};
+
+type Output<'a> = Writer<&'a mut ChunkedBytes>;
+
+// Writes the data remaining in `src` chunk by chunk.
+fn write_buf<B: Buf>(w: &mut dyn Write, mut src: B) -> io::Result<()> {
+    while src.has_remaining() {
+        let chunk = src.chunk();
+        let len = chunk.len();
+        w.write_all(chunk)?;
+        src.advance(len);
+    }
+    Ok(())
+}
+
+/// A writer compressing the data written through it into `ChunkedBytes`.
+///
+/// The compressed output is appended to the buffer as it is produced
+/// by the encoder. The end of the frame is written by `finish`, which must
+/// be called to complete the output.
+pub struct CompressWriter<'a> {
+    inner: zio::Writer<Output<'a>, raw::Encoder<'static>>,
+}
+
+impl<'a> CompressWriter<'a> {
+    /// Creates a writer compressing into `dst` with the given compression
+    /// level. Level 0 selects the default level of the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoder could not be created.
+    pub fn new(dst: &'a mut ChunkedBytes, level: i32) -> io::Result<Self> {
+        let encoder = raw::Encoder::new(level)?;
+        Ok(CompressWriter {
+            inner: zio::Writer::new(dst.writer(), encoder),
+        })
+    }
+
+    /// Sets the base 2 logarithm of the window size, the maximum distance
+    /// of back-references. A larger window can improve the compression
+    /// ratio on repetitive data, at the cost of memory on both sides;
+    /// the decompressor must accept a window at least as large.
+    ///
+    /// This should be called before any data is written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is out of the range supported
+    /// by the library.
+    pub fn set_window_log(&mut self, log: u32) -> io::Result<()> {
+        self.inner
+            .operation_mut()
+            .set_parameter(CParameter::WindowLog(log))
+    }
+
+    /// Consumes the data remaining in `src`, feeding it to the encoder
+    /// chunk by chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed output does not fit into
+    /// the maximum length of the buffer.
+    pub fn write_buf<B: Buf>(&mut self, src: B) -> io::Result<()> {
+        write_buf(&mut self.inner, src)
+    }
+
+    /// Completes the compressed frame, appending the remaining output
+    /// to the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed output does not fit into
+    /// the maximum length of the buffer.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+impl<'a> Write for CompressWriter<'a> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a> fmt::Debug for CompressWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressWriter")
+            .field("dst", self.inner.writer().get_ref())
+            .finish()
+    }
+}
+
+/// A writer decompressing the data written through it into `ChunkedBytes`.
+///
+/// The decompressed output is appended to the buffer as it is produced
+/// by the decoder. Consecutive frames are decompressed as one stream.
+/// `finish` should be called after the whole of the compressed data
+/// has been written to flush the remaining output and to check that
+/// the last frame is complete.
+pub struct DecompressWriter<'a> {
+    inner: zio::Writer<Output<'a>, raw::Decoder<'static>>,
+}
+
+impl<'a> DecompressWriter<'a> {
+    /// Creates a writer decompressing into `dst`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the decoder could not be created.
+    pub fn new(dst: &'a mut ChunkedBytes) -> io::Result<Self> {
+        let decoder = raw::Decoder::new()?;
+        Ok(DecompressWriter {
+            inner: zio::Writer::new(dst.writer(), decoder),
+        })
+    }
+
+    /// Sets the base 2 logarithm of the largest window size to accept.
+    /// Frames compressed with a larger window are rejected to limit
+    /// the memory used by the decoder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is out of the range supported
+    /// by the library.
+    pub fn set_window_log_max(&mut self, log: u32) -> io::Result<()> {
+        self.inner
+            .operation_mut()
+            .set_parameter(DParameter::WindowLogMax(log))
+    }
+
+    /// Consumes the data remaining in `src`, feeding it to the decoder
+    /// chunk by chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressed data is corrupt or requires
+    /// a larger window than allowed, or if the decompressed output does not
+    /// fit into the maximum length of the buffer.
+    pub fn write_buf<B: Buf>(&mut self, src: B) -> io::Result<()> {
+        write_buf(&mut self.inner, src)
+    }
+
+    /// Flushes the remaining decompressed output to the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the last frame is incomplete, or if
+    /// the decompressed output does not fit into the maximum length
+    /// of the buffer.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+impl<'a> Write for DecompressWriter<'a> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a> fmt::Debug for DecompressWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecompressWriter")
+            .field("dst", self.inner.writer().get_ref())
+            .finish()
+    }
+}
+
+/// Consumes the data remaining in `src`, appending it to `dst` compressed
+/// as a complete frame with the given compression level.
+///
+/// # Errors
+///
+/// Returns an error if the encoder could not be created, or if
+/// the compressed output does not fit into the maximum length of `dst`.
+pub fn compress_into<B: Buf>(
+    src: B,
+    dst: &mut ChunkedBytes,
+    level: i32,
+) -> io::Result<()> {
+    let mut w = CompressWriter::new(dst, level)?;
+    w.write_buf(src)?;
+    w.finish()
+}
+
+/// Consumes the data remaining in `src` as compressed frames, appending
+/// the decompressed data to `dst`.
+///
+/// # Errors
+///
+/// Returns an error if the decoder could not be created, if the compressed
+/// data is corrupt or incomplete, or if the decompressed output does not
+/// fit into the maximum length of `dst`.
+pub fn decompress_into<B: Buf>(
+    src: B,
+    dst: &mut ChunkedBytes,
+) -> io::Result<()> {
+    let mut w = DecompressWriter::new(dst)?;
+    w.write_buf(src)?;
+    w.finish()
+}